@@ -1,20 +1,51 @@
 import WebSocket, { WebSocketServer } from 'ws';
+import { randomUUID } from 'crypto';
 
 const PORT = process.env.PORT ? parseInt(process.env.PORT) : 8080;
 interface User {
     ws: WebSocket;
-    nick: String;
+    nick: string;
     isAlive: boolean;
 }
 
 interface Message {
-    messageType: String;
-    data: String;
-    dataArray: String[];
+    messageType: string;
+    data: string;
+    dataArray: string[];
+    id?: string;
+    attachment?: unknown;
+    channel?: string;
 }
 
 let users: User[] = [];
 
+// Recent message payloads, oldest first, for clients paging back through
+// history.
+const HISTORY_LIMIT = 500;
+const MAX_HISTORY_PAGE = 100;
+let history: { id: string; from: string; message: string }[] = [];
+
+// Server-defined emoji as a JSON object of shortcode to image URL, e.g.
+// CUSTOM_EMOJI='{"partyparrot":"https://example.com/parrot.gif"}'.
+const customEmoji: Record<string, string> = (() => {
+    try {
+        return process.env.CUSTOM_EMOJI ? JSON.parse(process.env.CUSTOM_EMOJI) : {};
+    } catch (e) {
+        console.log('Ignoring invalid CUSTOM_EMOJI', e);
+        return {};
+    }
+})();
+
+// Advertised to clients with the user list so they can show "5 / 20".
+const ROOM_CAPACITY = process.env.ROOM_CAPACITY ? parseInt(process.env.ROOM_CAPACITY) : undefined;
+
+const usersFrame = () =>
+    JSON.stringify({
+        messageType: 'users',
+        dataArray: users.map((u) => u.nick),
+        data: ROOM_CAPACITY ? String(ROOM_CAPACITY) : undefined,
+    });
+
 console.log(`Listening on port ${PORT}`);
 const wss = new WebSocketServer({ port: PORT });
 
@@ -27,23 +58,256 @@ wss.on('connection', (ws: WebSocket) => {
             const parsed_data: Message = JSON.parse(raw_data);
             switch (parsed_data.messageType) {
                 case 'register':
+                    // Names must be unique among live connections. A user who
+                    // is reconnecting has their old socket closed already.
+                    const taken = users.some(
+                        (u) => u.nick === parsed_data.data && u.ws !== ws && u.ws.readyState === WebSocket.OPEN
+                    );
+                    if (taken) {
+                        ws.send(
+                            JSON.stringify({
+                                messageType: 'registererror',
+                                data: `The name "${parsed_data.data}" is already taken.`,
+                            })
+                        );
+                        break;
+                    }
                     users.push({ ws, nick: parsed_data.data, isAlive: true });
-                    broadcast(JSON.stringify({ messageType: 'users', dataArray: users.map((u) => u.nick) }));
+                    ws.send(JSON.stringify({ messageType: 'customemoji', data: JSON.stringify(customEmoji) }));
+                    broadcast(usersFrame());
                     break;
                 case 'message':
                     const sender = users.find((u) => u.ws === ws);
                     if (sender) {
+                        const message = {
+                            // Clients send their own id so they can match the
+                            // echo against the copy they already show.
+                            id: typeof parsed_data.id === 'string' ? parsed_data.id : randomUUID(),
+                            from: sender.nick,
+                            message: parsed_data.data,
+                            // File details for messages that carry one; passed on untouched.
+                            attachment:
+                                typeof parsed_data.attachment === 'object' ? parsed_data.attachment : undefined,
+                            // Left out for the general room.
+                            channel: typeof parsed_data.channel === 'string' ? parsed_data.channel : undefined,
+                            time: Date.now(),
+                        };
+                        history.push(message);
+                        if (history.length > HISTORY_LIMIT) {
+                            history = history.slice(-HISTORY_LIMIT);
+                        }
+                        broadcast(JSON.stringify({ messageType: 'message', data: JSON.stringify(message) }));
+                    }
+                    break;
+                case 'poll':
+                    const author = users.find((u) => u.ws === ws);
+                    if (author) {
+                        const poll = JSON.parse(String(parsed_data.data));
+                        broadcast(
+                            JSON.stringify({
+                                messageType: 'poll',
+                                data: JSON.stringify({
+                                    id: typeof parsed_data.id === 'string' ? parsed_data.id : randomUUID(),
+                                    from: author.nick,
+                                    question: poll.question,
+                                    options: poll.options,
+                                    time: Date.now(),
+                                }),
+                            })
+                        );
+                    }
+                    break;
+                // Pieces of a message written as it's produced, e.g. by a bot.
+                // Stamped with the sender like a whole message would be.
+                case 'messagechunk':
+                    const streamer = users.find((u) => u.ws === ws);
+                    if (streamer) {
+                        const chunk = JSON.parse(String(parsed_data.data));
                         broadcast(
                             JSON.stringify({
-                                messageType: 'message',
+                                messageType: 'messagechunk',
                                 data: JSON.stringify({
-                                    from: sender.nick,
-                                    message: parsed_data.data,
+                                    id: String(chunk.id),
+                                    from: streamer.nick,
+                                    delta: String(chunk.delta ?? ''),
+                                    done: chunk.done === true,
                                     time: Date.now(),
                                 }),
                             })
                         );
                     }
+                    break;
+                // A page of messages older than `beforeId`, or the newest
+                // ones without it. Only the asking client gets the answer.
+                case 'history':
+                    const request = JSON.parse(String(parsed_data.data));
+                    const limit = Math.min(Math.max(Number(request.limit) || 0, 1), MAX_HISTORY_PAGE);
+                    const end = request.beforeId
+                        ? Math.max(history.findIndex((m) => m.id === request.beforeId), 0)
+                        : history.length;
+                    const start = Math.max(end - limit, 0);
+                    ws.send(
+                        JSON.stringify({
+                            messageType: 'history',
+                            dataArray: history.slice(start, end).map((m) => JSON.stringify(m)),
+                            data: JSON.stringify({ hasMore: start > 0 }),
+                        })
+                    );
+                    break;
+                // A client that reconnected asks for the current list.
+                case 'users':
+                    ws.send(usersFrame());
+                    break;
+                // Answers with our clock so the client can work out how far
+                // its own is off.
+                case 'timesync':
+                    const sync = JSON.parse(String(parsed_data.data));
+                    ws.send(
+                        JSON.stringify({
+                            messageType: 'timesync',
+                            data: JSON.stringify({ clientTime: Number(sync.clientTime), serverTime: Date.now() }),
+                        })
+                    );
+                    break;
+                // New text for a message, accepted only from whoever sent it
+                // and relayed with their name rather than the one claimed.
+                case 'edit':
+                    const editor = users.find((u) => u.ws === ws);
+                    if (editor) {
+                        const edit = JSON.parse(String(parsed_data.data));
+                        const original = history.find((m) => m.id === edit.message_id);
+                        if (original && original.from !== editor.nick) {
+                            break;
+                        }
+                        if (original) {
+                            original.message = String(edit.text);
+                        }
+                        broadcast(
+                            JSON.stringify({
+                                messageType: 'edit',
+                                data: JSON.stringify({
+                                    username: editor.nick,
+                                    message_id: String(edit.message_id),
+                                    text: String(edit.text),
+                                }),
+                            })
+                        );
+                    }
+                    break;
+                // Messages taken back by whoever sent them. Ids of anyone
+                // else's messages are ignored by every client.
+                case 'delete':
+                    const deleter = users.find((u) => u.ws === ws);
+                    if (deleter) {
+                        const deletion = JSON.parse(String(parsed_data.data));
+                        const ids: string[] = Array.isArray(deletion.message_ids) ? deletion.message_ids.map(String) : [];
+                        history = history.filter((m) => m.from !== deleter.nick || !ids.includes(m.id));
+                        broadcast(
+                            JSON.stringify({
+                                messageType: 'delete',
+                                data: JSON.stringify({ username: deleter.nick, message_ids: ids }),
+                            })
+                        );
+                    }
+                    break;
+                // Reports go to the server log for whoever moderates it;
+                // nobody else is told.
+                case 'report':
+                    const reporter = users.find((u) => u.ws === ws);
+                    if (reporter) {
+                        console.log('Report from', reporter.nick, String(parsed_data.data));
+                    }
+                    break;
+                // Votes are relayed with the voter's name rather than the
+                // one claimed, so nobody can vote on someone else's behalf.
+                case 'vote':
+                    const voter = users.find((u) => u.ws === ws);
+                    if (voter) {
+                        const vote = JSON.parse(String(parsed_data.data));
+                        broadcast(
+                            JSON.stringify({
+                                messageType: 'vote',
+                                data: JSON.stringify({
+                                    username: voter.nick,
+                                    poll_id: String(vote.poll_id),
+                                    option: Number(vote.option),
+                                }),
+                            })
+                        );
+                    }
+                    break;
+                // Reactions too are relayed with the reactor's own name.
+                case 'reaction':
+                    const reactor = users.find((u) => u.ws === ws);
+                    if (reactor) {
+                        const reaction = JSON.parse(String(parsed_data.data));
+                        broadcast(
+                            JSON.stringify({
+                                messageType: 'reaction',
+                                data: JSON.stringify({
+                                    username: reactor.nick,
+                                    message_id: String(reaction.message_id),
+                                    emoji: String(reaction.emoji),
+                                    added: Boolean(reaction.added),
+                                }),
+                            })
+                        );
+                    }
+                    break;
+                // Read receipts are relayed with the reader's own name, so
+                // nobody can mark messages read for someone else.
+                case 'read':
+                    const reader = users.find((u) => u.ws === ws);
+                    if (reader) {
+                        const receipt = JSON.parse(String(parsed_data.data));
+                        broadcast(
+                            JSON.stringify({
+                                messageType: 'read',
+                                data: JSON.stringify({
+                                    username: reader.nick,
+                                    message_id: String(receipt.message_id),
+                                }),
+                            })
+                        );
+                    }
+                    break;
+                // Presence too, so nobody can mark someone else away.
+                case 'presence':
+                    const present = users.find((u) => u.ws === ws);
+                    if (present) {
+                        const presence = JSON.parse(String(parsed_data.data));
+                        broadcast(
+                            JSON.stringify({
+                                messageType: 'presence',
+                                data: JSON.stringify({
+                                    username: present.nick,
+                                    status: String(presence.status),
+                                }),
+                            })
+                        );
+                    }
+                    break;
+                // Pins are relayed with the pinner's own name.
+                case 'pin':
+                    const pinner = users.find((u) => u.ws === ws);
+                    if (pinner) {
+                        const pin = JSON.parse(String(parsed_data.data));
+                        broadcast(
+                            JSON.stringify({
+                                messageType: 'pin',
+                                data: JSON.stringify({
+                                    username: pinner.nick,
+                                    message_id: String(pin.message_id),
+                                    pinned: Boolean(pin.pinned),
+                                }),
+                            })
+                        );
+                    }
+                    break;
+                // Typing frames carry their own payload and are relayed as-is.
+                case 'typing':
+                    broadcast(raw_data);
+                    break;
             }
         } catch (e) {
             console.log('Error in message', e);
@@ -56,7 +320,7 @@ const interval = setInterval(function ping() {
     const updated_users = users.filter((u) => current_clients.includes(u.ws));
     if (updated_users.length !== users.length) {
         users = updated_users;
-        broadcast(JSON.stringify({ messageType: 'users', dataArray: users.map((u) => u.nick) }));
+        broadcast(usersFrame());
     }
 }, 5000);
 
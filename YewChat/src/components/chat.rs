@@ -1,65 +1,340 @@
-use serde::{Deserialize, Serialize};
-use web_sys::{HtmlInputElement, KeyboardEvent};
+use gloo::events::EventListener;
+use gloo::timers::callback::{Interval, Timeout};
+use std::collections::{HashMap, HashSet};
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlInputElement, HtmlTextAreaElement, KeyboardEvent};
 use yew::prelude::*;
 use yew_agent::{Bridge, Bridged};
+use yew_router::prelude::*;
 
-use crate::{User, services::websocket::WebsocketService};
-use crate::services::event_bus::EventBus;
+use crate::{Route, User, services::websocket::{seconds_until, Backoff, WebsocketService}};
+use crate::components::chat_state::{
+    answer_clear, can_edit, deletable_ids, display_name, is_typing, message_frame, presence_frame, queue_frame, quote_prefill,
+    register_frame, reject_name, rejoin_frames, should_autofocus, startup_timed_out, toggle_selection,
+    without_blocked, ChatState, ComposerMode, DeleteUpdate, EditUpdate, HistoryRequest, Incoming, MessageData,
+    MsgTypes, PinUpdate, ReactionUpdate, ReadReceipt, ReportFrame, StreamState, TimeSync, TypingStatus,
+    VoteUpdate, WebSocketMessage,
+};
+use crate::components::message_body::render_body;
+use crate::config::{
+    debug_mode, idle_after_ms, link_preview_endpoint, max_reconnect_attempts,
+    edit_window_ms, presence_heartbeat_ms, query_param, translate_endpoint, upload_endpoint, COLLAPSE_MAX_CHARS,
+    COLLAPSE_MAX_LINES, HISTORY_PAGE_SIZE, IDLE_CHECK_MS, INACTIVITY_GRACE_MS,
+    INACTIVITY_PROMPT_MS, MAX_ATTACHMENT_BYTES, READ_RECEIPT_THROTTLE_MS,
+    SCROLL_THROTTLE_MS, SESSION_TICK_MS, STARTUP_TIMEOUT_MS, TOAST_DURATION_MS, TYPING_IDLE_MS,
+};
+use crate::services::clipboard::copy_text;
+use crate::services::download::download_text;
+use crate::services::emoji_support::is_emoji_supported;
+use crate::services::event_bus::{BusEvent, ChatEvent, EventBus, Request};
+use crate::services::link_preview::{fetch_preview, LinkPreview};
+use crate::services::translate::{browser_language, translate, TranslationCache};
+use crate::services::notification;
+use crate::services::preload::preload_image;
+use crate::services::settings::Settings;
+use crate::services::upload::upload_file;
+use crate::utils::actions::{available_actions, MessageAction};
+use crate::utils::attachment::{classify_drop, format_size, icon_for_mime, Attachment, DropDecision};
+use crate::utils::avatar::{accent_for_name, added_avatar_urls, avatar_overflow, avatar_url};
+use crate::utils::diagnostics::Diagnostics;
+use crate::utils::emoji::{
+    apply_skin_tone, expand_shortcodes, push_recent, supported_emoji, Emoji, EMOJIS, RECENT_EMOJI_LIMIT,
+};
+use crate::utils::grouping::{grouped_times, should_group};
+use crate::utils::inactivity::{Inactivity, InactivityTimer};
+use crate::utils::invite::{invite_url, message_link, parse_message_fragment};
+use crate::utils::notify::{should_notify, NotifyMode};
+use crate::utils::outbox::Outbox;
+use crate::utils::pane::{pane_state, PaneState};
+use crate::utils::pins::resolve_pins;
+use crate::utils::poll::{parse_poll_command, Poll};
+use crate::utils::presence::{occupancy_label, sort_users, IdleTracker, PresenceStatus};
+use crate::utils::reactions::{quick_react_step, reactors_summary, QUICK_REACTIONS};
+use crate::utils::receipts::seen_summary;
+use crate::utils::report::ReportReason;
+use crate::utils::scroll::{
+    prepended_scroll_top, scroll_position, ScrollMemory, ScrollPosition, Throttle, HISTORY_THRESHOLD_PX,
+};
+use crate::utils::send_key::is_send;
+use crate::utils::style::{
+    avatar_class, bubble_classes, composer_height, density_classes, is_narrow, picker_placement, sidebar_class,
+    DensityClasses, Placement, COMPOSER_MAX_HEIGHT_PX, PICKER_MAX_HEIGHT,
+};
+use crate::utils::text::{
+    collapsed_preview, first_url, format_quote, is_safe_url, message_stats, normalize_message,
+    parse_fenced, should_collapse, wrap_code, MessageStats,
+};
+use crate::utils::time::{
+    clock_offset, day_label, format_duration, format_time, full_timestamp, ms_until_midnight, same_day,
+    timestamp_class, TimeFormat, TimestampMode,
+};
+use crate::utils::toast::{ToastKind, ToastQueue};
+use crate::utils::typing::TypingStateMachine;
+use crate::utils::unread::{divider_index, ConversationId, UnreadCounts, GENERAL};
 
 pub enum Msg {
     HandleMsg(String),
     SubmitMessage,
     InputChanged,
+    TypingIdle,
     ToggleEmojiPicker,
     SelectEmoji(String),
+    SetDragging(bool),
+    FileDropped(web_sys::File),
+    FileUploaded(Option<Attachment>),
     HandleKeyDown(KeyboardEvent),
+    ToggleTimeFormat,
+    ToggleSettings,
+    ToggleSidebar,
+    ViewportResized,
+    ToggleDiagnostics,
+    CopyDiagnostics,
+    ToggleDensity,
+    CycleNotifyMode,
+    CycleNotificationSound,
+    CycleGroupWindow,
+    CycleSkinTone,
+    ToggleSendKey,
+    ToggleUserSort,
+    ToggleAvatarShape,
+    CycleTimestampMode,
+    ToggleSessionReset,
+    SessionTick,
+    ToggleCodeMode,
+    SetCodeLanguage(String),
+    UserActivity,
+    InactivityPrompt,
+    StayConnected,
+    CheckIdle,
+    Heartbeat,
+    TabVisible,
+    NewDay,
+    SwitchConversation(ConversationId),
+    RequestClear,
+    // Selection mode: clicking messages picks them for the toolbar's actions.
+    ToggleSelect(String),
+    ExitSelectMode,
+    CopySelected,
+    QuoteSelected,
+    ExportSelected,
+    DeleteSelected,
+    ConfirmClear,
+    CancelClear,
+    SelectionChanged { from: String },
+    QuoteSelection { from: String, text: String },
+    LinkPreviewLoaded { message_id: String, preview: Option<LinkPreview> },
+    TranslateMessage(String),
+    TranslationLoaded { message_id: String, original: String, target: String, text: Option<String> },
+    ToggleTranslation(String),
+    ConnectionChanged {
+        connection: u32,
+        connected: bool,
+        detail: Option<String>,
+    },
+    Reconnect,
+    CountdownTick,
+    StartupTimedOut,
+    ForceReconnect,
+    MessagesScrolled,
+    ScrollPositionChanged(ScrollPosition),
+    CheckRead,
+    SendReadReceipt,
+    WindowBlurred,
+    JumpToUnread,
+    ToggleReaction { message_id: String, emoji: String },
+    FocusMessage(String),
+    BlurMessage(String),
+    MessageKeyDown { message_id: String, event: KeyboardEvent },
+    TogglePin(String),
+    TogglePinnedPanel,
+    JumpToMessage(String),
+    FocusUser(String),
+    ClearUserHighlight,
+    HighlightMessage(String),
+    ClearMessageHighlight,
+    SetSearch(String),
+    CopyInviteLink,
+    PushToast { text: String, kind: ToastKind },
+    DismissToast(u32),
+    ExpireToasts,
+    OpenContextMenu { message_id: String, x: f64, y: f64 },
+    CloseContextMenu,
+    SubmitReport(ReportReason),
+    CancelReport,
+    BlockUser(String),
+    UnblockUser(String),
+    RevealBlocked(String),
+    MenuAction { message_id: String, action: MessageAction },
+    ToggleExpand(String),
+    Vote { poll_id: String, option: usize },
 }
 
-#[derive(Deserialize, Clone)]
-struct MessageData {
-    from: String,
-    message: String,
-    timestamp: Option<String>, // Added timestamp field
-}
+// Avatars shown next to the typing indicator before collapsing into "+N".
+const MAX_TYPING_AVATARS: usize = 3;
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename_all = "lowercase")]
-pub enum MsgTypes {
-    Users,
-    Register,
-    Message,
-    Typing, // Added typing message type
-}
+// Reader avatars shown under a message before collapsing into "+N".
+const MAX_READER_AVATARS: usize = 5;
+
+// Toasts stacked on screen at once; older ones make way for new ones.
+const MAX_TOASTS: usize = 3;
 
-#[derive(Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct WebSocketMessage {
-    message_type: MsgTypes,
-    data_array: Option<Vec<String>>,
-    data: Option<String>,
+// Names listed in a reaction's tooltip before "and N more".
+const MAX_REACTORS_LISTED: usize = 10;
+
+// How long a sidebar entry or linked message stays highlighted after
+// jumping to it.
+const USER_HIGHLIGHT_MS: u32 = 2_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ConnectionState {
+    Connecting,
+    Connected,
+    Disconnected,
+    // Stopped retrying on its own; waits for the user to resume.
+    GaveUp,
+    // Closed because nobody answered the inactivity prompt.
+    Suspended,
 }
 
-#[derive(Serialize, Deserialize)]
-struct TypingStatus {
-    username: String,
-    is_typing: bool,
+// Actions menu for one message, opened by right-click or its "⋯" button.
+struct ContextMenu {
+    message_id: String,
+    x: f64,
+    y: f64,
 }
 
-#[derive(Clone)]
-struct UserProfile {
-    name: String,
-    avatar: String,
+// Floating "Quote" button shown next to text selected inside a message.
+struct QuotePopover {
+    from: String,
+    text: String,
+    x: f64,
+    y: f64,
 }
 
 pub struct Chat {
-    users: Vec<UserProfile>,
+    state: ChatState,
+    // Avatar URLs already requested, so each is only preloaded once.
+    preloaded_avatars: Vec<String>,
     chat_input: NodeRef,
+    message_list: NodeRef,
     wss: WebsocketService,
-    messages: Vec<MessageData>,
-    _producer: Box<dyn Bridge<EventBus>>,
-    typing_users: Vec<String>,       // Added to track who's typing
+    connection: ConnectionState,
+    backoff: Backoff,
+    reconnect_timer: Option<Timeout>,
+    // When the scheduled reconnect fires, and a once-a-second tick that keeps
+    // the banner's countdown moving until then.
+    next_attempt_at: Option<f64>,
+    countdown: Option<Interval>,
+    max_reconnects: u32,
+    // Fires if the first connection never gets as far as a user list, when
+    // it was armed, and whether it did.
+    startup_timer: Option<Timeout>,
+    startup_at: f64,
+    startup_failed: bool,
+    // Why the last connection dropped, shown in debug mode.
+    last_disconnect: Option<String>,
+    // Texts of pending messages that couldn't be sent yet, by message id.
+    outbox: Outbox<String>,
+    producer: Box<dyn Bridge<EventBus>>,
     show_emoji_picker: bool,         // Added for emoji picker
-    typing_timeout: Option<i32>,     // For debouncing typing events
+    // The picker's emoji minus any this platform would draw as empty boxes.
+    picker_emoji: Vec<&'static Emoji>,
+    // A file is being dragged over the message pane.
+    dragging: bool,
+    emoji_placement: Placement,
+    typing: TypingStateMachine,
+    // Sends the pending "stopped typing" once the composer goes quiet.
+    // Replaced on every keystroke, which cancels the previous one.
+    typing_timeout: Option<Timeout>,
+    // Set while a page of older messages is on its way.
+    loading_history: bool,
+    // The list's scroll top and height before older messages were added, so
+    // the next render can keep the same messages in view.
+    history_anchor: Option<(f64, f64)>,
+    unread: UnreadCounts,
+    // Every conversation seen so far, in the order they turned up.
+    conversations: Vec<ConversationId>,
+    settings: Settings,
+    show_settings: bool,
+    // Hidden panel for bug reports, opened with Ctrl+Alt+D.
+    show_diagnostics: bool,
+    confirm_clear: bool,
+    // Selection mode, and the ids of the messages picked in it.
+    selecting: bool,
+    selected_ids: HashSet<String>,
+    quote_popover: Option<QuotePopover>,
+    context_menu: Option<ContextMenu>,
+    // The message whose report dialog is open.
+    report_target: Option<String>,
+    // Messages from blocked users that were opened anyway, by id.
+    revealed_blocked: HashSet<String>,
+    input_stats: MessageStats,
+    composer_mode: ComposerMode,
+    code_language: String,
+    // Keyed by message id. `None` while loading or when there's nothing to show.
+    link_previews: HashMap<String, Option<LinkPreview>>,
+    // Translations by message id; `None` while one is being fetched.
+    translations: HashMap<String, Option<String>>,
+    translation_cache: TranslationCache,
+    // Translated messages switched back to their original text.
+    showing_original: HashSet<String>,
+    username: String,
+    user: Option<User>,
+    // False when mounted without a user to chat as; only a login prompt is shown.
+    logged_in: bool,
+    debug: bool,
+    idle: IdleTracker,
+    inactivity: InactivityTimer,
+    // Sidebar entry flashed after clicking a sender's name, and the timer
+    // that clears it.
+    highlighted_user: Option<String>,
+    user_highlight_timer: Option<Timeout>,
+    // The message a `#msg-<id>` link opened the app on, until it's been
+    // scrolled to, and the message highlighted after arriving there.
+    link_target: Option<String>,
+    highlighted_message: Option<String>,
+    message_highlight_timer: Option<Timeout>,
+    // Message with keyboard focus, and the quick reaction highlighted for it.
+    focused_message: Option<String>,
+    quick_react_index: usize,
+    show_pinned: bool,
+    search_query: String,
+    toasts: ToastQueue,
+    // Fires when the next toast is due to be dismissed.
+    toast_timer: Option<Timeout>,
+    // Long messages the user has opened with "Show more", by id.
+    expanded: HashSet<String>,
+    scroll_memory: ScrollMemory,
+    // Where the message list is, or should be put on the next render. Every
+    // scroll-dependent feature reads this instead of measuring the DOM.
+    scroll: ScrollPosition,
+    restore_scroll: bool,
+    // Set once the composer has been focused after mounting.
+    input_autofocused: bool,
+    scroll_throttle: Throttle,
+    // Catches the last scroll event of a burst that the throttle held back.
+    scroll_trailing: Option<Timeout>,
+    last_read_sent: Option<String>,
+    // The last message we'd read when the window lost focus. Anything after
+    // it sits below a "New messages" divider until it has been seen.
+    unread_marker: Option<String>,
+    read_check: Option<Timeout>,
+    // Window events; none are attached until someone is logged in.
+    _listeners: Vec<EventListener>,
+    // Whether the viewport is too narrow for the sidebar column, and whether
+    // the sidebar drawer that replaces it is open.
+    narrow: bool,
+    sidebar_open: bool,
+    _idle_check: Option<Interval>,
+    _heartbeat: Option<Interval>,
+    // When this session connected, for the "online for" label, which a
+    // once-a-minute tick keeps current.
+    connected_since: f64,
+    _session_tick: Option<Interval>,
+    // How far the server's clock is ahead of ours, measured on connecting.
+    clock_offset: f64,
+    // Fires at local midnight so "Today"/"Yesterday" labels move on.
+    midnight_timer: Option<Timeout>,
 }
 
 impl Component for Chat {
@@ -67,346 +342,3553 @@ impl Component for Chat {
     type Properties = ();
 
     fn create(ctx: &Context<Self>) -> Self {
-        let (user, _) = ctx
-            .link()
-            .context::<User>(Callback::noop())
-            .expect("context to be set");
-        let wss = WebsocketService::new();
-        let username = user.username.borrow().clone();
+        let context = ctx.link().context::<User>(Callback::noop());
+        let session = session_username(context.as_ref().map(|(user, _)| user));
+        let username = session.clone().unwrap_or_default();
+        // `?channel=` (as put in invite links) opens on that channel.
+        let initial_conversation = query_param("channel")
+            .filter(|channel| !channel.is_empty())
+            .unwrap_or_else(|| GENERAL.to_string());
 
-        let message = WebSocketMessage {
-            message_type: MsgTypes::Register,
-            data: Some(username.to_string()),
-            data_array: None,
+        let mut chat = Self {
+            state: ChatState::default(),
+            preloaded_avatars: vec![],
+            outbox: Outbox::default(),
+            chat_input: NodeRef::default(),
+            message_list: NodeRef::default(),
+            // Replaced by a real socket below once we know who is chatting.
+            wss: WebsocketService::offline(&WebsocketService::configured_url()),
+            connection: ConnectionState::Connecting,
+            backoff: Backoff::new(),
+            reconnect_timer: None,
+            startup_timer: None,
+            startup_at: 0.0,
+            startup_failed: false,
+            next_attempt_at: None,
+            countdown: None,
+            max_reconnects: max_reconnect_attempts(),
+            last_disconnect: None,
+            producer: EventBus::bridge(ctx.link().batch_callback(|event| match event {
+                BusEvent::Socket(s) => Some(Msg::HandleMsg(s)),
+                BusEvent::Chat(ChatEvent::ConnectionChanged { connection, connected, detail }) => {
+                    Some(Msg::ConnectionChanged { connection, connected, detail })
+                }
+                BusEvent::Chat(_) => None,
+            })),
+            show_emoji_picker: false,
+            picker_emoji: supported_emoji(EMOJIS, is_emoji_supported),
+            dragging: false,
+            emoji_placement: Placement::Above,
+            typing: TypingStateMachine::default(),
+            typing_timeout: None,
+            loading_history: false,
+            history_anchor: None,
+            unread: UnreadCounts::new(&initial_conversation),
+            conversations: if initial_conversation == GENERAL {
+                vec![initial_conversation]
+            } else {
+                vec![GENERAL.to_string(), initial_conversation]
+            },
+            settings: Settings::load(),
+            show_settings: false,
+            show_diagnostics: false,
+            confirm_clear: false,
+            selecting: false,
+            selected_ids: HashSet::new(),
+            quote_popover: None,
+            context_menu: None,
+            report_target: None,
+            revealed_blocked: HashSet::new(),
+            input_stats: MessageStats::default(),
+            composer_mode: ComposerMode::Normal,
+            code_language: String::new(),
+            link_previews: HashMap::new(),
+            translations: HashMap::new(),
+            translation_cache: TranslationCache::default(),
+            showing_original: HashSet::new(),
+            username: username.clone(),
+            user: context.map(|(user, _)| user),
+            logged_in: session.is_some(),
+            debug: debug_mode(),
+            idle: IdleTracker::new(js_sys::Date::now(), idle_after_ms()),
+            inactivity: InactivityTimer::new(js_sys::Date::now(), INACTIVITY_PROMPT_MS, INACTIVITY_GRACE_MS),
+            highlighted_user: None,
+            user_highlight_timer: None,
+            link_target: web_sys::window()
+                .and_then(|w| w.location().hash().ok())
+                .and_then(|hash| parse_message_fragment(&hash)),
+            highlighted_message: None,
+            message_highlight_timer: None,
+            focused_message: None,
+            quick_react_index: 0,
+            show_pinned: false,
+            search_query: String::new(),
+            toasts: ToastQueue::new(MAX_TOASTS),
+            toast_timer: None,
+            expanded: HashSet::new(),
+            scroll_memory: ScrollMemory::default(),
+            scroll: ScrollPosition::Bottom,
+            restore_scroll: true,
+            input_autofocused: false,
+            scroll_throttle: Throttle::new(SCROLL_THROTTLE_MS),
+            scroll_trailing: None,
+            last_read_sent: None,
+            unread_marker: None,
+            read_check: None,
+            _listeners: Vec::new(),
+            narrow: viewport_is_narrow(),
+            sidebar_open: false,
+            _idle_check: None,
+            _heartbeat: None,
+            connected_since: js_sys::Date::now(),
+            _session_tick: None,
+            clock_offset: 0.0,
+            midnight_timer: None,
         };
 
-        if let Ok(_) = wss
-            .tx
-            .clone()
-            .try_send(serde_json::to_string(&message).unwrap())
-        {
-            log::debug!("message sent successfully");
+        // Nothing to connect as: the view only links back to the login page.
+        if !chat.logged_in {
+            log::error!("Chat mounted without a logged-in user");
+            return chat;
         }
 
-        Self {
-            users: vec![],
-            messages: vec![],
-            chat_input: NodeRef::default(),
-            wss,
-            _producer: EventBus::bridge(ctx.link().callback(Msg::HandleMsg)),
-            typing_users: vec![],
-            show_emoji_picker: false,
-            typing_timeout: None,
+        // Opening it closes any socket an earlier mount left open.
+        match WebsocketService::new() {
+            Ok(wss) => {
+                chat.wss = wss;
+                chat.register();
+                let link = ctx.link().clone();
+                chat.startup_at = js_sys::Date::now();
+                chat.startup_timer = Some(Timeout::new(STARTUP_TIMEOUT_MS, move || {
+                    link.send_message(Msg::StartupTimedOut)
+                }));
+            }
+            // Stay offline and retry on the usual backoff.
+            Err(e) => {
+                log::error!("failed to open websocket: {}", e);
+                chat.connection = ConnectionState::Disconnected;
+                chat.last_disconnect = Some(e);
+                chat.schedule_reconnect(ctx);
+            }
         }
+
+        // Any of these counts as the user being active for away detection.
+        let window = web_sys::window().expect("window to exist");
+        let activity_events = ["mousemove", "mousedown", "keydown", "touchstart"];
+        let activity_listeners: Vec<EventListener> = activity_events
+            .iter()
+            .map(|event| {
+                let link = ctx.link().clone();
+                EventListener::new(&window, *event, move |_| {
+                    link.send_message(Msg::UserActivity)
+                })
+            })
+            .collect();
+        let shortcut_listener = {
+            let link = ctx.link().clone();
+            EventListener::new(&window, "keydown", move |event| {
+                let event = match event.dyn_ref::<KeyboardEvent>() {
+                    Some(event) => event,
+                    None => return,
+                };
+                if event.key() == "Escape" {
+                    link.send_message(Msg::CloseContextMenu)
+                } else if (event.ctrl_key() || event.meta_key()) && event.alt_key() && event.code() == "KeyD" {
+                    // Ctrl+Alt+D (Cmd+Alt+D on a Mac). Matched on `code`
+                    // because Alt changes the typed character on some layouts.
+                    event.prevent_default();
+                    link.send_message(Msg::ToggleDiagnostics)
+                }
+            })
+        };
+        let focus_listener = {
+            let link = ctx.link().clone();
+            EventListener::new(&window, "focus", move |_| link.send_message(Msg::CheckRead))
+        };
+        let blur_listener = {
+            let link = ctx.link().clone();
+            EventListener::new(&window, "blur", move |_| link.send_message(Msg::WindowBlurred))
+        };
+        let visibility_listener = {
+            let link = ctx.link().clone();
+            let document = window.document().expect("document to exist");
+            let target = document.clone();
+            EventListener::new(&target, "visibilitychange", move |_| {
+                if !document.hidden() {
+                    link.send_message(Msg::TabVisible)
+                }
+            })
+        };
+        let resize_listener = {
+            let link = ctx.link().clone();
+            EventListener::new(&window, "resize", move |_| link.send_message(Msg::ViewportResized))
+        };
+        let idle_check = {
+            let link = ctx.link().clone();
+            Interval::new(IDLE_CHECK_MS, move || link.send_message(Msg::CheckIdle))
+        };
+        let heartbeat = {
+            let link = ctx.link().clone();
+            Interval::new(presence_heartbeat_ms(), move || link.send_message(Msg::Heartbeat))
+        };
+
+        let session_tick = {
+            let link = ctx.link().clone();
+            Interval::new(SESSION_TICK_MS, move || link.send_message(Msg::SessionTick))
+        };
+
+        chat._listeners = activity_listeners;
+        chat._listeners.extend([
+            shortcut_listener,
+            focus_listener,
+            blur_listener,
+            visibility_listener,
+            resize_listener,
+        ]);
+        chat._idle_check = Some(idle_check);
+        chat._heartbeat = Some(heartbeat);
+        chat._session_tick = Some(session_tick);
+        chat.schedule_midnight(ctx);
+        chat
     }
     
     fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
         match msg {
             Msg::HandleMsg(s) => {
-                let msg: WebSocketMessage = serde_json::from_str(&s).unwrap();
-                match msg.message_type {
-                    MsgTypes::Users => {
-                        let users_from_message = msg.data_array.unwrap_or_default();
-                        self.users = users_from_message
-                            .iter()
-                            .map(|u| UserProfile {
-                                name: u.into(),
-                                avatar: format!(
-                                    "https://avatars.dicebear.com/api/adventurer-neutral/{}.svg",
-                                    u
-                                )
-                                .into(),
-                            })
-                            .collect();
-                        return true;
+                let msg: WebSocketMessage = match serde_json::from_str(&s) {
+                    Ok(msg) => msg,
+                    Err(e) => {
+                        log::warn!("ignoring malformed frame: {}", e);
+                        return false;
                     }
-                    MsgTypes::Message => {
-                        let message_data: MessageData =
-                            serde_json::from_str(&msg.data.unwrap()).unwrap();
-                        self.messages.push(message_data);
-                        return true;
+                };
+                match self.state.apply_incoming(msg, self.now(), self.debug) {
+                    Incoming::Unchanged => false,
+                    Incoming::Changed => true,
+                    Incoming::Users { changed } => {
+                        // The server answers every registration with the user
+                        // list, so this is also our sign the connection is up.
+                        let was_connected = self.connection == ConnectionState::Connected;
+                        let was_disconnected =
+                            matches!(self.connection, ConnectionState::Disconnected | ConnectionState::GaveUp);
+                        if was_disconnected {
+                            self.push_toast(ctx, "Reconnected", ToastKind::Info);
+                        }
+                        self.connection = ConnectionState::Connected;
+                        self.startup_timer = None;
+                        self.startup_failed = false;
+                        self.backoff.reset();
+                        if !was_connected {
+                            if was_disconnected && self.settings.reset_session_on_reconnect {
+                                self.connected_since = js_sys::Date::now();
+                            }
+                            self.send_time_sync();
+                            // Fill an empty list with recent history the
+                            // first time round.
+                            if self.state.messages.iter().all(|m| m.pending) {
+                                self.request_history();
+                            }
+                            self.flush_outbox();
+                        }
+                        // Fetch new avatars up front so they show from cache
+                        // instead of popping in one by one.
+                        let avatars: Vec<String> = self.state.users.iter().map(|u| u.avatar.clone()).collect();
+                        for url in added_avatar_urls(&self.preloaded_avatars, &avatars) {
+                            preload_image(&url);
+                            self.preloaded_avatars.push(url);
+                        }
+                        changed || !was_connected
                     }
-                    MsgTypes::Typing => {
-                        // Handle typing status updates
-                        if let Some(data) = msg.data {
-                            let typing_status: TypingStatus = serde_json::from_str(&data).unwrap();
-                            
-                            if typing_status.is_typing {
-                                // Add user to typing list if not already there
-                                if !self.typing_users.contains(&typing_status.username) {
-                                    self.typing_users.push(typing_status.username);
-                                }
+                    Incoming::Message { id, from, text, conversation } => {
+                        let notify = !self.settings.blocked.contains(&from)
+                            && should_notify(self.settings.notify, &text, &self.username, from == self.username);
+                        if notify {
+                            notification::notify_message(&from, &text);
+                            notification::play_sound(self.settings.notification_sound);
+                        }
+                        if let Some((id, url)) = id.zip(first_url(&text).map(String::from)) {
+                            self.request_link_preview(ctx, id, url);
+                        }
+                        self.publish(ChatEvent::NewMessage { from, message: text });
+                        if !self.conversations.contains(&conversation) {
+                            self.conversations.push(conversation.clone());
+                        }
+                        self.unread.record(&conversation);
+                        self.schedule_read_check(ctx);
+                        true
+                    }
+                    Incoming::Typing => {
+                        self.publish(ChatEvent::TypingChanged(self.state.typing_users.clone()));
+                        true
+                    }
+                    Incoming::RegisterError(error) => {
+                        self.reject_login(ctx, error);
+                        false
+                    }
+                    Incoming::TimeSync { sent, server } => {
+                        self.clock_offset = clock_offset(sent, server, js_sys::Date::now());
+                        false
+                    }
+                    Incoming::History { added } => {
+                        self.loading_history = false;
+                        // Keep paging back until a linked message turns up.
+                        let target_missing = self.link_target.as_ref().map_or(false, |target| {
+                            !self.state.messages.iter().any(|m| m.id.as_ref() == Some(target))
+                        });
+                        if target_missing {
+                            if self.state.has_more_history {
+                                self.request_history();
                             } else {
-                                // Remove user from typing list
-                                self.typing_users.retain(|u| u != &typing_status.username);
+                                self.link_target = None;
+                                self.push_toast(ctx, "The linked message is no longer available", ToastKind::Info);
                             }
-                            return true;
                         }
-                        return false;
-                    }
-                    _ => {
-                        return false;
+                        if added > 0 && self.scroll != ScrollPosition::Bottom {
+                            if let Some(list) = self.message_list.cast::<web_sys::Element>() {
+                                self.history_anchor = Some((list.scroll_top() as f64, list.scroll_height() as f64));
+                            }
+                        }
+                        true
                     }
                 }
             }
             Msg::SubmitMessage => {
-    let input = self.chat_input.cast::<HtmlInputElement>();
-    if let Some(input) = input {
-        let input_value = input.value();
-        if !input_value.trim().is_empty() {
-            // Send message without nesting
-            let message = WebSocketMessage {
-                message_type: MsgTypes::Message,
-                data: Some(input_value),
-                data_array: None,
-            };
-            
-            if let Err(e) = self
-                .wss
-                .tx
-                .clone()
-                .try_send(serde_json::to_string(&message).unwrap())
-            {
-                log::debug!("error sending to channel: {:?}", e);
+                let input = self.chat_input.cast::<HtmlTextAreaElement>();
+                if let Some(input) = input {
+                    let text = if self.composer_mode == ComposerMode::Code {
+                        wrap_code(&input.value(), &self.code_language)
+                    } else {
+                        normalize_message(&input.value())
+                    };
+                    let poll = match (&self.composer_mode, &text) {
+                        (ComposerMode::Normal, Some(text)) => parse_poll_command(text),
+                        _ => None,
+                    };
+                    let editing = match &self.composer_mode {
+                        ComposerMode::Edit { message_id, .. } => Some(message_id.clone()),
+                        _ => None,
+                    };
+                    // Stop typing before the message goes out, so other clients
+                    // never see the indicator outlive it.
+                    if poll.is_some() || text.is_some() {
+                        self.stop_typing();
+                    }
+                    if let Some(message_id) = editing {
+                        if let Some(text) = text {
+                            self.send_edit(ctx, message_id, text);
+                        }
+                        if let Some(prior_input) = self.composer_mode.cancel() {
+                            input.set_value(&prior_input);
+                        }
+                    } else if let Some(poll) = poll {
+                        self.send_poll(&poll);
+                        input.set_value("");
+                    } else if let Some(text) = text {
+                        self.send_message(ctx, text, None);
+                        input.set_value("");
+                        if let ComposerMode::Quote { .. } = self.composer_mode {
+                            self.composer_mode = ComposerMode::Normal;
+                        }
+                    }
+                };
+
+                self.show_emoji_picker = false;
+                self.quote_popover = None;
+                self.refresh_input_stats();
+                true
+            }
+            Msg::SetDragging(dragging) => {
+                let changed = self.dragging != dragging;
+                self.dragging = dragging;
+                changed
+            }
+            Msg::FileDropped(file) => {
+                self.dragging = false;
+                match classify_drop(&file.type_(), file.size(), MAX_ATTACHMENT_BYTES) {
+                    DropDecision::Unsupported => {
+                        self.push_toast(ctx, "Only images can be sent", ToastKind::Error)
+                    }
+                    DropDecision::TooLarge => {
+                        self.push_toast(ctx, "That image is too large to send", ToastKind::Error)
+                    }
+                    DropDecision::Upload => match upload_endpoint() {
+                        Some(endpoint) => {
+                            self.push_toast(ctx, "Uploading…", ToastKind::Info);
+                            let (name, size, mime) = (file.name(), file.size(), file.type_());
+                            ctx.link().send_future(async move {
+                                let url = upload_file(&endpoint, file).await;
+                                Msg::FileUploaded(url.map(|url| Attachment { url, name, size, mime }))
+                            });
+                        }
+                        None => self.push_toast(ctx, "File uploads aren't set up on this server", ToastKind::Error),
+                    },
+                }
+                true
+            }
+            Msg::FileUploaded(attachment) => {
+                match attachment {
+                    Some(attachment) => self.send_message(ctx, attachment.url.clone(), Some(attachment)),
+                    None => self.push_toast(ctx, "Upload failed", ToastKind::Error),
+                }
+                true
             }
-            
-            input.set_value("");
-            self.send_typing_status(ctx, false);
-        }
-    };
-    
-    self.show_emoji_picker = false;
-    true
-}
             Msg::InputChanged => {
-                // Send a typing status message
-                self.send_typing_status(ctx, true);
+                let input = self.chat_input.cast::<HtmlTextAreaElement>();
+                if let Some(input) = input.as_ref().filter(|_| self.composer_mode != ComposerMode::Code) {
+                    let value = input.value();
+                    let expanded = expand_shortcodes(&value, self.settings.skin_tone);
+                    if expanded != value {
+                        input.set_value(&expanded);
+                    }
+                }
+                // Send a typing status message. Clearing the input counts as
+                // having stopped typing.
+                let value = input.map(|input| input.value()).unwrap_or_default();
+                let typing = is_typing(&value);
+                if let Some(status) = self.typing.on_input(typing) {
+                    self.send_typing_status(status);
+                }
+                self.schedule_typing_idle(ctx);
+                self.refresh_input_stats()
+            }
+            Msg::ToggleCodeMode => {
+                self.composer_mode = if self.composer_mode == ComposerMode::Code {
+                    ComposerMode::Normal
+                } else {
+                    ComposerMode::Code
+                };
+                true
+            }
+            Msg::SetCodeLanguage(language) => {
+                self.code_language = language;
                 false
             }
             Msg::ToggleEmojiPicker => {
                 self.show_emoji_picker = !self.show_emoji_picker;
+                if self.show_emoji_picker {
+                    self.emoji_placement = self.measure_picker_placement();
+                }
                 true
             }
             Msg::SelectEmoji(emoji) => {
                 // Insert emoji at cursor position in input field
-                if let Some(input) = self.chat_input.cast::<HtmlInputElement>() {
+                if let Some(input) = self.chat_input.cast::<HtmlTextAreaElement>() {
                     let current_value = input.value();
                     input.set_value(&format!("{}{}", current_value, emoji));
                     input.focus().unwrap();
                 }
-                false
+                push_recent(&mut self.settings.recent_emoji, &emoji, RECENT_EMOJI_LIMIT);
+                self.settings.save();
+                self.refresh_input_stats();
+                true
             }
             Msg::HandleKeyDown(event) => {
-                // Handle Enter key to submit
-                if event.key() == "Enter" && !event.shift_key() {
+                // Enter or Ctrl/Cmd+Enter submits, depending on the setting
+                let modifier = event.ctrl_key() || event.meta_key();
+                if is_send(&event.key(), event.shift_key(), modifier, self.settings.send_key) {
                     event.prevent_default();
+                    // SubmitMessage re-renders once it's handled
                     ctx.link().send_message(Msg::SubmitMessage);
+                    return false;
+                }
+                // Escape leaves code/quote/edit mode and puts back what was typed before
+                if event.key() == "Escape" && self.composer_mode != ComposerMode::Normal {
+                    if let Some(prior_input) = self.composer_mode.cancel() {
+                        if let Some(input) = self.chat_input.cast::<HtmlTextAreaElement>() {
+                            input.set_value(&prior_input);
+                        }
+                        self.refresh_input_stats();
+                    }
                     return true;
                 }
                 false
             }
-        }
-    }
-    
-    fn view(&self, ctx: &Context<Self>) -> Html {
-        let submit = ctx.link().callback(|_| Msg::SubmitMessage);
-        let input_changed = ctx.link().callback(|_| Msg::InputChanged);
-        let toggle_emoji = ctx.link().callback(|_| Msg::ToggleEmojiPicker);
-        let on_keydown = ctx.link().callback(|e: KeyboardEvent| Msg::HandleKeyDown(e));
-        
-        // Create typing indicator text
-        let typing_text = if !self.typing_users.is_empty() {
-            if self.typing_users.len() == 1 {
-                format!("{} is typing...", self.typing_users[0])
-            } else if self.typing_users.len() == 2 {
-                format!("{} and {} are typing...", self.typing_users[0], self.typing_users[1])
-            } else {
-                String::from("Several people are typing...")
+            Msg::ToggleTimeFormat => {
+                self.settings.time_format = self.settings.time_format.toggled();
+                self.settings.save();
+                true
             }
-        } else {
-            String::new()
-        };
-        
-        html! {
-            <div class="flex w-screen">
-                <div class="flex-none w-56 h-screen bg-gray-100">
-                    <div class="text-xl p-3">{"Users"}</div>
-                    {
-                        self.users.clone().iter().map(|u| {
-                            html!{
-                                <div class="flex m-3 bg-white rounded-lg p-2">
-                                    <div>
-                                        <img class="w-12 h-12 rounded-full" src={u.avatar.clone()} alt="avatar"/>
-                                    </div>
-                                    <div class="flex-grow p-3">
-                                        <div class="flex text-xs justify-between">
-                                            <div>{u.name.clone()}</div>
-                                        </div>
-                                        <div class="text-xs text-gray-400">
-                                            {"Hi there!"}
-                                        </div>
-                                    </div>
-                                </div>
+            Msg::ToggleDensity => {
+                self.settings.density = self.settings.density.toggled();
+                self.settings.save();
+                true
+            }
+            Msg::UserActivity => {
+                let now = js_sys::Date::now();
+                self.inactivity.on_activity(now);
+                let change = self.idle.on_activity(now);
+                self.apply_own_presence(change)
+            }
+            Msg::CheckIdle => {
+                let now = js_sys::Date::now();
+                match self.inactivity.tick(now) {
+                    Some(Inactivity::Prompted) => ctx.link().send_message(Msg::InactivityPrompt),
+                    Some(Inactivity::TimedOut) => {
+                        self.suspend();
+                        return true;
+                    }
+                    _ => {}
+                }
+                let change = self.idle.tick(now);
+                self.apply_own_presence(change)
+            }
+            Msg::InactivityPrompt => true,
+            Msg::StayConnected => {
+                let suspended = self.inactivity.state() == Inactivity::TimedOut;
+                self.inactivity.stay(js_sys::Date::now());
+                if suspended {
+                    // Registers again once the new socket is up.
+                    self.connection = ConnectionState::Disconnected;
+                    self.reconnect(ctx);
+                }
+                true
+            }
+            Msg::NewDay => {
+                self.schedule_midnight(ctx);
+                true
+            }
+            Msg::TabVisible => {
+                // Timers are throttled in hidden tabs, so what the server has
+                // for us may be stale: re-send presence and the typing state
+                // the composer actually shows.
+                if self.connection != ConnectionState::Connected {
+                    return false;
+                }
+                let change = self.idle.on_activity(js_sys::Date::now());
+                if !self.apply_own_presence(change) {
+                    self.send_presence(self.idle.status());
+                }
+                let value = self
+                    .chat_input
+                    .cast::<HtmlTextAreaElement>()
+                    .map(|input| input.value())
+                    .unwrap_or_default();
+                let status = self.typing.resync(is_typing(&value));
+                self.send_typing_status(status);
+                if status {
+                    self.schedule_typing_idle(ctx);
+                } else {
+                    self.typing_timeout = None;
+                }
+                true
+            }
+            Msg::TypingIdle => {
+                self.typing_timeout = None;
+                if let Some(status) = self.typing.on_idle() {
+                    self.send_typing_status(status);
+                }
+                false
+            }
+            Msg::Heartbeat => {
+                if self.connection == ConnectionState::Connected {
+                    self.send_presence(self.idle.status());
+                }
+                false
+            }
+            Msg::SelectionChanged { from } => {
+                let popover = current_selection().map(|(text, x, y)| QuotePopover { from, text, x, y });
+                let changed = popover.is_some() || self.quote_popover.is_some();
+                self.quote_popover = popover;
+                changed
+            }
+            Msg::QuoteSelection { from, text } => {
+                if let Some(input) = self.chat_input.cast::<HtmlTextAreaElement>() {
+                    let prior_input = input.value();
+                    input.set_value(&format!("{}{}", format_quote(&from, &text), prior_input));
+                    input.focus().ok();
+                    self.composer_mode = ComposerMode::Quote { from, prior_input };
+                }
+                if let Some(Ok(Some(selection))) = web_sys::window().map(|w| w.get_selection()) {
+                    selection.remove_all_ranges().ok();
+                }
+                self.quote_popover = None;
+                self.refresh_input_stats();
+                true
+            }
+            Msg::LinkPreviewLoaded { message_id, preview } => {
+                let loaded = preview.is_some();
+                self.link_previews.insert(message_id, preview);
+                loaded
+            }
+            Msg::TranslateMessage(message_id) => {
+                // Already translated: just show it again.
+                match self.translations.get(&message_id) {
+                    Some(Some(_)) => return self.showing_original.remove(&message_id),
+                    Some(None) => return false,
+                    None => {}
+                }
+                let endpoint = match translate_endpoint() {
+                    Some(endpoint) => endpoint,
+                    None => return false,
+                };
+                let text = match self.state.messages.iter().find(|m| m.id.as_ref() == Some(&message_id)) {
+                    Some(message) => message.message.clone(),
+                    None => return false,
+                };
+                let link = ctx.link().clone();
+                let id = message_id.clone();
+                let cached = self.translation_cache.cached_or(&text, &browser_language(), |original, target| {
+                    let (original, target) = (original.to_string(), target.to_string());
+                    link.send_future(async move {
+                        let text = translate(&endpoint, &original, &target).await;
+                        Msg::TranslationLoaded { message_id: id, original, target, text }
+                    });
+                });
+                self.translations.insert(message_id, cached);
+                true
+            }
+            Msg::TranslationLoaded { message_id, original, target, text } => {
+                match text {
+                    Some(text) => {
+                        self.translation_cache.insert(original, target, text.clone());
+                        self.translations.insert(message_id, Some(text));
+                    }
+                    None => {
+                        // Forget the attempt so the action can be retried.
+                        self.translations.remove(&message_id);
+                        self.push_toast(ctx, "Couldn't translate that message", ToastKind::Error);
+                    }
+                }
+                true
+            }
+            Msg::ToggleTranslation(message_id) => {
+                if !self.showing_original.remove(&message_id) {
+                    self.showing_original.insert(message_id);
+                }
+                true
+            }
+            Msg::ConnectionChanged { connection, connected, detail } => {
+                // A suspended socket was closed on purpose and stays closed.
+                if connection != self.wss.id || connected || self.connection == ConnectionState::Suspended {
+                    return false;
+                }
+                self.connection = ConnectionState::Disconnected;
+                self.last_disconnect = detail;
+                self.schedule_reconnect(ctx);
+                true
+            }
+            Msg::Reconnect => {
+                self.reconnect(ctx);
+                true
+            }
+            Msg::CountdownTick => self.next_attempt_at.is_some(),
+            Msg::StartupTimedOut => {
+                self.startup_timer = None;
+                let connected = matches!(self.connection, ConnectionState::Connecting | ConnectionState::Connected);
+                let registered = self.connection == ConnectionState::Connected;
+                self.startup_failed =
+                    startup_timed_out(connected, registered, js_sys::Date::now() - self.startup_at);
+                self.startup_failed
+            }
+            Msg::ForceReconnect => {
+                self.startup_failed = false;
+                self.backoff.reset();
+                self.connection = ConnectionState::Disconnected;
+                self.reconnect(ctx);
+                true
+            }
+            Msg::MessagesScrolled => {
+                if !self.scroll_throttle.ready(js_sys::Date::now()) {
+                    if self.scroll_trailing.is_none() {
+                        let link = ctx.link().clone();
+                        self.scroll_trailing = Some(Timeout::new(SCROLL_THROTTLE_MS, move || {
+                            link.send_message(Msg::MessagesScrolled)
+                        }));
+                    }
+                    return false;
+                }
+                self.scroll_trailing = None;
+                if let Some(list) = self.message_list.cast::<web_sys::Element>() {
+                    ctx.link().send_message(Msg::ScrollPositionChanged(scroll_position(
+                        list.scroll_top() as f64,
+                        list.scroll_height() as f64,
+                        list.client_height() as f64,
+                    )));
+                }
+                false
+            }
+            Msg::ScrollPositionChanged(position) => {
+                self.scroll = position;
+                self.schedule_read_check(ctx);
+                match position {
+                    ScrollPosition::Offset(top) if top <= HISTORY_THRESHOLD_PX && self.state.has_more_history => {
+                        self.request_history()
+                    }
+                    _ => false,
+                }
+            }
+            Msg::CheckRead => {
+                self.schedule_read_check(ctx);
+                false
+            }
+            Msg::SendReadReceipt => {
+                self.read_check = None;
+                self.send_read_receipt()
+            }
+            Msg::WindowBlurred => {
+                if self.unread_marker.is_none() {
+                    self.unread_marker = self.last_read_sent.clone();
+                }
+                false
+            }
+            Msg::JumpToUnread => {
+                let divider = web_sys::window()
+                    .and_then(|w| w.document())
+                    .and_then(|d| d.get_element_by_id(UNREAD_DIVIDER_ID));
+                if let Some(divider) = divider {
+                    divider.scroll_into_view();
+                }
+                false
+            }
+            Msg::ToggleReaction { message_id, emoji } => {
+                // Our own reaction shows up once the server relays it back.
+                let added = !self.state.reactions.has_reacted(&message_id, &emoji, &self.username);
+                self.send_reaction(message_id, emoji, added);
+                self.context_menu.take().is_some()
+            }
+            Msg::FocusMessage(message_id) => {
+                self.focused_message = Some(message_id);
+                self.quick_react_index = 0;
+                true
+            }
+            Msg::BlurMessage(message_id) => {
+                if self.focused_message.as_ref() != Some(&message_id) {
+                    return false;
+                }
+                self.focused_message = None;
+                true
+            }
+            Msg::MessageKeyDown { message_id, event } => {
+                let delta = match event.key().as_str() {
+                    "ArrowRight" => 1,
+                    "ArrowLeft" => -1,
+                    "Enter" => {
+                        event.prevent_default();
+                        let emoji = QUICK_REACTIONS[self.quick_react_index].to_string();
+                        ctx.link().send_message(Msg::ToggleReaction { message_id, emoji });
+                        return false;
+                    }
+                    _ => return false,
+                };
+                event.prevent_default();
+                self.quick_react_index =
+                    quick_react_step(self.quick_react_index, delta, QUICK_REACTIONS.len());
+                true
+            }
+            Msg::OpenContextMenu { message_id, x, y } => {
+                self.context_menu = Some(ContextMenu { message_id, x, y });
+                self.quote_popover = None;
+                true
+            }
+            Msg::CloseContextMenu => self.context_menu.take().is_some(),
+            Msg::SubmitReport(reason) => {
+                if let Some(message_id) = self.report_target.take() {
+                    if self.send_report(message_id, reason) {
+                        self.push_toast(ctx, "Thanks, the message was reported", ToastKind::Info);
+                    } else {
+                        self.push_toast(ctx, "Couldn't send the report", ToastKind::Error);
+                    }
+                }
+                true
+            }
+            Msg::CancelReport => self.report_target.take().is_some(),
+            Msg::BlockUser(name) => {
+                if name == self.username || !self.settings.blocked.insert(name) {
+                    return false;
+                }
+                self.settings.save();
+                true
+            }
+            Msg::UnblockUser(name) => {
+                if !self.settings.blocked.remove(&name) {
+                    return false;
+                }
+                self.settings.save();
+                true
+            }
+            Msg::RevealBlocked(message_id) => self.revealed_blocked.insert(message_id),
+            Msg::MenuAction { message_id, action } => {
+                self.context_menu = None;
+                let message = self
+                    .state
+                    .messages
+                    .iter()
+                    .find(|m| m.id.as_ref() == Some(&message_id));
+                match (action, message) {
+                    (MessageAction::Copy, Some(message)) => {
+                        if copy_text(&message.message) {
+                            ctx.link().send_message(Msg::PushToast {
+                                text: String::from("Copied"),
+                                kind: ToastKind::Info,
+                            });
+                        }
+                    }
+                    (MessageAction::Quote, Some(message)) => {
+                        ctx.link().send_message(Msg::QuoteSelection {
+                            from: message.from.clone(),
+                            text: message.message.clone(),
+                        });
+                    }
+                    (MessageAction::Pin, _) => ctx.link().send_message(Msg::TogglePin(message_id)),
+                    (MessageAction::Report, _) => self.report_target = Some(message_id),
+                    (MessageAction::CopyLink, _) => {
+                        let page = web_sys::window().and_then(|w| w.location().href().ok());
+                        match page {
+                            Some(page) if copy_text(&message_link(&page, &message_id)) => {
+                                self.push_toast(ctx, "Message link copied", ToastKind::Info)
                             }
-                        }).collect::<Html>()
+                            _ => self.push_toast(ctx, "Couldn't copy the message link", ToastKind::Error),
+                        }
                     }
-                </div>
-                <div class="grow h-screen flex flex-col">
-                    <div class="w-full h-14 border-b-2 border-gray-300">
-                        <div class="text-xl p-3">{"💬 Chat!"}</div>
-                    </div>
-                    <div class="w-full grow overflow-auto border-b-2 border-gray-300">
-                        {
-                            self.messages.iter().map(|m| {
-                                // Create the default profile outside the unwrap_or to avoid borrowing issues
-                                let default_profile = UserProfile {
-                                    name: m.from.clone(),
-                                    avatar: format!(
-                                        "https://avatars.dicebear.com/api/adventurer-neutral/{}.svg", 
-                                        m.from
-                                    ),
+                    (MessageAction::Translate, _) => ctx.link().send_message(Msg::TranslateMessage(message_id)),
+                    (MessageAction::Select, _) => ctx.link().send_message(Msg::ToggleSelect(message_id)),
+                    (MessageAction::Edit, Some(message)) => {
+                        if can_edit(message, self.now(), edit_window_ms() as f64) {
+                            let text = message.message.clone();
+                            if let Some(input) = self.chat_input.cast::<HtmlTextAreaElement>() {
+                                let prior_input = match self.composer_mode.cancel() {
+                                    Some(prior_input) => prior_input,
+                                    None => input.value(),
                                 };
-                                
-                                // Now use the created profile
-                                let user = self.users.iter().find(|u| u.name == m.from).unwrap_or(&default_profile);
-                                
-                                html!{
-                                    <div class="flex items-end w-3/6 bg-gray-100 m-8 rounded-tl-lg rounded-tr-lg rounded-br-lg">
-                                        <img class="w-8 h-8 rounded-full m-3" src={user.avatar.clone()} alt="avatar"/>
-                                        <div class="p-3 w-full">
-                                            <div class="flex justify-between items-center">
-                                                <div class="text-sm font-medium">
-                                                    {m.from.clone()}
-                                                </div>
-                                                <div class="text-xs text-gray-400">
-                                                    {m.timestamp.clone().unwrap_or_default()}
-                                                </div>
-                                            </div>
-                                            <div class="text-xs text-gray-700 mt-1">
-                                                {
-                                                    if m.message.ends_with(".gif") {
-                                                        html! {
-                                                            <img class="mt-3" src={m.message.clone()}/>
-                                                        }
-                                                    } else {
-                                                        html! {
-                                                            {m.message.clone()}
-                                                        }
-                                                    }
-                                                }
-                                            </div>
-                                        </div>
-                                    </div>
-                                }
-                            }).collect::<Html>()
-                        }
-                        
-                        {
-                            // Display typing indicators
-                            if !self.typing_users.is_empty() {
-                                html! {
-                                    <div class="flex items-center px-6 py-2 text-sm italic text-gray-500">
-                                        {typing_text}
-                                        <div class="flex items-center ml-2">
-                                            <div class="w-2 h-2 bg-gray-400 rounded-full mr-1 animate-bounce"></div>
-                                            <div class="w-2 h-2 bg-gray-400 rounded-full mr-1 animate-bounce"></div>
-                                            <div class="w-2 h-2 bg-gray-400 rounded-full animate-bounce"></div>
-                                        </div>
-                                    </div>
-                                }
-                            } else {
-                                html! {}
+                                input.set_value(&text);
+                                input.focus().ok();
+                                self.composer_mode = ComposerMode::Edit { message_id, prior_input };
                             }
+                            self.refresh_input_stats();
                         }
-                    </div>
-                    <div class="w-full h-14 flex px-3 items-center relative">
-                        <button 
-                            onclick={toggle_emoji}
-                            class="p-2 text-gray-500 hover:text-gray-700 focus:outline-none"
-                        >
-                            {"😀"}
-                        </button>
-                        <input 
-                            ref={self.chat_input.clone()} 
-                            type="text" 
-                            placeholder="Message" 
-                            class="block w-full py-2 pl-4 mx-3 bg-gray-100 rounded-full outline-none focus:text-gray-700" 
-                            name="message" 
-                            onkeydown={on_keydown}
-                            oninput={input_changed}
-                            required=true 
-                        />
-                        <button 
-                            onclick={submit} 
-                            class="p-3 shadow-sm bg-blue-600 w-10 h-10 rounded-full flex justify-center items-center color-white"
-                        >
-                            <svg fill="#000000" viewBox="0 0 24 24" xmlns="http://www.w3.org/2000/svg" class="fill-white">
-                                <path d="M0 0h24v24H0z" fill="none"></path><path d="M2.01 21L23 12 2.01 3 2 10l15 2-15 2z"></path>
-                            </svg>
+                    }
+                    (MessageAction::Block, Some(message)) => {
+                        ctx.link().send_message(Msg::BlockUser(message.from.clone()))
+                    }
+                    // Reactions are picked straight from the menu's emoji row.
+                    (MessageAction::React, _) | (_, None) => {}
+                }
+                true
+            }
+            Msg::Vote { poll_id, option } => {
+                // Our vote is counted once the server relays it back.
+                let vote = VoteUpdate {
+                    username: self.username.clone(),
+                    poll_id,
+                    option,
+                };
+                match serde_json::to_string(&vote) {
+                    Ok(data) => {
+                        self.send_ws(&WebSocketMessage {
+                            message_type: MsgTypes::Vote,
+                            data: Some(data),
+                            data_array: None,
+                            id: None,
+                            attachment: None,
+                            channel: None,
+                        });
+                    }
+                    Err(e) => log::error!("failed to serialize vote: {}", e),
+                }
+                false
+            }
+            Msg::TogglePin(message_id) => {
+                let pinned = !self.state.pinned_ids.contains(&message_id);
+                self.send_pin(message_id, pinned);
+                false
+            }
+            Msg::SetSearch(query) => {
+                let query = query.trim().to_string();
+                let changed = query != self.search_query;
+                self.search_query = query;
+                changed
+            }
+            Msg::CopyInviteLink => {
+                let origin = web_sys::window().and_then(|w| w.location().origin().ok());
+                let origin = match origin {
+                    Some(origin) => origin,
+                    None => return false,
+                };
+                let ws = query_param("ws");
+                let url = invite_url(&origin, ws.as_deref(), self.unread.active());
+                if copy_text(&url) {
+                    self.push_toast(ctx, "Invite link copied", ToastKind::Info);
+                } else {
+                    self.push_toast(ctx, "Couldn't copy the invite link", ToastKind::Error);
+                }
+                true
+            }
+            Msg::PushToast { text, kind } => {
+                self.push_toast(ctx, &text, kind);
+                true
+            }
+            Msg::DismissToast(id) => self.toasts.dismiss(id),
+            Msg::ExpireToasts => {
+                let now = js_sys::Date::now();
+                let expired = self.toasts.prune(now);
+                self.schedule_toast_expiry(ctx, now);
+                expired
+            }
+            Msg::ToggleExpand(message_id) => {
+                if !self.expanded.remove(&message_id) {
+                    self.expanded.insert(message_id);
+                }
+                true
+            }
+            Msg::TogglePinnedPanel => {
+                self.show_pinned = !self.show_pinned;
+                true
+            }
+            Msg::JumpToMessage(message_id) => {
+                let row = web_sys::window()
+                    .and_then(|w| w.document())
+                    .and_then(|d| d.get_element_by_id(&message_dom_id(&message_id)));
+                if let Some(row) = row {
+                    row.scroll_into_view();
+                }
+                false
+            }
+            Msg::FocusUser(from) => {
+                let entry = match self.state.sidebar_entry(&from) {
+                    Some(user) => user_dom_id(&user.id),
+                    None => {
+                        let name = display_name(&from, &self.state.users).to_string();
+                        self.push_toast(ctx, &format!("{} is no longer online", name), ToastKind::Info);
+                        return true;
+                    }
+                };
+                let element = web_sys::window()
+                    .and_then(|w| w.document())
+                    .and_then(|d| d.get_element_by_id(&entry));
+                if let Some(element) = element {
+                    element.scroll_into_view();
+                }
+                let link = ctx.link().clone();
+                self.user_highlight_timer = Some(Timeout::new(USER_HIGHLIGHT_MS, move || {
+                    link.send_message(Msg::ClearUserHighlight)
+                }));
+                self.highlighted_user = Some(from);
+                true
+            }
+            Msg::ClearUserHighlight => {
+                self.user_highlight_timer = None;
+                self.highlighted_user.take().is_some()
+            }
+            Msg::HighlightMessage(message_id) => {
+                self.highlighted_message = Some(message_id);
+                let link = ctx.link().clone();
+                self.message_highlight_timer = Some(Timeout::new(USER_HIGHLIGHT_MS, move || {
+                    link.send_message(Msg::ClearMessageHighlight)
+                }));
+                true
+            }
+            Msg::ClearMessageHighlight => {
+                self.message_highlight_timer = None;
+                self.highlighted_message.take().is_some()
+            }
+            Msg::SwitchConversation(conversation) => {
+                self.sidebar_open = false;
+                if self.unread.active() == Some(conversation.as_str()) {
+                    return true;
+                }
+                self.switch_conversation(&conversation);
+                true
+            }
+            Msg::RequestClear => {
+                self.confirm_clear = true;
+                true
+            }
+            Msg::ToggleSelect(message_id) => {
+                self.selecting = true;
+                toggle_selection(&mut self.selected_ids, message_id);
+                true
+            }
+            Msg::ExitSelectMode => {
+                self.selecting = false;
+                self.selected_ids.clear();
+                true
+            }
+            Msg::CopySelected => {
+                let text = transcript(&self.selected_messages(), self.settings.time_format);
+                if copy_text(&text) {
+                    self.push_toast(ctx, "Copied", ToastKind::Info);
+                } else {
+                    self.push_toast(ctx, "Couldn't copy the messages", ToastKind::Error);
+                }
+                true
+            }
+            Msg::QuoteSelected => {
+                let messages = self.selected_messages();
+                let mut senders: Vec<&str> = messages.iter().map(|m| m.from.as_str()).collect();
+                senders.dedup();
+                let from = match senders.as_slice() {
+                    [from] => from.to_string(),
+                    _ => format!("{} messages", messages.len()),
+                };
+                let prefill = quote_prefill(&self.state.messages, &self.selected_ids);
+                if prefill.is_empty() {
+                    return false;
+                }
+                if let Some(input) = self.chat_input.cast::<HtmlTextAreaElement>() {
+                    let prior_input = input.value();
+                    input.set_value(&format!("{}{}", prefill, prior_input));
+                    input.focus().ok();
+                    self.composer_mode = ComposerMode::Quote { from, prior_input };
+                }
+                self.refresh_input_stats();
+                ctx.link().send_message(Msg::ExitSelectMode);
+                true
+            }
+            Msg::ExportSelected => {
+                let text = transcript(&self.selected_messages(), self.settings.time_format);
+                if !download_text("chat-export.txt", &text) {
+                    self.push_toast(ctx, "Couldn't export the messages", ToastKind::Error);
+                }
+                true
+            }
+            Msg::DeleteSelected => {
+                let message_ids = deletable_ids(&self.state.messages, &self.selected_ids, &self.username);
+                if message_ids.is_empty() {
+                    return false;
+                }
+                self.send_delete(ctx, message_ids);
+                ctx.link().send_message(Msg::ExitSelectMode);
+                false
+            }
+            Msg::ConfirmClear => {
+                if !answer_clear(&mut self.confirm_clear, &mut self.state, true) {
+                    return false;
+                }
+                self.unread.clear();
+                self.unread_marker = None;
+                self.last_read_sent = None;
+                true
+            }
+            Msg::CancelClear => answer_clear(&mut self.confirm_clear, &mut self.state, false),
+            Msg::CycleNotifyMode => {
+                self.settings.notify = self.settings.notify.cycled();
+                self.settings.save();
+                if self.settings.notify != NotifyMode::Off {
+                    notification::request_permission();
+                }
+                true
+            }
+            Msg::CycleNotificationSound => {
+                self.settings.notification_sound = self.settings.notification_sound.cycled();
+                self.settings.save();
+                // Each choice is played as it comes up, so they can be compared.
+                notification::play_sound(self.settings.notification_sound);
+                true
+            }
+            Msg::ToggleSendKey => {
+                self.settings.send_key = self.settings.send_key.toggled();
+                self.settings.save();
+                true
+            }
+            Msg::ToggleUserSort => {
+                self.settings.user_sort = self.settings.user_sort.toggled();
+                self.settings.save();
+                true
+            }
+            Msg::ToggleSessionReset => {
+                self.settings.reset_session_on_reconnect = !self.settings.reset_session_on_reconnect;
+                self.settings.save();
+                true
+            }
+            Msg::SessionTick => self.connection == ConnectionState::Connected,
+            Msg::CycleTimestampMode => {
+                self.settings.timestamps = self.settings.timestamps.cycled();
+                self.settings.save();
+                true
+            }
+            Msg::ToggleAvatarShape => {
+                self.settings.avatar_shape = self.settings.avatar_shape.toggled();
+                self.settings.save();
+                true
+            }
+            Msg::CycleSkinTone => {
+                self.settings.skin_tone = self.settings.skin_tone.cycled();
+                self.settings.save();
+                true
+            }
+            Msg::CycleGroupWindow => {
+                self.settings.group_window = self.settings.group_window.cycled();
+                self.settings.save();
+                true
+            }
+            Msg::ToggleSettings => {
+                self.show_settings = !self.show_settings;
+                true
+            }
+            Msg::ToggleSidebar => {
+                // Narrow screens open and close the drawer; wider ones switch
+                // focus mode, which is remembered.
+                if self.narrow {
+                    self.sidebar_open = !self.sidebar_open;
+                } else {
+                    self.settings.sidebar_hidden = !self.settings.sidebar_hidden;
+                    self.settings.save();
+                }
+                true
+            }
+            Msg::ViewportResized => {
+                let narrow = viewport_is_narrow();
+                if narrow == self.narrow {
+                    return false;
+                }
+                self.narrow = narrow;
+                self.sidebar_open = false;
+                true
+            }
+            Msg::ToggleDiagnostics => {
+                self.show_diagnostics = !self.show_diagnostics;
+                true
+            }
+            Msg::CopyDiagnostics => {
+                if copy_text(&self.diagnostics().to_json()) {
+                    self.push_toast(ctx, "Diagnostics copied", ToastKind::Info);
+                } else {
+                    self.push_toast(ctx, "Couldn't copy the diagnostics", ToastKind::Error);
+                }
+                true
+            }
+        }
+    }
+    
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        if !self.logged_in {
+            return html! {
+                <div class="flex flex-col w-screen h-screen justify-center items-center text-gray-600">
+                    <div class="text-xl mb-2">{"Please log in"}</div>
+                    <Link<Route> to={Route::Login} classes="text-blue-600 underline">
+                        {"Go to login"}
+                    </Link<Route>>
+                </div>
+            };
+        }
+
+        let visible = self.visible_messages();
+        let submit = ctx.link().callback(|_| Msg::SubmitMessage);
+        let input_changed = ctx.link().callback(|_| Msg::InputChanged);
+        let toggle_emoji = ctx.link().callback(|_| Msg::ToggleEmojiPicker);
+        let code_mode = self.composer_mode == ComposerMode::Code;
+        let toggle_code = ctx.link().callback(|_| Msg::ToggleCodeMode);
+        let set_code_language = ctx.link().callback(|e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            Msg::SetCodeLanguage(input.value())
+        });
+        let on_keydown = ctx.link().callback(|e: KeyboardEvent| Msg::HandleKeyDown(e));
+        let toggle_settings = ctx.link().callback(|_| Msg::ToggleSettings);
+        let request_clear = ctx.link().callback(|_| Msg::RequestClear);
+        let on_scroll = ctx.link().callback(|_: Event| Msg::MessagesScrolled);
+        let on_search = ctx.link().callback(|e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            Msg::SetSearch(input.value())
+        });
+        
+        // Dragging a file over the chat shows the drop overlay; dropping it
+        // sends the first file.
+        let on_drag_over = ctx.link().callback(|e: DragEvent| {
+            e.prevent_default();
+            Msg::SetDragging(true)
+        });
+        let on_drop = ctx.link().callback(|e: DragEvent| {
+            e.prevent_default();
+            let file = e.data_transfer().and_then(|t| t.files()).and_then(|files| files.get(0));
+            file.map_or(Msg::SetDragging(false), Msg::FileDropped)
+        });
+
+        let density = density_classes(self.settings.density);
+        let skip_to_input = {
+            let chat_input = self.chat_input.clone();
+            Callback::from(move |e: MouseEvent| {
+                e.prevent_default();
+                if let Some(input) = chat_input.cast::<HtmlTextAreaElement>() {
+                    input.focus().ok();
+                }
+            })
+        };
+
+        // Create typing indicator text
+        let typing_now = without_blocked(&self.state.typing_users, &self.settings.blocked);
+        let typing_visible = !typing_now.is_empty();
+        let typing_users = if typing_visible {
+            typing_now
+        } else {
+            without_blocked(&self.state.typing_display, &self.settings.blocked)
+        };
+        let typing_text = if !typing_users.is_empty() {
+            if typing_users.len() == 1 {
+                format!("{} is typing...", typing_users[0])
+            } else if typing_users.len() == 2 {
+                format!("{} and {} are typing...", typing_users[0], typing_users[1])
+            } else {
+                String::from("Several people are typing...")
+            }
+        } else {
+            String::new()
+        };
+        
+        let sidebar_visible = if self.narrow { self.sidebar_open } else { !self.settings.sidebar_hidden };
+
+        // Tab order follows the markup: skip link, sidebar, messages, composer.
+        html! {
+            <div class="flex w-screen">
+                <a
+                    href={format!("#{}", MESSAGE_INPUT_ID)}
+                    onclick={skip_to_input}
+                    class="skip-link px-3 py-2 text-sm text-white bg-blue-600 rounded"
+                >
+                    {"Skip to message input"}
+                </a>
+                {
+                    if self.narrow && self.sidebar_open {
+                        html! {
+                            <div
+                                onclick={ctx.link().callback(|_| Msg::ToggleSidebar)}
+                                class="fixed inset-0 z-30 bg-black bg-opacity-30"
+                            ></div>
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+                <div class={sidebar_class(sidebar_visible, self.narrow)}>
+                    <div class="flex justify-between items-center p-3">
+                        <div class="text-xl">{"Conversations"}</div>
+                        <button
+                            onclick={ctx.link().callback(|_| Msg::ToggleSidebar)}
+                            title="Hide sidebar"
+                            class="px-1 text-gray-500 hover:text-gray-700"
+                        >
+                            {"⇤"}
+                        </button>
+                    </div>
+                    {
+                        self.conversations.iter().map(|conversation| {
+                            let active = self.unread.active() == Some(conversation.as_str());
+                            let open = {
+                                let conversation = conversation.clone();
+                                ctx.link().callback(move |_| Msg::SwitchConversation(conversation.clone()))
+                            };
+                            html! {
+                                <div class="px-3 mb-1">
+                                    <button
+                                        onclick={open}
+                                        aria-current={active.then_some("true")}
+                                        class={classes!(
+                                            "flex", "w-full", "px-2", "py-1", "justify-between", "items-center", "rounded-lg",
+                                            if active { "bg-white" } else { "hover:bg-white" },
+                                        )}
+                                    >
+                                        <div class="text-sm">{format!("# {}", conversation)}</div>
+                                        {
+                                            match self.unread.count(conversation) {
+                                                0 => html! {},
+                                                n => html! {
+                                                    <div class="px-2 text-xs text-white bg-red-500 rounded-full">{n}</div>
+                                                },
+                                            }
+                                        }
+                                    </button>
+                                </div>
+                            }
+                        }).collect::<Html>()
+                    }
+                    <div class="flex justify-between items-baseline p-3">
+                        <div class="text-xl">{"Users"}</div>
+                        <div class="text-xs text-gray-500">
+                            {occupancy_label(self.online_count(), self.state.capacity)}
+                        </div>
+                    </div>
+                    {
+                        if pane_state(self.loaded(), self.other_user_count()) == PaneState::Empty {
+                            empty_state("👋", "No one else is here yet")
+                        } else {
+                            html! {}
+                        }
+                    }
+                    {
+                        sort_users(
+                            &self.state.users,
+                            self.settings.user_sort,
+                            |u| (u.id.as_str(), u.name.as_str()),
+                            |id| self.presence_of(id),
+                        )
+                        .iter().map(|u| {
+                            let status = self.presence_of(&u.id);
+                            let dot = match status {
+                                PresenceStatus::Online => "bg-green-500",
+                                PresenceStatus::Away => "bg-yellow-400",
+                            };
+                            let highlight = if self.highlighted_user.as_ref() == Some(&u.id) {
+                                "ring-2 ring-blue-400"
+                            } else {
+                                ""
+                            };
+                            html!{
+                                <div
+                                    id={user_dom_id(&u.id)}
+                                    class={format!("flex m-3 bg-white rounded-lg p-2 transition-shadow {}", highlight)}
+                                >
+                                    <div>
+                                        <img class={classes!("w-12", "h-12", avatar_class(self.settings.avatar_shape))} src={u.avatar.clone()} alt="avatar"/>
+                                    </div>
+                                    <div class="flex-grow p-3">
+                                        <div class="flex text-xs justify-between">
+                                            <div class={classes!("font-medium", accent_for_name(&u.id).text)}>{u.name.clone()}</div>
+                                        </div>
+                                        <div class="flex items-center text-xs text-gray-400">
+                                            <span class={format!("w-2 h-2 mr-1 rounded-full {}", dot)}></span>
+                                            {status.label()}
+                                            {
+                                                if self.settings.blocked.contains(&u.id) {
+                                                    let name = u.id.clone();
+                                                    let unblock = ctx.link().callback(move |_| Msg::UnblockUser(name.clone()));
+                                                    html! {
+                                                        <>
+                                                            {" · Blocked · "}
+                                                            <button onclick={unblock} class="underline hover:text-gray-700">{"Unblock"}</button>
+                                                        </>
+                                                    }
+                                                } else {
+                                                    html! {}
+                                                }
+                                            }
+                                        </div>
+                                    </div>
+                                </div>
+                            }
+                        }).collect::<Html>()
+                    }
+                </div>
+                <div class="relative grow min-w-0 h-screen flex flex-col" ondragover={on_drag_over} ondrop={on_drop}>
+                    {
+                        if self.dragging {
+                            html! {
+                                <div
+                                    ondragleave={ctx.link().callback(|_| Msg::SetDragging(false))}
+                                    class="absolute inset-0 z-30 flex justify-center items-center text-lg font-medium text-blue-700 bg-blue-50 bg-opacity-90 border-4 border-dashed border-blue-400"
+                                >
+                                    {"Drop an image to send it"}
+                                </div>
+                            }
+                        } else {
+                            html! {}
+                        }
+                    }
+                    <div class="w-full h-14 border-b-2 border-gray-300 flex justify-between items-center">
+                        <div class="flex items-baseline p-3">
+                            {
+                                if !sidebar_visible || self.narrow {
+                                    html! {
+                                        <button
+                                            onclick={ctx.link().callback(|_| Msg::ToggleSidebar)}
+                                            title="Show sidebar"
+                                            class="mr-3 text-gray-500 hover:text-gray-700"
+                                        >
+                                            {"☰"}
+                                        </button>
+                                    }
+                                } else {
+                                    html! {}
+                                }
+                            }
+                            <div class="text-xl">{"💬 Chat!"}</div>
+                            {
+                                if self.connection == ConnectionState::Connected {
+                                    html! {
+                                        <div
+                                            class="ml-3 text-xs text-gray-400"
+                                            title={format!("Connected since {}", format_time(self.connected_since, self.settings.time_format))}
+                                        >
+                                            {format!("Online for {}", format_duration(js_sys::Date::now() - self.connected_since))}
+                                        </div>
+                                    }
+                                } else {
+                                    html! {}
+                                }
+                            }
+                        </div>
+                        <div class="flex items-center mx-3">
+                            <input
+                                type="search"
+                                value={self.search_query.clone()}
+                                oninput={on_search}
+                                placeholder="Search messages"
+                                class="w-48 mr-2 px-3 py-1 text-sm bg-gray-100 rounded-full outline-none"
+                            />
+                            <button
+                                onclick={ctx.link().callback(|_| Msg::CopyInviteLink)}
+                                title="Copy invite link"
+                                class="p-2 text-sm text-gray-500 hover:text-gray-700 focus:outline-none"
+                            >
+                                {"🔗"}
+                            </button>
+                            <button
+                                onclick={request_clear}
+                                title="Clear conversation"
+                                class="p-2 text-gray-500 hover:text-gray-700 focus:outline-none"
+                            >
+                                {"🗑️"}
+                            </button>
+                            <button
+                                onclick={toggle_settings}
+                                title="Settings"
+                                class="p-2 text-gray-500 hover:text-gray-700 focus:outline-none"
+                            >
+                                {"⚙️"}
+                            </button>
+                        </div>
+                    </div>
+                    { self.view_connection_banner(ctx) }
+                    { self.view_selection_bar(ctx) }
+                    { self.view_pinned(ctx) }
+                    {
+                        if self.unread_divider_index(&visible).is_some() {
+                            html! {
+                                <button
+                                    onclick={ctx.link().callback(|_| Msg::JumpToUnread)}
+                                    class="w-full py-1 text-xs font-medium text-red-600 bg-red-50 hover:bg-red-100"
+                                >
+                                    {"New messages ↓ Jump"}
+                                </button>
+                            }
+                        } else {
+                            html! {}
+                        }
+                    }
+                    <div
+                        ref={self.message_list.clone()}
+                        onscroll={on_scroll}
+                        class="w-full grow overflow-auto border-b-2 border-gray-300"
+                    >
+                        {
+                            if self.loading_history {
+                                html! {
+                                    <div class="flex justify-center items-center py-2 text-xs text-gray-500" role="status">
+                                        <span class="w-3 h-3 mr-2 border-2 border-gray-300 border-t-blue-500 rounded-full animate-spin" aria-hidden="true"></span>
+                                        {"Loading earlier messages…"}
+                                    </div>
+                                }
+                            } else {
+                                html! {}
+                            }
+                        }
+                        {
+                            if pane_state(self.loaded(), visible.len()) == PaneState::Empty {
+                                empty_state("💬", "No messages — say hi!")
+                            } else {
+                                html! {}
+                            }
+                        }
+                        {
+                            let unread_divider = self.unread_divider_index(&visible);
+                            let now = js_sys::Date::now();
+                            visible.iter().enumerate().map(|(index, m)| html! {
+                                <key={m.row_key(index)}>
+                                    {
+                                        match new_day_at(&visible, index) {
+                                            Some(time) => view_day_separator(&day_label(time, now)),
+                                            None => html! {},
+                                        }
+                                    }
+                                    {
+                                        if unread_divider == Some(index) {
+                                            view_unread_divider()
+                                        } else {
+                                            html! {}
+                                        }
+                                    }
+                                    {
+                                        // Dividers above a message always start a new group.
+                                        let grouped = unread_divider != Some(index)
+                                            && new_day_at(&visible, index).is_none()
+                                            && self.groups_with_previous(&visible, index);
+                                        self.view_message(ctx, m, &density, grouped)
+                                    }
+                                </>
+                            }).collect::<Html>()
+                        }
+                        
+                        {
+                            // Display typing indicators
+                            if !typing_users.is_empty() {
+                                let overflow = avatar_overflow(typing_users.len(), MAX_TYPING_AVATARS);
+                                html! {
+                                    <div
+                                        aria-hidden={(!typing_visible).to_string()}
+                                        class={format!(
+                                            "flex items-center px-6 py-2 text-sm italic text-gray-500 transition-opacity duration-300 {}",
+                                            if typing_visible { "opacity-100" } else { "opacity-0" },
+                                        )}
+                                    >
+                                        <div class="flex items-center mr-2">
+                                            {
+                                                typing_users.iter().take(overflow.shown).map(|name| {
+                                                    let avatar = self
+                                                        .state
+                                                        .users
+                                                        .iter()
+                                                        .find(|u| &u.id == name)
+                                                        .map(|u| u.avatar.clone())
+                                                        .unwrap_or_else(|| avatar_url(name));
+                                                    html! {
+                                                        <img
+                                                            class={classes!("w-6", "h-6", "-mr-1", "border-2", "border-white", avatar_class(self.settings.avatar_shape))}
+                                                            src={avatar}
+                                                            alt={name.clone()}
+                                                            title={name.clone()}
+                                                        />
+                                                    }
+                                                }).collect::<Html>()
+                                            }
+                                            {
+                                                if overflow.extra > 0 {
+                                                    html! {
+                                                        <div class="ml-2 text-xs not-italic">{format!("+{}", overflow.extra)}</div>
+                                                    }
+                                                } else {
+                                                    html! {}
+                                                }
+                                            }
+                                        </div>
+                                        {typing_text}
+                                        <div class="flex items-center ml-2">
+                                            <div class="w-2 h-2 bg-gray-400 rounded-full mr-1 animate-bounce"></div>
+                                            <div class="w-2 h-2 bg-gray-400 rounded-full mr-1 animate-bounce"></div>
+                                            <div class="w-2 h-2 bg-gray-400 rounded-full animate-bounce"></div>
+                                        </div>
+                                    </div>
+                                }
+                            } else {
+                                html! {}
+                            }
+                        }
+                    </div>
+                    {
+                        match &self.composer_mode {
+                            ComposerMode::Normal => html! {},
+                            ComposerMode::Code => composer_mode_hint("Sending as code"),
+                            ComposerMode::Quote { from, .. } => {
+                                composer_mode_hint(&format!("Quoting {}", from))
+                            }
+                            ComposerMode::Edit { .. } => composer_mode_hint("Editing message"),
+                        }
+                    }
+                    <div class="w-full min-h-14 flex px-3 py-2 items-end relative">
+                        <button 
+                            onclick={toggle_emoji}
+                            class="p-2 text-gray-500 hover:text-gray-700 focus:outline-none"
+                        >
+                            {"😀"}
+                        </button>
+                        <button
+                            onclick={toggle_code}
+                            title="Send as code snippet"
+                            class={if code_mode {
+                                "p-2 font-mono text-sm text-blue-600 focus:outline-none"
+                            } else {
+                                "p-2 font-mono text-sm text-gray-500 hover:text-gray-700 focus:outline-none"
+                            }}
+                        >
+                            {"</>"}
+                        </button>
+                        {
+                            if code_mode {
+                                html! {
+                                    <input
+                                        value={self.code_language.clone()}
+                                        oninput={set_code_language}
+                                        placeholder="language"
+                                        class="w-24 ml-1 px-2 py-1 text-xs font-mono bg-gray-100 rounded outline-none"
+                                    />
+                                }
+                            } else {
+                                html! {}
+                            }
+                        }
+                        <textarea
+                            ref={self.chat_input.clone()}
+                            id={MESSAGE_INPUT_ID}
+                            rows="1"
+                            placeholder={if code_mode { "Code" } else { "Message" }}
+                            class={format!(
+                                "block w-full py-2 pl-4 pr-2 mx-3 bg-gray-100 rounded-2xl resize-none overflow-y-hidden focus:text-gray-700 {}",
+                                if code_mode { "font-mono" } else { "" },
+                            )}
+                            name="message"
+                            onkeydown={on_keydown}
+                            oninput={input_changed}
+                            required=true
+                        />
+                        <button 
+                            onclick={submit} 
+                            class="p-3 shadow-sm bg-blue-600 w-10 h-10 rounded-full flex justify-center items-center color-white"
+                        >
+                            <svg fill="#000000" viewBox="0 0 24 24" xmlns="http://www.w3.org/2000/svg" class="fill-white">
+                                <path d="M0 0h24v24H0z" fill="none"></path><path d="M2.01 21L23 12 2.01 3 2 10l15 2-15 2z"></path>
+                            </svg>
                         </button>
                         
                         {
-                            // Emoji picker
-                            if self.show_emoji_picker {
-                                let emojis = vec!["😀", "😂", "😍", "🥳", "😎", "🤔", "👍", "❤️", "🎉", "🔥", "👏", "✅", "🙏", "🤣", "😊", "🥰"];
-                                
-                                html! {
-                                    <div class="absolute bottom-16 left-4 bg-white shadow-lg rounded-lg p-2 grid grid-cols-8 gap-1 z-10">
+                            // Emoji picker
+                            if self.show_emoji_picker {
+                                html! {
+                                    <div class={format!(
+                                        "absolute {} left-4 max-h-48 overflow-y-auto bg-white shadow-lg rounded-lg p-2 grid grid-cols-8 gap-1 z-10",
+                                        self.emoji_placement.picker_class(),
+                                    )}>
+                                        {
+                                            if self.settings.recent_emoji.is_empty() {
+                                                html! {}
+                                            } else {
+                                                html! {
+                                                    <>
+                                                        <div class="col-span-8 text-xs text-gray-400">{"Recent"}</div>
+                                                        {
+                                                            self.settings.recent_emoji.iter().map(|glyph| {
+                                                                let emoji = glyph.clone();
+                                                                let onclick = ctx.link().callback(move |_| Msg::SelectEmoji(emoji.clone()));
+                                                                html! {
+                                                                    <button {onclick} class="p-1 text-xl hover:bg-gray-100 rounded">
+                                                                        {glyph.clone()}
+                                                                    </button>
+                                                                }
+                                                            }).collect::<Html>()
+                                                        }
+                                                        <div class="col-span-8 text-xs text-gray-400">{"All"}</div>
+                                                    </>
+                                                }
+                                            }
+                                        }
+                                        {
+                                            self.picker_emoji.iter().map(|emoji| {
+                                                let glyph = apply_skin_tone(emoji.glyph, self.settings.skin_tone);
+                                                let emoji_clone = glyph.clone();
+                                                let onclick = ctx.link().callback(move |_| Msg::SelectEmoji(emoji_clone.clone()));
+
+                                                html! {
+                                                    <button
+                                                        onclick={onclick}
+                                                        title={format!(":{}:", emoji.shortcode)}
+                                                        class="p-1 text-xl hover:bg-gray-100 rounded"
+                                                    >
+                                                        {glyph}
+                                                    </button>
+                                                }
+                                            }).collect::<Html>()
+                                        }
+                                    </div>
+                                }
+                            } else {
+                                html! {}
+                            }
+                        }
+                    </div>
+                    { self.view_input_stats() }
+                </div>
+                { self.view_settings(ctx) }
+                { self.view_diagnostics(ctx) }
+                { self.view_confirm_clear(ctx) }
+                { self.view_inactivity(ctx) }
+                { self.view_quote_popover(ctx) }
+                { self.view_context_menu(ctx) }
+                { self.view_report_dialog(ctx) }
+                { self.view_toasts(ctx) }
+            </div>
+        }
+    }
+
+    fn rendered(&mut self, ctx: &Context<Self>, _first_render: bool) {
+        // The entrance animation only applies for one render.
+        self.state.just_added = None;
+
+        if should_autofocus(self.input_autofocused, self.loaded(), is_touch_device(), focus_is_taken()) {
+            if let Some(input) = self.chat_input.cast::<HtmlTextAreaElement>() {
+                input.focus().ok();
+                self.input_autofocused = true;
+            }
+        }
+
+        let list = match self.message_list.cast::<web_sys::Element>() {
+            Some(list) => list,
+            None => return,
+        };
+        // A list pinned to the bottom stays there as messages come in; an
+        // offset is only applied when coming back to the conversation, or
+        // shifted to make room for older messages added above.
+        let anchor = self.history_anchor.take();
+        match self.scroll {
+            ScrollPosition::Bottom => list.set_scroll_top(list.scroll_height()),
+            ScrollPosition::Offset(_) if anchor.is_some() => {
+                let (top, height) = anchor.unwrap_or_default();
+                let top = prepended_scroll_top(top, height, list.scroll_height() as f64);
+                list.set_scroll_top(top as i32);
+                self.scroll = ScrollPosition::Offset(top);
+            }
+            ScrollPosition::Offset(top) if self.restore_scroll => list.set_scroll_top(top as i32),
+            ScrollPosition::Offset(_) => {}
+        }
+        self.restore_scroll = false;
+
+        // A linked message is jumped to on the first render that has it.
+        let row = self.link_target.as_ref().and_then(|target| {
+            web_sys::window()?
+                .document()?
+                .get_element_by_id(&message_dom_id(target))
+        });
+        if let Some(row) = row {
+            row.scroll_into_view();
+            ctx.link().send_message(Msg::ScrollPositionChanged(scroll_position(
+                list.scroll_top() as f64,
+                list.scroll_height() as f64,
+                list.client_height() as f64,
+            )));
+            if let Some(target) = self.link_target.take() {
+                ctx.link().send_message(Msg::HighlightMessage(target));
+            }
+        }
+    }
+}
+
+// The current DOM selection's text and the top-left corner of its bounding
+// box, if anything non-blank is selected.
+fn current_selection() -> Option<(String, f64, f64)> {
+    let selection = web_sys::window()?.get_selection().ok()??;
+    let text: String = selection.to_string().into();
+    if text.trim().is_empty() {
+        return None;
+    }
+    let rect = selection.get_range_at(0).ok()?.get_bounding_client_rect();
+    Some((text, rect.left(), rect.top()))
+}
+
+// The name to chat as, or `None` when there's no `User` context or no name
+// in it, e.g. when the chat route is opened before logging in.
+fn session_username(user: Option<&User>) -> Option<String> {
+    let username = user?.username.borrow().trim().to_string();
+    (!username.is_empty()).then_some(username)
+}
+
+// Grows the message input with its content up to a limit, then scrolls.
+// The height is reset first so the content's height is measured afresh
+// when text is removed.
+fn fit_input_height(input: &HtmlTextAreaElement) {
+    input.set_attribute("style", "height: auto").ok();
+    let (height, scroll) = composer_height(input.scroll_height() as f64, COMPOSER_MAX_HEIGHT_PX);
+    let overflow = if scroll { "auto" } else { "hidden" };
+    input
+        .set_attribute("style", &format!("height: {}px; overflow-y: {}", height, overflow))
+        .ok();
+}
+
+// Plain-text copy of messages, one "[time] name: text" line each.
+fn transcript(messages: &[&MessageData], format: TimeFormat) -> String {
+    messages
+        .iter()
+        .map(|m| {
+            let time = m.time.map(|ms| format_time(ms, format)).unwrap_or_default();
+            format!("[{}] {}: {}", time, m.from, m.message)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Epoch millis of `messages[index]` when it starts a new day, including the
+// first message, so a separator can be drawn above it.
+fn new_day_at(messages: &[&MessageData], index: usize) -> Option<f64> {
+    let time = messages.get(index)?.time?;
+    let previous = index.checked_sub(1).and_then(|i| messages[i].time);
+    match previous {
+        Some(previous) if same_day(previous, time) => None,
+        _ => Some(time),
+    }
+}
+
+fn viewport_is_narrow() -> bool {
+    web_sys::window()
+        .and_then(|w| w.inner_width().ok())
+        .and_then(|width| width.as_f64())
+        .map_or(false, is_narrow)
+}
+
+// Whether the device takes touch input, where focusing an input brings up the
+// on-screen keyboard.
+fn is_touch_device() -> bool {
+    web_sys::window().is_some_and(|w| w.navigator().max_touch_points() > 0)
+}
+
+// Whether something other than the page itself already has the focus.
+fn focus_is_taken() -> bool {
+    web_sys::window()
+        .and_then(|w| w.document())
+        .and_then(|d| d.active_element())
+        .is_some_and(|element| element.tag_name() != "BODY")
+}
+
+fn is_entering(message: &MessageData, just_added: Option<&str>) -> bool {
+    just_added.is_some() && message.id.as_deref() == just_added
+}
+
+const UNREAD_DIVIDER_ID: &str = "unread-divider";
+const MESSAGE_INPUT_ID: &str = "message-input";
+
+fn view_unread_divider() -> Html {
+    html! {
+        <div id={UNREAD_DIVIDER_ID} class="flex items-center px-6 my-2 text-xs font-medium text-red-500">
+            <div class="grow border-t border-red-300"></div>
+            <div class="px-2">{"New messages"}</div>
+            <div class="grow border-t border-red-300"></div>
+        </div>
+    }
+}
+
+// A file chip with an icon, name and size, linking to the file. Images are
+// shown above it. Nothing links out unless the URL is a plain web link.
+fn view_attachment(attachment: &Attachment) -> Html {
+    let href = is_safe_url(&attachment.url).then(|| attachment.url.clone());
+    html! {
+        <div class="mt-1">
+            {
+                if attachment.is_image() && href.is_some() {
+                    html! { <img class="mb-1 max-h-64 rounded" src={href.clone()} alt={attachment.name.clone()}/> }
+                } else {
+                    html! {}
+                }
+            }
+            <a
+                href={href}
+                target="_blank"
+                rel="noopener noreferrer"
+                download={attachment.name.clone()}
+                class="inline-flex items-center px-2 py-1 bg-white border border-gray-300 rounded hover:bg-gray-50"
+            >
+                <span class="mr-2 text-base">{icon_for_mime(&attachment.mime)}</span>
+                <span class="mr-2 font-medium truncate">{attachment.name.clone()}</span>
+                <span class="text-gray-400">{format_size(attachment.size)}</span>
+            </a>
+        </div>
+    }
+}
+
+fn view_day_separator(label: &str) -> Html {
+    html! {
+        <div role="separator" class="flex items-center px-6 my-2 text-xs font-medium text-gray-400">
+            <div class="grow border-t border-gray-200"></div>
+            <div class="px-2">{label}</div>
+            <div class="grow border-t border-gray-200"></div>
+        </div>
+    }
+}
+
+// DOM id of a user's sidebar entry.
+fn user_dom_id(user_id: &str) -> String {
+    format!("user-{}", user_id)
+}
+
+// DOM id of a message's row, used to jump to it.
+fn message_dom_id(message_id: &str) -> String {
+    format!("message-{}", message_id)
+}
+
+fn new_message_id() -> String {
+    format!(
+        "{:x}-{:x}",
+        js_sys::Date::now() as u64,
+        (js_sys::Math::random() * u32::MAX as f64) as u32
+    )
+}
+
+fn composer_mode_hint(label: &str) -> Html {
+    html! {
+        <div class="flex justify-between px-6 pt-1 text-xs text-gray-500">
+            <span>{label.to_string()}</span>
+            <span>{"Esc to cancel"}</span>
+        </div>
+    }
+}
+
+fn empty_state(icon: &str, text: &str) -> Html {
+    html! {
+        <div class="flex flex-col items-center p-6 text-sm text-gray-400">
+            <div class="text-4xl mb-2">{icon}</div>
+            <div>{text}</div>
+        </div>
+    }
+}
+
+fn settings_row(label: &str, value: &str, onclick: Callback<MouseEvent>) -> Html {
+    html! {
+        <div class="flex justify-between items-center py-2">
+            <div class="text-sm">{label}</div>
+            <button
+                {onclick}
+                class="px-2 py-1 text-xs text-gray-600 border border-gray-300 rounded hover:bg-gray-100"
+            >
+                {value}
+            </button>
+        </div>
+    }
+}
+
+impl Chat {
+    // The local user's status comes straight from the idle tracker; everyone
+    // else is online until a presence frame says otherwise.
+    fn presence_of(&self, name: &str) -> PresenceStatus {
+        if name == self.username {
+            return self.idle.status();
+        }
+        self.state.presence
+            .get(name)
+            .copied()
+            .unwrap_or(PresenceStatus::Online)
+    }
+
+    // Remembers where the current conversation was scrolled to and restores
+    // `conversation`'s position on the next render.
+    fn switch_conversation(&mut self, conversation: &str) {
+        let current = self.unread.active().unwrap_or(GENERAL).to_string();
+        self.scroll_memory.save(&current, self.scroll);
+        self.scroll = self.scroll_memory.restore(conversation);
+        self.restore_scroll = true;
+        self.unread.activate(conversation);
+    }
+
+    fn push_toast(&mut self, ctx: &Context<Self>, text: &str, kind: ToastKind) {
+        let now = js_sys::Date::now();
+        self.toasts.push(text.to_string(), kind, now + TOAST_DURATION_MS as f64);
+        self.schedule_toast_expiry(ctx, now);
+    }
+
+    // Wakes up when the next toast is due to go, if any are showing.
+    fn schedule_toast_expiry(&mut self, ctx: &Context<Self>, now: f64) {
+        self.toast_timer = self.toasts.next_expiry().map(|at| {
+            let link = ctx.link().clone();
+            Timeout::new((at - now).max(0.0).ceil() as u32, move || link.send_message(Msg::ExpireToasts))
+        });
+    }
+
+    // The server refused our name: clear it and send the user back to the
+    // login page with the reason. Reconnects stop since they'd be refused too.
+    fn reject_login(&mut self, ctx: &Context<Self>, error: String) {
+        log::warn!("registration refused: {}", error);
+        self.reconnect_timer = None;
+        if let Some(user) = &self.user {
+            reject_name(&mut user.username.borrow_mut(), &mut user.login_error.borrow_mut(), error);
+        }
+        match ctx.link().history() {
+            Some(history) => history.push(Route::Login),
+            None => log::error!("no router to return to the login page"),
+        }
+    }
+
+    // Nothing is known until the server has answered our registration.
+    fn loaded(&self) -> bool {
+        self.connection != ConnectionState::Connecting
+    }
+
+    // Everyone in the room who isn't away, ourselves included.
+    fn online_count(&self) -> usize {
+        self.state
+            .users
+            .iter()
+            .filter(|u| self.presence_of(&u.id) == PresenceStatus::Online)
+            .count()
+    }
+
+    fn other_user_count(&self) -> usize {
+        self.state.users.iter().filter(|u| u.id != self.username).count()
+    }
+
+    fn apply_own_presence(&mut self, change: Option<PresenceStatus>) -> bool {
+        match change {
+            Some(status) => {
+                self.send_presence(status);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn send_presence(&self, status: PresenceStatus) {
+        if let Some(frame) = presence_frame(&self.username, status) {
+            self.send_ws(&frame);
+        }
+    }
+
+    fn publish(&mut self, event: ChatEvent) {
+        self.producer.send(Request::Publish(event));
+    }
+
+    fn register(&self) {
+        if self.send_ws(&register_frame(&self.username)) {
+            log::debug!("message sent successfully");
+        }
+    }
+
+    fn rejoin(&self) {
+        for frame in rejoin_frames(&self.username, self.idle.status()) {
+            self.send_ws(&frame);
+        }
+    }
+
+    fn schedule_reconnect(&mut self, ctx: &Context<Self>) {
+        if self.backoff.exhausted(self.max_reconnects) {
+            self.reconnect_timer = None;
+            self.next_attempt_at = None;
+            self.countdown = None;
+            self.connection = ConnectionState::GaveUp;
+            return;
+        }
+        let delay = self.backoff.next_delay();
+        let link = ctx.link().clone();
+        self.reconnect_timer = Some(Timeout::new(delay, move || {
+            link.send_message(Msg::Reconnect)
+        }));
+        self.next_attempt_at = Some(js_sys::Date::now() + f64::from(delay));
+        let link = ctx.link().clone();
+        self.countdown = Some(Interval::new(1_000, move || link.send_message(Msg::CountdownTick)));
+    }
+
+    // Closes the connection after the inactivity prompt went unanswered. The
+    // user list is left as it was, since nothing updates it until resuming.
+    fn suspend(&mut self) {
+        self.stop_typing();
+        self.connection = ConnectionState::Suspended;
+        self.reconnect_timer = None;
+        self.next_attempt_at = None;
+        self.countdown = None;
+        self.wss.close();
+    }
+
+    // Opens a new socket and registers again. If the socket can't even be
+    // created, the next attempt is scheduled straight away.
+    fn reconnect(&mut self, ctx: &Context<Self>) {
+        self.reconnect_timer = None;
+        self.next_attempt_at = None;
+        self.countdown = None;
+        match self.wss.reconnect() {
+            Ok(()) => self.rejoin(),
+            Err(e) => {
+                log::error!("reconnect failed: {}", e);
+                self.schedule_reconnect(ctx);
+            }
+        }
+    }
+
+    // Coalesces scroll, focus and new-message triggers into at most one
+    // receipt per READ_RECEIPT_THROTTLE_MS.
+    fn schedule_read_check(&mut self, ctx: &Context<Self>) {
+        if self.read_check.is_some() {
+            return;
+        }
+        let link = ctx.link().clone();
+        self.read_check = Some(Timeout::new(READ_RECEIPT_THROTTLE_MS, move || {
+            link.send_message(Msg::SendReadReceipt)
+        }));
+    }
+
+    // Also clears the unread divider once the newest message has been seen.
+    // Returns whether that needs a re-render.
+    fn send_read_receipt(&mut self) -> bool {
+        let focused = web_sys::window()
+            .and_then(|w| w.document())
+            .and_then(|d| d.has_focus().ok())
+            .unwrap_or(false);
+        if !focused {
+            return false;
+        }
+        let latest = self.latest_visible_message();
+        let newest = self.state.messages.iter().rev().find_map(|m| m.id.as_ref());
+        let divider_cleared = latest.is_some()
+            && latest.as_ref() == newest
+            && self.unread_marker.take().is_some();
+        let message_id = match latest {
+            Some(id) if self.last_read_sent.as_ref() != Some(&id) => id,
+            _ => return divider_cleared,
+        };
+
+        let receipt = ReadReceipt {
+            username: self.username.clone(),
+            message_id: message_id.clone(),
+        };
+        match serde_json::to_string(&receipt) {
+            Ok(data) => {
+                let sent = self.send_ws(&WebSocketMessage {
+                    message_type: MsgTypes::Read,
+                    data: Some(data),
+                    data_array: None,
+                    id: None,
+                    attachment: None,
+                    channel: None,
+                });
+                if sent {
+                    self.last_read_sent = Some(message_id);
+                }
+            }
+            Err(e) => log::error!("failed to serialize read receipt: {}", e),
+        }
+        divider_cleared
+    }
+
+    // Index in `messages` (those shown) where the unread divider goes.
+    fn unread_divider_index(&self, messages: &[&MessageData]) -> Option<usize> {
+        let last_read = self.unread_marker.as_deref()?;
+        let ids: Vec<Option<&str>> = messages.iter().map(|m| m.id.as_deref()).collect();
+        divider_index(&ids, last_read)
+    }
+
+    fn groups_with_previous(&self, messages: &[&MessageData], index: usize) -> bool {
+        let previous = match index.checked_sub(1).map(|i| messages[i]) {
+            Some(previous) if !previous.unsupported && previous.poll.is_none() => previous,
+            _ => return false,
+        };
+        let current = messages[index];
+        if current.poll.is_some() {
+            return false;
+        }
+        match (previous.time, current.time) {
+            (Some(previous_time), Some(time)) => should_group(
+                (&previous.from, previous_time),
+                (&current.from, time),
+                self.settings.group_window,
+            ),
+            _ => false,
+        }
+    }
+
+    fn schedule_midnight(&mut self, ctx: &Context<Self>) {
+        let delay = ms_until_midnight(js_sys::Date::now());
+        let link = ctx.link().clone();
+        // A little past midnight so the new day has definitely started.
+        self.midnight_timer = Some(Timeout::new(delay as u32 + 1_000, move || {
+            link.send_message(Msg::NewDay)
+        }));
+    }
+
+    fn send_reaction(&self, message_id: String, emoji: String, added: bool) {
+        let update = ReactionUpdate {
+            username: self.username.clone(),
+            message_id,
+            emoji,
+            added,
+        };
+        match serde_json::to_string(&update) {
+            Ok(data) => {
+                self.send_ws(&WebSocketMessage {
+                    message_type: MsgTypes::Reaction,
+                    data: Some(data),
+                    data_array: None,
+                    id: None,
+                    attachment: None,
+                    channel: None,
+                });
+            }
+            Err(e) => log::error!("failed to serialize reaction: {}", e),
+        }
+    }
+
+    // The current time by the server's clock, as far as we know it, so the
+    // times we stamp on messages sort with the ones the server stamps.
+    fn now(&self) -> f64 {
+        js_sys::Date::now() + self.clock_offset
+    }
+
+    fn send_time_sync(&self) {
+        let sync = TimeSync {
+            client_time: js_sys::Date::now(),
+            server_time: None,
+        };
+        match serde_json::to_string(&sync) {
+            Ok(data) => {
+                self.send_ws(&WebSocketMessage {
+                    message_type: MsgTypes::TimeSync,
+                    data: Some(data),
+                    data_array: None,
+                    id: None,
+                    attachment: None,
+                    channel: None,
+                });
+            }
+            Err(e) => log::error!("failed to serialize time sync: {}", e),
+        }
+    }
+
+    // Asks for the page of messages before the oldest one shown. Returns
+    // whether a request went out, so the spinner can be shown.
+    fn request_history(&mut self) -> bool {
+        if self.loading_history {
+            return false;
+        }
+        let request = HistoryRequest {
+            before_id: self.state.messages.iter().find(|m| !m.pending).and_then(|m| m.id.clone()),
+            limit: HISTORY_PAGE_SIZE,
+        };
+        match serde_json::to_string(&request) {
+            Ok(data) => {
+                self.loading_history = self.send_ws(&WebSocketMessage {
+                    message_type: MsgTypes::History,
+                    data: Some(data),
+                    data_array: None,
+                    id: None,
+                    attachment: None,
+                    channel: None,
+                });
+            }
+            Err(e) => log::error!("failed to serialize history request: {}", e),
+        }
+        self.loading_history
+    }
+
+    fn send_poll(&self, poll: &Poll) {
+        match serde_json::to_string(poll) {
+            Ok(data) => {
+                self.send_ws(&WebSocketMessage {
+                    message_type: MsgTypes::Poll,
+                    data: Some(data),
+                    data_array: None,
+                    id: Some(new_message_id()),
+                    attachment: None,
+                    channel: None,
+                });
+            }
+            Err(e) => log::error!("failed to serialize poll: {}", e),
+        }
+    }
+
+    fn send_report(&self, message_id: String, reason: ReportReason) -> bool {
+        let report = ReportFrame {
+            username: self.username.clone(),
+            message_id,
+            reason,
+        };
+        match serde_json::to_string(&report) {
+            Ok(data) => self.send_ws(&WebSocketMessage {
+                message_type: MsgTypes::Report,
+                data: Some(data),
+                data_array: None,
+                id: None,
+                attachment: None,
+                channel: None,
+            }),
+            Err(e) => {
+                log::error!("failed to serialize report: {}", e);
+                false
+            }
+        }
+    }
+
+    // Sends new text for one of our messages. The change shows once the
+    // server relays it back, like everyone else's edits.
+    fn send_edit(&mut self, ctx: &Context<Self>, message_id: String, text: String) {
+        let message = self.state.messages.iter().find(|m| m.id.as_ref() == Some(&message_id));
+        let message = match message {
+            Some(message) if message.message == text => return,
+            Some(message) => message,
+            None => return,
+        };
+        // The window may have closed while the edit was being typed.
+        if !can_edit(message, self.now(), edit_window_ms() as f64) {
+            self.push_toast(ctx, "This message can no longer be edited", ToastKind::Error);
+            return;
+        }
+        if self.connection != ConnectionState::Connected {
+            self.push_toast(ctx, "Can't edit messages while offline", ToastKind::Error);
+            return;
+        }
+        let update = EditUpdate {
+            username: self.username.clone(),
+            message_id,
+            text,
+        };
+        match serde_json::to_string(&update) {
+            Ok(data) => {
+                self.send_ws(&WebSocketMessage {
+                    message_type: MsgTypes::Edit,
+                    data: Some(data),
+                    data_array: None,
+                    id: None,
+                    attachment: None,
+                    channel: None,
+                });
+            }
+            Err(e) => log::error!("failed to serialize edit: {}", e),
+        }
+    }
+
+    // Asks the server to take back our messages. They go once it relays the
+    // deletion back, like anyone else's.
+    fn send_delete(&mut self, ctx: &Context<Self>, message_ids: Vec<String>) {
+        if self.connection != ConnectionState::Connected {
+            self.push_toast(ctx, "Can't delete messages while offline", ToastKind::Error);
+            return;
+        }
+        let update = DeleteUpdate {
+            username: self.username.clone(),
+            message_ids,
+        };
+        match serde_json::to_string(&update) {
+            Ok(data) => {
+                self.send_ws(&WebSocketMessage {
+                    message_type: MsgTypes::Delete,
+                    data: Some(data),
+                    data_array: None,
+                    id: None,
+                    attachment: None,
+                    channel: None,
+                });
+            }
+            Err(e) => log::error!("failed to serialize delete: {}", e),
+        }
+    }
+
+    // The selected messages, in the order they're shown.
+    fn selected_messages(&self) -> Vec<&MessageData> {
+        self.state
+            .messages
+            .iter()
+            .filter(|m| m.id.as_ref().map_or(false, |id| self.selected_ids.contains(id)))
+            .collect()
+    }
+
+    fn send_pin(&self, message_id: String, pinned: bool) {
+        let update = PinUpdate {
+            username: self.username.clone(),
+            message_id,
+            pinned,
+        };
+        match serde_json::to_string(&update) {
+            Ok(data) => {
+                self.send_ws(&WebSocketMessage {
+                    message_type: MsgTypes::Pin,
+                    data: Some(data),
+                    data_array: None,
+                    id: None,
+                    attachment: None,
+                    channel: None,
+                });
+            }
+            Err(e) => log::error!("failed to serialize pin: {}", e),
+        }
+    }
+
+    // The id of the last message whose row has scrolled into the list's
+    // viewport, even partially.
+    fn latest_visible_message(&self) -> Option<String> {
+        let list = self.message_list.cast::<web_sys::Element>()?;
+        let bottom = list.get_bounding_client_rect().bottom();
+        let rows = list.query_selector_all("[data-message-id]").ok()?;
+        (0..rows.length())
+            .rev()
+            .filter_map(|i| rows.item(i))
+            .filter_map(|node| node.dyn_into::<web_sys::Element>().ok())
+            .find(|row| row.get_bounding_client_rect().top() < bottom)
+            .and_then(|row| row.get_attribute("data-message-id"))
+    }
+
+    fn measure_picker_placement(&self) -> Placement {
+        let viewport_height = web_sys::window()
+            .and_then(|w| w.inner_height().ok())
+            .and_then(|h| h.as_f64());
+        match (viewport_height, self.chat_input.cast::<web_sys::Element>()) {
+            (Some(viewport_height), Some(input)) => {
+                let rect = input.get_bounding_client_rect();
+                picker_placement(viewport_height, rect.top(), rect.bottom(), PICKER_MAX_HEIGHT)
+            }
+            _ => Placement::Above,
+        }
+    }
+
+    // Recomputes the composer stats. Returns whether they changed.
+    // Every change to the input's value comes through here, so the input's
+    // height is kept in step as well.
+    fn refresh_input_stats(&mut self) -> bool {
+        let input = self.chat_input.cast::<HtmlTextAreaElement>();
+        if let Some(input) = &input {
+            fit_input_height(input);
+        }
+        let stats = input.map(|input| message_stats(&input.value())).unwrap_or_default();
+        let changed = stats != self.input_stats;
+        self.input_stats = stats;
+        changed
+    }
+
+    fn request_link_preview(&mut self, ctx: &Context<Self>, message_id: String, url: String) {
+        let endpoint = match link_preview_endpoint() {
+            Some(endpoint) => endpoint,
+            None => return,
+        };
+        // GIF links are already rendered inline as images.
+        if url.ends_with(".gif") || self.link_previews.contains_key(&message_id) {
+            return;
+        }
+
+        self.link_previews.insert(message_id.clone(), None);
+        ctx.link().send_future(async move {
+            let preview = fetch_preview(&endpoint, &url).await;
+            Msg::LinkPreviewLoaded { message_id, preview }
+        });
+    }
+
+    // Sends a chat message and shows it as pending until the server echoes it.
+    // While offline it's queued instead and goes out after reconnecting.
+    fn send_message(&mut self, ctx: &Context<Self>, text: String, attachment: Option<Attachment>) {
+        let id = new_message_id();
+        let channel = self.active_channel();
+        let connected = self.connection == ConnectionState::Connected;
+        if !(connected && self.send_text(&id, &text, attachment.as_ref(), channel.as_deref())) {
+            self.outbox.push(id.clone(), text.clone());
+            self.push_toast(ctx, "Offline — message will be sent on reconnect", ToastKind::Info);
+        }
+        self.state.push_message(MessageData {
+            id: Some(id),
+            pending: true,
+            unsupported: false,
+            edited: false,
+            channel,
+            stream: StreamState::Whole,
+            attachment,
+            poll: None,
+            from: self.username.clone(),
+            message: text,
+            timestamp: None,
+            time: Some(self.now()),
+        });
+    }
+
+    // The channel new messages go to, or `None` for the general room.
+    fn active_channel(&self) -> Option<String> {
+        self.unread.active().filter(|c| *c != GENERAL).map(String::from)
+    }
+
+    // Messages in the conversation being viewed, in order.
+    fn visible_messages(&self) -> Vec<&MessageData> {
+        let active = self.unread.active().unwrap_or(GENERAL);
+        self.state.messages.iter().filter(|m| m.conversation() == active).collect()
+    }
+
+    fn send_text(&self, id: &str, text: &str, attachment: Option<&Attachment>, channel: Option<&str>) -> bool {
+        self.send_ws(&message_frame(id, text, attachment, channel))
+    }
+
+    // Sends what was queued while offline, in the order it was written, with
+    // the attachment and channel of its pending copy.
+    fn flush_outbox(&mut self) {
+        let (state, tx) = (&self.state, &self.wss.tx);
+        let sent = self.outbox.flush(|id, text| {
+            let pending = state.pending(id);
+            let attachment = pending.and_then(|m| m.attachment.as_ref());
+            let channel = pending.and_then(|m| m.channel.as_deref());
+            queue_frame(tx, &message_frame(id, text, attachment, channel))
+        });
+        if sent.is_empty() {
+            return;
+        }
+        let now = js_sys::Date::now();
+        for id in &sent {
+            self.state.resend_pending(id, now);
+        }
+        // Re-placed messages have already animated in once.
+        self.state.just_added = None;
+    }
+
+    // A grouped message drops its avatar and name/time header and sits under
+    // the previous message from the same sender.
+    fn view_message(
+        &self,
+        ctx: &Context<Self>,
+        m: &MessageData,
+        density: &DensityClasses,
+        grouped: bool,
+    ) -> Html {
+        if m.unsupported {
+            return html! {
+                <div class="px-6 py-1 text-xs text-center text-gray-400">
+                    {m.message.clone()}
+                </div>
+            };
+        }
+
+        let revealed = m.id.as_ref().map_or(false, |id| self.revealed_blocked.contains(id));
+        if self.settings.blocked.contains(&m.from) && !revealed {
+            let show = m.id.clone().map(|id| {
+                let reveal = ctx.link().callback(move |_| Msg::RevealBlocked(id.clone()));
+                html! { <button onclick={reveal} class="ml-1 underline hover:text-gray-600">{"show"}</button> }
+            });
+            return html! {
+                <div class="px-6 py-1 text-xs text-center text-gray-400">
+                    {"Blocked message —"}
+                    { for show }
+                </div>
+            };
+        }
+
+        let avatar = self
+            .state
+            .users
+            .iter()
+            .find(|u| u.id == m.from)
+            .map(|u| u.avatar.clone())
+            .unwrap_or_else(|| avatar_url(&m.from));
+
+        let (time_label, time_title) = match m.time {
+            Some(ms) => (
+                format_time(ms, self.settings.time_format),
+                full_timestamp(ms, self.settings.time_format),
+            ),
+            None => (m.timestamp.clone().unwrap_or_default(), String::new()),
+        };
+
+        let focus_sender = {
+            let from = m.from.clone();
+            ctx.link().callback(move |_| Msg::FocusUser(from.clone()))
+        };
+
+        let (body_title, gutter_time) = grouped_times(
+            grouped,
+            self.settings.timestamps == TimestampMode::Hidden,
+            &time_label,
+            &time_title,
+        );
+
+        let open_menu = {
+            let message_id = m.id.clone();
+            ctx.link().batch_callback(move |e: MouseEvent| {
+                let message_id = message_id.clone()?;
+                e.prevent_default();
+                Some(Msg::OpenContextMenu {
+                    message_id,
+                    x: e.client_x() as f64,
+                    y: e.client_y() as f64,
+                })
+            })
+        };
+
+        let from = m.from.clone();
+        let on_mouseup = ctx.link().callback(move |_: MouseEvent| {
+            Msg::SelectionChanged { from: from.clone() }
+        });
+
+        let classes = bubble_classes(m.from == self.username);
+        let row = if is_entering(m, self.state.just_added.as_deref()) {
+            format!("{} message-enter", classes.row)
+        } else {
+            classes.row.to_string()
+        };
+        let accent = accent_for_name(&m.from);
+        let highlighted = m.id.is_some() && m.id == self.highlighted_message;
+        let selected = m.id.as_ref().map_or(false, |id| self.selected_ids.contains(id));
+        let bubble = format!(
+            "flex items-end w-3/6 border-l-4 transition-shadow {} {} {} {} {}",
+            accent.border,
+            classes.bubble,
+            density.bubble,
+            if m.pending { "opacity-60" } else { "" },
+            if highlighted || selected { "ring-2 ring-blue-400" } else { "" },
+        );
+
+        // In selection mode a click anywhere on a sent message picks it.
+        let selectable = self.selecting && !m.pending;
+        let on_select = m.id.clone().filter(|_| selectable).map(|id| {
+            ctx.link().callback(move |_: MouseEvent| Msg::ToggleSelect(id.clone()))
+        });
+
+        // Pending messages have no receipts to collect yet.
+        let receipt_id = m.id.clone().filter(|_| !m.pending);
+
+        // Focusing a message with the keyboard shows its quick-react bar.
+        let (on_focus, on_blur, on_keydown) = match receipt_id.clone() {
+            Some(id) => {
+                let (focus_id, blur_id) = (id.clone(), id.clone());
+                (
+                    Some(ctx.link().callback(move |_: FocusEvent| Msg::FocusMessage(focus_id.clone()))),
+                    Some(ctx.link().callback(move |_: FocusEvent| Msg::BlurMessage(blur_id.clone()))),
+                    // Keys pressed on buttons inside the message are theirs.
+                    Some(ctx.link().batch_callback(move |event: KeyboardEvent| {
+                        (event.target() == event.current_target()).then(|| Msg::MessageKeyDown {
+                            message_id: id.clone(),
+                            event,
+                        })
+                    })),
+                )
+            }
+            None => (None, None, None),
+        };
+
+        html!{
+            <div
+                class={row}
+                id={m.id.as_deref().map(message_dom_id)}
+                data-message-id={receipt_id.clone()}
+                tabindex={receipt_id.as_ref().map(|_| "0")}
+                onfocus={on_focus}
+                onblur={on_blur}
+                onkeydown={on_keydown}
+                onclick={on_select}
+            >
+                <div class={format!("group relative {}", bubble)} oncontextmenu={open_menu}>
+                    {
+                        if selectable {
+                            html! {
+                                <input
+                                    type="checkbox"
+                                    checked={selected}
+                                    aria-label="Select message"
+                                    class="absolute -left-6 top-3 pointer-events-none"
+                                />
+                            }
+                        } else {
+                            html! {}
+                        }
+                    }
+                    {
+                        match gutter_time {
+                            // Grouped messages show their time in the avatar's
+                            // place while hovered.
+                            Some(label) => html! {
+                                <div
+                                    class={format!(
+                                        "{} flex-none flex items-center justify-center text-[10px] text-gray-400 opacity-0 group-hover:opacity-100",
+                                        density.avatar,
+                                    )}
+                                    aria-hidden="true"
+                                >
+                                    {label}
+                                </div>
+                            },
+                            None => html! {
+                                <img class={format!("{} {}", density.avatar, avatar_class(self.settings.avatar_shape))} src={avatar} alt="avatar"/>
+                            },
+                        }
+                    }
+                    <div class={format!("{} w-full", density.body)}>
+                        {
+                            if grouped {
+                                html! {}
+                            } else {
+                                html! {
+                                    <div class="flex justify-between items-center">
+                                        <button
+                                            onclick={focus_sender}
+                                            class={classes!("text-sm", "font-medium", "hover:underline", accent.text)}
+                                        >
+                                            {display_name(&m.from, &self.state.users).to_string()}
+                                        </button>
+                                        <div
+                                            class={classes!("text-xs", "text-gray-400", timestamp_class(self.settings.timestamps))}
+                                            title={time_title}
+                                        >
+                                            {time_label}
+                                        </div>
+                                    </div>
+                                }
+                            }
+                        }
+                        <div
+                            class={classes!("text-xs", "text-gray-700", "whitespace-pre-wrap", (!grouped).then_some("mt-1"))}
+                            title={body_title}
+                            onmouseup={on_mouseup}
+                        >
+                            {
+                                match &m.poll {
+                                    Some(poll) => self.view_poll(ctx, m, poll),
+                                    None => match &m.attachment {
+                                        Some(attachment) => view_attachment(attachment),
+                                        None => self.view_body(ctx, m),
+                                    },
+                                }
+                            }
+                            {
+                                if m.stream == StreamState::Streaming {
+                                    html! { <span class="animate-pulse" aria-hidden="true">{"▍"}</span> }
+                                } else {
+                                    html! {}
+                                }
+                            }
+                            {
+                                if m.edited {
+                                    html! { <span class="ml-1 text-gray-400">{"(edited)"}</span> }
+                                } else {
+                                    html! {}
+                                }
+                            }
+                        </div>
+                        { self.view_translation(ctx, m) }
+                        { self.view_link_preview(m) }
+                        { self.view_reactions(ctx, m) }
+                        { self.view_quick_react(ctx, m) }
+                        { self.view_readers(m) }
+                        { self.view_seen_summary(m) }
+                    </div>
+                </div>
+            </div>
+        }
+    }
+
+    fn view_toasts(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <div class="fixed bottom-20 right-4 z-30 flex flex-col items-end">
+                {
+                    self.toasts.iter().map(|toast| {
+                        let id = toast.id;
+                        let dismiss = ctx.link().callback(move |_| Msg::DismissToast(id));
+                        html! {
+                            <div
+                                onclick={dismiss}
+                                class={format!("mt-2 px-4 py-2 text-sm rounded shadow-lg cursor-pointer {}", toast.kind.classes())}
+                            >
+                                {toast.text.clone()}
+                            </div>
+                        }
+                    }).collect::<Html>()
+                }
+            </div>
+        }
+    }
+
+    fn view_connection_banner(&self, ctx: &Context<Self>) -> Html {
+        let retry = ctx.link().callback(|_| Msg::ForceReconnect);
+        if self.startup_failed {
+            return html! {
+                <div class="flex justify-between items-center px-4 py-3 text-sm text-white bg-red-600">
+                    <span>{"Couldn't connect to the chat server."}</span>
+                    <button onclick={retry} class="px-3 py-1 font-medium text-red-700 bg-white rounded hover:bg-red-50">
+                        {"Retry"}
+                    </button>
+                </div>
+            };
+        }
+        if self.connection == ConnectionState::GaveUp {
+            return html! {
+                <div class="flex justify-between items-center px-4 py-3 text-sm text-white bg-red-600">
+                    <span>{"Couldn't reach the server. Automatic reconnects have stopped."}</span>
+                    <button onclick={retry} class="px-3 py-1 font-medium text-red-700 bg-white rounded hover:bg-red-50">
+                        {"Reconnect"}
+                    </button>
+                </div>
+            };
+        }
+        if self.connection != ConnectionState::Disconnected {
+            return html! {};
+        }
+
+        html! {
+            <div class="flex justify-between items-center px-4 py-2 text-sm text-red-700 bg-red-100">
+                <div>
+                    <span>
+                        {
+                            match self.next_attempt_at {
+                                Some(at) => format!(
+                                    "Disconnected from the server. Reconnecting in {}s…",
+                                    seconds_until(js_sys::Date::now(), at),
+                                ),
+                                None => String::from("Disconnected from the server. Reconnecting…"),
+                            }
+                        }
+                    </span>
+                    {
+                        if self.debug {
+                            html! {
+                                <div class="text-xs">
+                                    {format!(
+                                        "Attempt {} · {}",
+                                        self.backoff.attempt(),
+                                        self.last_disconnect.as_deref().unwrap_or("no close reason"),
+                                    )}
+                                </div>
+                            }
+                        } else {
+                            html! {}
+                        }
+                    }
+                </div>
+                <button onclick={retry} class="px-2 py-1 text-xs font-medium border border-red-300 rounded hover:bg-red-200">
+                    {"Retry now"}
+                </button>
+            </div>
+        }
+    }
+
+    // The message text, collapsed behind "Show more" when it's long. Code
+    // blocks are left whole since cutting one would break its formatting.
+    fn view_body(&self, ctx: &Context<Self>, message: &MessageData) -> Html {
+        let id = match &message.id {
+            Some(id)
+                if parse_fenced(&message.message).is_none()
+                    && should_collapse(&message.message, COLLAPSE_MAX_LINES, COLLAPSE_MAX_CHARS) =>
+            {
+                id.clone()
+            }
+            _ => return render_body(&message.message, &self.state.custom_emoji, &self.search_query),
+        };
+
+        let expanded = self.expanded.contains(&id);
+        let text = if expanded {
+            message.message.clone()
+        } else {
+            collapsed_preview(&message.message, COLLAPSE_MAX_LINES, COLLAPSE_MAX_CHARS)
+        };
+        let toggle = ctx.link().callback(move |_| Msg::ToggleExpand(id.clone()));
+
+        html! {
+            <>
+                { render_body(&text, &self.state.custom_emoji, &self.search_query) }
+                <button onclick={toggle} class="block mt-1 text-xs font-medium text-blue-600 hover:underline">
+                    { if expanded { "Show less" } else { "Show more" } }
+                </button>
+            </>
+        }
+    }
+
+    // A poll's question and options with their vote counts. Clicking an
+    // option votes for it, or moves our vote there.
+    fn view_poll(&self, ctx: &Context<Self>, message: &MessageData, poll: &Poll) -> Html {
+        let poll_id = message.id.clone().unwrap_or_default();
+        let tally = self.state.poll_tallies.get(&poll_id);
+        let counts = tally
+            .map(|t| t.counts(poll.options.len()))
+            .unwrap_or_else(|| vec![0; poll.options.len()]);
+        let total: usize = counts.iter().sum();
+        let own_vote = tally.and_then(|t| t.vote_of(&self.username));
+
+        html! {
+            <div class="mt-1">
+                <div class="mb-2 font-medium">{format!("📊 {}", poll.question)}</div>
+                {
+                    poll.options.iter().zip(counts).enumerate().map(|(option, (label, count))| {
+                        let poll_id = poll_id.clone();
+                        let vote = ctx.link().callback(move |_| Msg::Vote {
+                            poll_id: poll_id.clone(),
+                            option,
+                        });
+                        let percent = if total == 0 { 0 } else { count * 100 / total };
+                        let border = if own_vote == Some(option) { "border-blue-500" } else { "border-gray-300" };
+                        html! {
+                            <button
+                                onclick={vote}
+                                class={format!("relative block w-full mb-1 px-2 py-1 text-left bg-white border rounded overflow-hidden {}", border)}
+                            >
+                                <div class="absolute inset-y-0 left-0 bg-blue-100" style={format!("width: {}%;", percent)}></div>
+                                <div class="relative flex justify-between">
+                                    <span>{label.clone()}</span>
+                                    <span class="text-gray-500">{count}</span>
+                                </div>
+                            </button>
+                        }
+                    }).collect::<Html>()
+                }
+                <div class="text-gray-400">{format!("{} votes", total)}</div>
+            </div>
+        }
+    }
+
+    // Collapsible list of pinned messages above the conversation. Clicking an
+    // entry scrolls to the message.
+    fn view_pinned(&self, ctx: &Context<Self>) -> Html {
+        let pinned = resolve_pins(&self.state.pinned_ids, |id| {
+            self.state.messages.iter().find(|m| m.id.as_deref() == Some(id))
+        });
+        if pinned.is_empty() {
+            return html! {};
+        }
+
+        let toggle = ctx.link().callback(|_| Msg::TogglePinnedPanel);
+        html! {
+            <div class="px-4 py-2 text-sm bg-yellow-50 border-b border-gray-200">
+                <button onclick={toggle} class="text-xs font-medium text-gray-600">
+                    {format!("📌 {} pinned {}", pinned.len(), if self.show_pinned { "▾" } else { "▸" })}
+                </button>
+                {
+                    if self.show_pinned {
+                        pinned.iter().map(|m| {
+                            let id = m.id.clone().unwrap_or_default();
+                            let jump = ctx.link().callback(move |_| Msg::JumpToMessage(id.clone()));
+                            html! {
+                                <div onclick={jump} class="mt-1 truncate cursor-pointer hover:underline">
+                                    <span class="font-medium">{format!("{}: ", m.from)}</span>
+                                    {m.message.clone()}
+                                </div>
+                            }
+                        }).collect::<Html>()
+                    } else {
+                        html! {}
+                    }
+                }
+            </div>
+        }
+    }
+
+    fn view_input_stats(&self) -> Html {
+        let stats = self.input_stats;
+        if stats.chars == 0 {
+            return html! {};
+        }
+
+        let remaining_class = if stats.remaining < 0 { "text-red-500" } else { "" };
+        html! {
+            <div class="flex justify-end px-6 pb-1 text-xs text-gray-400">
+                {format!("{} words · {} chars · ", stats.words, stats.chars)}
+                <span class={remaining_class}>{format!("{} left", stats.remaining)}</span>
+            </div>
+        }
+    }
+
+    // The translation below the original text, with a toggle between the two.
+    fn view_translation(&self, ctx: &Context<Self>, message: &MessageData) -> Html {
+        let id = match &message.id {
+            Some(id) => id.clone(),
+            None => return html! {},
+        };
+        let translation = match self.translations.get(&id) {
+            Some(Some(text)) => text.clone(),
+            Some(None) => {
+                return html! { <div class="mt-1 text-xs italic text-gray-400">{"Translating…"}</div> };
+            }
+            None => return html! {},
+        };
+
+        let original = self.showing_original.contains(&id);
+        let toggle = ctx.link().callback(move |_| Msg::ToggleTranslation(id.clone()));
+        html! {
+            <div class="mt-1 pt-1 text-xs border-t border-gray-200">
+                {
+                    if original {
+                        html! {}
+                    } else {
+                        html! { <div class="text-gray-700 whitespace-pre-wrap">{translation}</div> }
+                    }
+                }
+                <button onclick={toggle} class="text-gray-400 underline hover:text-gray-600">
+                    { if original { "Show translation" } else { "Show original" } }
+                </button>
+            </div>
+        }
+    }
+
+    fn view_link_preview(&self, message: &MessageData) -> Html {
+        let preview = match message.id.as_ref().and_then(|id| self.link_previews.get(id)) {
+            Some(Some(preview)) => preview,
+            _ => return html! {},
+        };
+
+        html! {
+            <div class="flex mt-2 bg-white border border-gray-200 rounded overflow-hidden">
+                {
+                    match &preview.image {
+                        Some(image) => html! {
+                            <img class="w-16 h-16 object-cover flex-none" src={image.clone()} alt=""/>
+                        },
+                        None => html! {},
+                    }
+                }
+                <div class="p-2 min-w-0">
+                    <div class="text-xs font-medium truncate">
+                        {preview.title.clone().unwrap_or_default()}
+                    </div>
+                    <div class="text-xs text-gray-500 truncate">
+                        {preview.description.clone().unwrap_or_default()}
+                    </div>
+                </div>
+            </div>
+        }
+    }
+
+    // Reaction pills under a message plus the "⋯" button, shown on hover,
+    // that opens the message's actions. Hovering a pill lists who reacted.
+    fn view_reactions(&self, ctx: &Context<Self>, message: &MessageData) -> Html {
+        let id = match &message.id {
+            Some(id) if !message.pending => id.clone(),
+            _ => return html! {},
+        };
+
+        let open_menu = {
+            let message_id = id.clone();
+            ctx.link().callback(move |e: MouseEvent| Msg::OpenContextMenu {
+                message_id: message_id.clone(),
+                x: e.client_x() as f64,
+                y: e.client_y() as f64,
+            })
+        };
+        let toggle = |emoji: &str| {
+            let message_id = id.clone();
+            let emoji = emoji.to_string();
+            ctx.link().callback(move |_| Msg::ToggleReaction {
+                message_id: message_id.clone(),
+                emoji: emoji.clone(),
+            })
+        };
+
+        html! {
+            <>
+                <div class="flex flex-wrap mt-1">
+                    {
+                        self.state.reactions.for_message(&id).iter().map(|reaction| {
+                            let own = reaction.users.iter().any(|u| u == &self.username);
+                            let class = if own {
+                                "mr-1 mb-1 px-2 text-xs bg-blue-200 border border-blue-400 rounded-full"
+                            } else {
+                                "mr-1 mb-1 px-2 text-xs bg-white border border-gray-300 rounded-full"
+                            };
+                            html! {
+                                <button
+                                    onclick={toggle(&reaction.emoji)}
+                                    title={reactors_summary(&reaction.users, MAX_REACTORS_LISTED)}
+                                    {class}
+                                >
+                                    {format!("{} {}", reaction.emoji, reaction.users.len())}
+                                </button>
+                            }
+                        }).collect::<Html>()
+                    }
+                </div>
+                <button
+                    onclick={open_menu}
+                    title="More actions"
+                    class="absolute -top-3 right-2 hidden group-hover:block px-2 text-sm bg-white shadow rounded-full"
+                >
+                    {"⋯"}
+                </button>
+            </>
+        }
+    }
+
+    // Keyboard quick-react bar for the focused message: arrows move between
+    // emoji and Enter reacts with the highlighted one.
+    fn view_quick_react(&self, ctx: &Context<Self>, message: &MessageData) -> Html {
+        let id = match &message.id {
+            Some(id) if self.focused_message.as_ref() == Some(id) => id,
+            _ => return html! {},
+        };
+
+        html! {
+            <div role="toolbar" aria-label="Quick reactions" class="flex mt-1">
+                {
+                    QUICK_REACTIONS.iter().enumerate().map(|(index, emoji)| {
+                        let message_id = id.clone();
+                        let reaction = emoji.to_string();
+                        let react = ctx.link().callback(move |_| Msg::ToggleReaction {
+                            message_id: message_id.clone(),
+                            emoji: reaction.clone(),
+                        });
+                        // Keeps focus on the message so the bar stays open.
+                        let keep_focus = Callback::from(|e: MouseEvent| e.prevent_default());
+                        let highlight = if index == self.quick_react_index {
+                            "bg-blue-200 ring-2 ring-blue-400"
+                        } else {
+                            "bg-white"
+                        };
+                        html! {
+                            <button
+                                tabindex="-1"
+                                aria-pressed={(index == self.quick_react_index).to_string()}
+                                onmousedown={keep_focus}
+                                onclick={react}
+                                class={format!("mr-1 px-1 text-sm rounded {}", highlight)}
+                            >
+                                {*emoji}
+                            </button>
+                        }
+                    }).collect::<Html>()
+                }
+            </div>
+        }
+    }
+
+    // The actions available for the message the menu was opened on. Clicking
+    // anywhere outside the menu closes it.
+    fn view_context_menu(&self, ctx: &Context<Self>) -> Html {
+        let menu = match &self.context_menu {
+            Some(menu) => menu,
+            None => return html! {},
+        };
+        let message = match self.state.messages.iter().find(|m| m.id.as_ref() == Some(&menu.message_id)) {
+            Some(message) => message,
+            None => return html! {},
+        };
+
+        let pinned = self.state.pinned_ids.contains(&menu.message_id);
+        let close = ctx.link().callback(|_| Msg::CloseContextMenu);
+        let actions = available_actions(
+            message.from == self.username,
+            message.pending,
+            translate_endpoint().is_some(),
+        );
+        // Polls and attachments aren't plain text to edit.
+        let editable = message.poll.is_none() && message.attachment.is_none();
+        let edit_window = edit_window_ms() as f64;
+        let too_old = !can_edit(message, self.now(), edit_window);
+
+        html! {
+            <>
+                <div onclick={close} class="fixed inset-0 z-20"></div>
+                <div
+                    style={format!("left: {}px; top: {}px;", menu.x, menu.y)}
+                    class="fixed z-30 w-44 py-1 text-sm bg-white rounded shadow-lg"
+                >
+                    {
+                        actions.into_iter().filter(|action| *action != MessageAction::Edit || editable).map(|action| {
+                            let message_id = menu.message_id.clone();
+                            if action == MessageAction::React {
+                                return html! {
+                                    <div class="flex justify-between px-2 pb-1 mb-1 border-b border-gray-100">
                                         {
-                                            emojis.iter().map(|emoji| {
-                                                let emoji_clone = emoji.to_string();
-                                                let onclick = ctx.link().callback(move |_| Msg::SelectEmoji(emoji_clone.clone()));
-                                                
+                                            QUICK_REACTIONS.iter().map(|emoji| {
+                                                let message_id = message_id.clone();
+                                                let reaction = emoji.to_string();
+                                                let react = ctx.link().callback(move |_| Msg::ToggleReaction {
+                                                    message_id: message_id.clone(),
+                                                    emoji: reaction.clone(),
+                                                });
                                                 html! {
-                                                    <button onclick={onclick} class="p-1 text-xl hover:bg-gray-100 rounded">
-                                                        {emoji}
-                                                    </button>
+                                                    <button onclick={react} class="text-base hover:scale-125">{*emoji}</button>
                                                 }
                                             }).collect::<Html>()
                                         }
                                     </div>
-                                }
-                            } else {
-                                html! {}
+                                };
+                            }
+                            let onclick = ctx.link().callback(move |_| Msg::MenuAction {
+                                message_id: message_id.clone(),
+                                action,
+                            });
+                            if action == MessageAction::Edit && too_old {
+                                let reason = format!(
+                                    "Messages can only be edited for {} after sending",
+                                    format_duration(edit_window),
+                                );
+                                return html! {
+                                    <button disabled=true title={reason} class="block w-full px-3 py-1 text-left text-gray-400 cursor-not-allowed">
+                                        {action.label(pinned)}
+                                    </button>
+                                };
+                            }
+                            html! {
+                                <button {onclick} class="block w-full px-3 py-1 text-left hover:bg-gray-100">
+                                    {action.label(pinned)}
+                                </button>
                             }
+                        }).collect::<Html>()
+                    }
+                </div>
+            </>
+        }
+    }
+
+    // Toolbar shown in selection mode, acting on the selected messages.
+    fn view_selection_bar(&self, ctx: &Context<Self>) -> Html {
+        if !self.selecting {
+            return html! {};
+        }
+
+        let count = self.selected_ids.len();
+        let deletable = deletable_ids(&self.state.messages, &self.selected_ids, &self.username).len();
+        let button = "ml-3 text-blue-600 hover:underline disabled:text-gray-400 disabled:no-underline";
+
+        html! {
+            <div class="w-full flex items-center px-3 py-2 text-sm bg-blue-50 border-b border-blue-100">
+                <div class="grow text-gray-700">
+                    {if count == 1 { String::from("1 message selected") } else { format!("{} messages selected", count) }}
+                </div>
+                <button class={button} disabled={count == 0} onclick={ctx.link().callback(|_| Msg::CopySelected)}>
+                    {"Copy"}
+                </button>
+                <button class={button} disabled={count == 0} onclick={ctx.link().callback(|_| Msg::QuoteSelected)}>
+                    {"Quote"}
+                </button>
+                <button class={button} disabled={count == 0} onclick={ctx.link().callback(|_| Msg::ExportSelected)}>
+                    {"Export"}
+                </button>
+                <button
+                    class={button}
+                    disabled={deletable == 0}
+                    title="Only your own messages can be deleted"
+                    onclick={ctx.link().callback(|_| Msg::DeleteSelected)}
+                >
+                    {format!("Delete ({})", deletable)}
+                </button>
+                <button class="ml-3 text-gray-500 hover:text-gray-700" onclick={ctx.link().callback(|_| Msg::ExitSelectMode)}>
+                    {"Cancel"}
+                </button>
+            </div>
+        }
+    }
+
+    fn view_report_dialog(&self, ctx: &Context<Self>) -> Html {
+        if self.report_target.is_none() {
+            return html! {};
+        }
+
+        let cancel = ctx.link().callback(|_| Msg::CancelReport);
+
+        html! {
+            <div class="fixed inset-0 z-20 flex justify-center items-center bg-black bg-opacity-30">
+                <div class="w-80 bg-white rounded-lg shadow-lg p-4" role="dialog" aria-labelledby="report-title">
+                    <div id="report-title" class="text-lg font-medium">{"Report message"}</div>
+                    <div class="text-sm text-gray-500 mt-2">{"What's wrong with it?"}</div>
+                    <div class="mt-3">
+                        {
+                            ReportReason::ALL.iter().map(|&reason| {
+                                let onclick = ctx.link().callback(move |_| Msg::SubmitReport(reason));
+                                html! {
+                                    <button {onclick} class="block w-full px-3 py-2 mb-1 text-sm text-left rounded hover:bg-gray-100">
+                                        {reason.label()}
+                                    </button>
+                                }
+                            }).collect::<Html>()
                         }
                     </div>
+                    <div class="flex justify-end mt-2">
+                        <button onclick={cancel} class="px-3 py-1 text-sm text-gray-600 rounded hover:bg-gray-100">
+                            {"Cancel"}
+                        </button>
+                    </div>
                 </div>
             </div>
         }
     }
-}
 
-impl Chat {
-    fn send_typing_status(&mut self, ctx: &Context<Self>, is_typing: bool) {
-        // Get current user
-        let (user, _) = ctx
-            .link()
-            .context::<User>(Callback::noop())
-            .expect("context to be set");
-        
-        let username = user.username.borrow().clone();
-        
+    // Small avatars of everyone whose latest read message is this one, apart
+    // from its author and ourselves.
+    fn view_readers(&self, message: &MessageData) -> Html {
+        let id = match &message.id {
+            Some(id) if !message.pending => id,
+            _ => return html! {},
+        };
+        let readers: Vec<&str> = self
+            .state
+            .receipts
+            .readers_of(id)
+            .into_iter()
+            .filter(|reader| *reader != message.from && *reader != self.username)
+            .collect();
+        if readers.is_empty() {
+            return html! {};
+        }
+
+        let overflow = avatar_overflow(readers.len(), MAX_READER_AVATARS);
+        html! {
+            <div class="flex justify-end items-center mt-1" title={format!("Seen by {}", readers.join(", "))}>
+                {
+                    readers.iter().take(overflow.shown).map(|reader| html! {
+                        <img class={classes!("w-4", "h-4", "-ml-1", "border", "border-white", avatar_class(self.settings.avatar_shape))} src={avatar_url(reader)} alt={reader.to_string()}/>
+                    }).collect::<Html>()
+                }
+                {
+                    if overflow.extra > 0 {
+                        html! { <div class="ml-1 text-xs text-gray-400">{format!("+{}", overflow.extra)}</div> }
+                    } else {
+                        html! {}
+                    }
+                }
+            </div>
+        }
+    }
+
+    // "Seen by N" / "Seen by everyone" under our newest sent message, counted
+    // against the other users who are currently online rather than away.
+    fn view_seen_summary(&self, message: &MessageData) -> Html {
+        let latest_own = self
+            .state
+            .messages
+            .iter()
+            .rev()
+            .find(|m| m.from == self.username && !m.pending)
+            .and_then(|m| m.id.as_deref());
+        let id = match message.id.as_deref() {
+            Some(id) if message.from == self.username && latest_own == Some(id) => id,
+            _ => return html! {},
+        };
+
+        let position = |id: &str| self.state.messages.iter().position(|m| m.id.as_deref() == Some(id));
+        let online: Vec<&str> = self
+            .state
+            .users
+            .iter()
+            .map(|u| u.id.as_str())
+            .filter(|name| *name != self.username)
+            .filter(|name| self.presence_of(name) == PresenceStatus::Online)
+            .collect();
+        let readers = online
+            .iter()
+            .filter(|name| self.state.receipts.has_read(name, id, position))
+            .count();
+
+        match seen_summary(readers, online.len()) {
+            Some(summary) => html! {
+                <div class="mt-1 text-right text-xs text-gray-400">{summary}</div>
+            },
+            None => html! {},
+        }
+    }
+
+    fn view_quote_popover(&self, ctx: &Context<Self>) -> Html {
+        let popover = match &self.quote_popover {
+            Some(popover) => popover,
+            None => return html! {},
+        };
+
+        let from = popover.from.clone();
+        let text = popover.text.clone();
+        let quote = ctx.link().callback(move |_| Msg::QuoteSelection {
+            from: from.clone(),
+            text: text.clone(),
+        });
+
+        html! {
+            <button
+                onclick={quote}
+                style={format!("left: {}px; top: {}px;", popover.x, popover.y - 32.0)}
+                class="fixed z-10 px-2 py-1 text-xs text-white bg-gray-800 rounded shadow"
+            >
+                {"❝ Quote"}
+            </button>
+        }
+    }
+
+    fn view_confirm_clear(&self, ctx: &Context<Self>) -> Html {
+        if !self.confirm_clear {
+            return html! {};
+        }
+
+        let confirm = ctx.link().callback(|_| Msg::ConfirmClear);
+        let cancel = ctx.link().callback(|_| Msg::CancelClear);
+
+        html! {
+            <div class="fixed inset-0 z-20 flex justify-center items-center bg-black bg-opacity-30">
+                <div class="w-80 bg-white rounded-lg shadow-lg p-4">
+                    <div class="text-lg font-medium">{"Clear conversation?"}</div>
+                    <div class="text-sm text-gray-500 mt-2">
+                        {"This removes all messages from your screen. Other users are not affected."}
+                    </div>
+                    <div class="flex justify-end mt-4">
+                        <button onclick={cancel} class="px-3 py-1 mr-2 text-sm text-gray-600 rounded hover:bg-gray-100">
+                            {"Cancel"}
+                        </button>
+                        <button onclick={confirm} class="px-3 py-1 text-sm text-white bg-red-500 rounded hover:bg-red-600">
+                            {"Clear"}
+                        </button>
+                    </div>
+                </div>
+            </div>
+        }
+    }
+
+    fn view_inactivity(&self, ctx: &Context<Self>) -> Html {
+        let (title, detail, action) = if self.connection == ConnectionState::Suspended {
+            (
+                "Disconnected",
+                "You were away for a while, so the chat disconnected to save resources.",
+                "Reconnect",
+            )
+        } else if self.inactivity.state() == Inactivity::Prompted {
+            (
+                "Still there?",
+                "The chat will disconnect soon to save resources.",
+                "I'm here",
+            )
+        } else {
+            return html! {};
+        };
+
+        let stay = ctx.link().callback(|_| Msg::StayConnected);
+
+        html! {
+            <div class="fixed inset-0 z-20 flex justify-center items-center bg-black bg-opacity-30">
+                <div class="w-80 bg-white rounded-lg shadow-lg p-4" role="alertdialog" aria-labelledby="inactivity-title">
+                    <div id="inactivity-title" class="text-lg font-medium">{title}</div>
+                    <div class="text-sm text-gray-500 mt-2">{detail}</div>
+                    <div class="flex justify-end mt-4">
+                        <button onclick={stay} class="px-3 py-1 text-sm text-white bg-blue-600 rounded hover:bg-blue-700">
+                            {action}
+                        </button>
+                    </div>
+                </div>
+            </div>
+        }
+    }
+
+    fn diagnostics(&self) -> Diagnostics {
+        Diagnostics {
+            connection: format!("{:?}", self.connection),
+            reconnect_attempts: self.backoff.attempt(),
+            queued_messages: self.outbox.len(),
+            message_count: self.state.messages.len(),
+            last_error: self.last_disconnect.clone(),
+            socket_url: self.wss.url().to_string(),
+        }
+    }
+
+    fn view_diagnostics(&self, ctx: &Context<Self>) -> Html {
+        if !self.show_diagnostics {
+            return html! {};
+        }
+
+        let close = ctx.link().callback(|_| Msg::ToggleDiagnostics);
+        let copy = ctx.link().callback(|_| Msg::CopyDiagnostics);
+
+        html! {
+            <div class="fixed inset-0 z-20 flex justify-center items-center bg-black bg-opacity-30">
+                <div class="w-96 bg-white rounded-lg shadow-lg p-4">
+                    <div class="flex justify-between items-center mb-2">
+                        <div class="text-lg font-medium">{"Diagnostics"}</div>
+                        <button onclick={close} class="text-gray-400 hover:text-gray-700">{"✕"}</button>
+                    </div>
+                    <dl class="text-sm">
+                        {
+                            self.diagnostics().rows().into_iter().map(|(label, value)| html! {
+                                <div class="flex justify-between py-1 border-b border-gray-100">
+                                    <dt class="text-gray-500">{label}</dt>
+                                    <dd class="ml-4 font-mono text-right break-all">{value}</dd>
+                                </div>
+                            }).collect::<Html>()
+                        }
+                    </dl>
+                    <button
+                        onclick={copy}
+                        class="mt-3 w-full py-1 text-sm text-white bg-blue-600 rounded hover:bg-blue-700"
+                    >
+                        {"Copy diagnostics"}
+                    </button>
+                </div>
+            </div>
+        }
+    }
+
+    fn view_settings(&self, ctx: &Context<Self>) -> Html {
+        if !self.show_settings {
+            return html! {};
+        }
+
+        let close = ctx.link().callback(|_| Msg::ToggleSettings);
+
+        html! {
+            <div class="fixed inset-0 z-20 flex justify-center items-center bg-black bg-opacity-30">
+                <div class="w-80 bg-white rounded-lg shadow-lg p-4">
+                    <div class="flex justify-between items-center mb-2">
+                        <div class="text-lg font-medium">{"Settings"}</div>
+                        <button onclick={close} class="text-gray-400 hover:text-gray-700">{"✕"}</button>
+                    </div>
+                    {
+                        settings_row(
+                            "Time format",
+                            self.settings.time_format.label(),
+                            ctx.link().callback(|_| Msg::ToggleTimeFormat),
+                        )
+                    }
+                    {
+                        settings_row(
+                            "Show message times",
+                            self.settings.timestamps.label(),
+                            ctx.link().callback(|_| Msg::CycleTimestampMode),
+                        )
+                    }
+                    {
+                        settings_row(
+                            "Message density",
+                            self.settings.density.label(),
+                            ctx.link().callback(|_| Msg::ToggleDensity),
+                        )
+                    }
+                    {
+                        settings_row(
+                            "Notify me on",
+                            self.settings.notify.label(),
+                            ctx.link().callback(|_| Msg::CycleNotifyMode),
+                        )
+                    }
+                    {
+                        settings_row(
+                            "Notification sound",
+                            self.settings.notification_sound.label(),
+                            ctx.link().callback(|_| Msg::CycleNotificationSound),
+                        )
+                    }
+                    {
+                        settings_row(
+                            "Group messages within",
+                            self.settings.group_window.label(),
+                            ctx.link().callback(|_| Msg::CycleGroupWindow),
+                        )
+                    }
+                    {
+                        settings_row(
+                            "Emoji skin tone",
+                            &apply_skin_tone("👍", self.settings.skin_tone),
+                            ctx.link().callback(|_| Msg::CycleSkinTone),
+                        )
+                    }
+                    {
+                        settings_row(
+                            "Send with",
+                            self.settings.send_key.label(),
+                            ctx.link().callback(|_| Msg::ToggleSendKey),
+                        )
+                    }
+                    {
+                        settings_row(
+                            "Sort people",
+                            self.settings.user_sort.label(),
+                            ctx.link().callback(|_| Msg::ToggleUserSort),
+                        )
+                    }
+                    {
+                        settings_row(
+                            "Avatar shape",
+                            self.settings.avatar_shape.label(),
+                            ctx.link().callback(|_| Msg::ToggleAvatarShape),
+                        )
+                    }
+                    {
+                        settings_row(
+                            "Online time after reconnecting",
+                            if self.settings.reset_session_on_reconnect { "Starts over" } else { "Keeps counting" },
+                            ctx.link().callback(|_| Msg::ToggleSessionReset),
+                        )
+                    }
+                </div>
+            </div>
+        }
+    }
+
+    fn stop_typing(&mut self) {
+        self.typing_timeout = None;
+        if let Some(status) = self.typing.on_submit() {
+            self.send_typing_status(status);
+        }
+    }
+
+    // Restarts the quiet period while typing; nothing is pending otherwise.
+    fn schedule_typing_idle(&mut self, ctx: &Context<Self>) {
+        self.typing_timeout = self.typing.is_typing().then(|| {
+            let link = ctx.link().clone();
+            Timeout::new(TYPING_IDLE_MS, move || link.send_message(Msg::TypingIdle))
+        });
+    }
+
+    fn send_typing_status(&self, is_typing: bool) {
         // Create typing status
         let typing_status = TypingStatus {
-            username,
+            username: self.username.clone(),
             is_typing,
         };
         
+        let data = match serde_json::to_string(&typing_status) {
+            Ok(data) => data,
+            Err(e) => {
+                log::error!("failed to serialize typing status: {}", e);
+                return;
+            }
+        };
+
         // Send typing status through WebSocket
         let message = WebSocketMessage {
             message_type: MsgTypes::Typing,
-            data: Some(serde_json::to_string(&typing_status).unwrap()),
+            data: Some(data),
             data_array: None,
+            id: None,
+            attachment: None,
+            channel: None,
         };
-        
-        if let Err(e) = self
-            .wss
-            .tx
-            .clone()
-            .try_send(serde_json::to_string(&message).unwrap())
-        {
-            log::debug!("error sending typing status: {:?}", e);
-        }
+        self.send_ws(&message);
+    }
+
+    fn send_ws(&self, message: &WebSocketMessage) -> bool {
+        queue_frame(&self.wss.tx, message)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,1624 @@
+// The chat's data model and the handling of incoming frames, kept free of
+// the DOM and the component's `Context` so it runs (and is tested) natively.
+
+use futures::channel::mpsc::Sender;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::config::{MAX_INCOMING_MESSAGE_CHARS, MAX_USERS, STARTUP_TIMEOUT_MS};
+use crate::utils::attachment::Attachment;
+use crate::utils::avatar::avatar_url;
+use crate::utils::emoji::CustomEmoji;
+use crate::utils::pins::set_pinned;
+use crate::utils::poll::{Poll, PollTally};
+use crate::utils::presence::PresenceStatus;
+use crate::utils::reactions::Reactions;
+use crate::utils::receipts::ReadReceipts;
+use crate::utils::report::ReportReason;
+use crate::utils::text::{format_quote, truncate_chars};
+use crate::utils::time::parse_timestamp;
+use crate::utils::unread::GENERAL;
+
+// Oldest messages are dropped past this point to keep rendering cheap.
+const MAX_MESSAGES: usize = 500;
+
+// How a message's text arrived. Streamed messages are assembled from
+// `messagechunk` frames and shown as they grow.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum StreamState {
+    #[default]
+    Whole,
+    Streaming,
+    Streamed,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct MessageData {
+    #[serde(default)]
+    pub id: Option<String>,
+    // Shown locally before the server has echoed it back.
+    #[serde(skip)]
+    pub pending: bool,
+    // Stands in for a message whose payload couldn't be read.
+    #[serde(skip)]
+    pub unsupported: bool,
+    // Its sender changed the text after sending it.
+    #[serde(skip)]
+    pub edited: bool,
+    // The channel it was sent to; `None` is the general room.
+    #[serde(default)]
+    pub channel: Option<String>,
+    // Set for polls, whose `message` is then the question.
+    #[serde(skip)]
+    pub poll: Option<Poll>,
+    #[serde(skip)]
+    pub stream: StreamState,
+    #[serde(default)]
+    pub attachment: Option<Attachment>,
+    pub from: String,
+    pub message: String,
+    pub timestamp: Option<String>, // Raw timestamp as sent, in whatever format
+    #[serde(default)]
+    pub time: Option<f64>, // Epoch millis, set by the server or parsed from `timestamp`
+}
+
+impl MessageData {
+    pub fn conversation(&self) -> &str {
+        self.channel.as_deref().unwrap_or(GENERAL)
+    }
+
+    // Keeps a rendered row, and state inside it like revealed spoilers, with
+    // its message when history is prepended. Placeholders without an id fall
+    // back to their position.
+    pub fn row_key(&self, index: usize) -> String {
+        match &self.id {
+            Some(id) => format!("id:{}", id),
+            None => format!("at:{}", index),
+        }
+    }
+
+    pub fn unsupported() -> Self {
+        Self {
+            id: None,
+            pending: false,
+            unsupported: true,
+            edited: false,
+            channel: None,
+            stream: StreamState::Whole,
+            attachment: None,
+            poll: None,
+            from: String::new(),
+            message: String::from("⚠️ unsupported message"),
+            timestamp: None,
+            time: None,
+        }
+    }
+}
+
+// Reads a message frame's payload. Payloads that don't parse are logged and
+// dropped, or shown as a placeholder when `placeholder` is set. Without a
+// server time, `timestamp` is parsed instead, falling back to the time it
+// was received.
+pub fn parse_message_data(data: Option<&str>, placeholder: bool, now: f64) -> Option<MessageData> {
+    match data.map(serde_json::from_str::<MessageData>) {
+        Some(Ok(mut message)) => {
+            let parsed = message.timestamp.as_deref().and_then(|raw| parse_timestamp(raw, now));
+            message.time = message.time.or(parsed).or(Some(now));
+            return Some(message);
+        }
+        Some(Err(e)) => log::warn!("unreadable message payload: {}", e),
+        None => log::warn!("message frame without a payload"),
+    }
+    placeholder.then(|| MessageData {
+        time: Some(now),
+        ..MessageData::unsupported()
+    })
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MsgTypes {
+    Users,
+    Register,
+    Message,
+    Typing, // Added typing message type
+    Presence,
+    Read,
+    Reaction,
+    Pin,
+    Poll,
+    Vote,
+    CustomEmoji,
+    RegisterError,
+    MessageChunk,
+    History,
+    Report,
+    TimeSync,
+    Edit,
+    Delete,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebSocketMessage {
+    pub message_type: MsgTypes,
+    pub data_array: Option<Vec<String>>,
+    pub data: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachment: Option<Attachment>,
+    // Set on messages sent to a channel other than the general room.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TypingStatus {
+    pub username: String,
+    pub is_typing: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PresenceUpdate {
+    pub username: String,
+    pub status: PresenceStatus,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ReadReceipt {
+    pub username: String,
+    pub message_id: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ReactionUpdate {
+    pub username: String,
+    pub message_id: String,
+    pub emoji: String,
+    pub added: bool,
+}
+
+// Our clock when the sync went out, echoed back with the server's.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeSync {
+    pub client_time: f64,
+    #[serde(default)]
+    pub server_time: Option<f64>,
+}
+
+// Sent to the server only; the message stays on screen.
+#[derive(Serialize)]
+pub struct ReportFrame {
+    pub username: String,
+    pub message_id: String,
+    pub reason: ReportReason,
+}
+
+// New text for one of the sender's own messages.
+#[derive(Serialize, Deserialize)]
+pub struct EditUpdate {
+    pub username: String,
+    pub message_id: String,
+    pub text: String,
+}
+
+// Messages the sender took back.
+#[derive(Serialize, Deserialize)]
+pub struct DeleteUpdate {
+    pub username: String,
+    pub message_ids: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PinUpdate {
+    pub username: String,
+    pub message_id: String,
+    pub pinned: bool,
+}
+
+// A poll as relayed by the server, which adds the id, author and time.
+#[derive(Deserialize)]
+struct PollFrame {
+    id: String,
+    from: String,
+    question: String,
+    options: Vec<String>,
+    time: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct MessageChunk {
+    id: String,
+    from: String,
+    delta: String,
+    done: bool,
+    time: Option<f64>,
+}
+
+// Asks for up to `limit` messages older than `before_id`, or the newest ones
+// when there is no id yet.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryRequest {
+    pub before_id: Option<String>,
+    pub limit: u32,
+}
+
+// Sent with a page of history; the messages themselves are in `data_array`.
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HistoryPage {
+    #[serde(default)]
+    has_more: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct VoteUpdate {
+    pub username: String,
+    pub poll_id: String,
+    pub option: usize,
+}
+
+// What the composer is doing beyond sending a plain message.
+#[derive(Debug, PartialEq)]
+pub enum ComposerMode {
+    Normal,
+    Code,
+    // `prior_input` is what had been typed before the quote was inserted.
+    Quote { from: String, prior_input: String },
+    // Replacing the text of one of our messages, which the input was filled with.
+    Edit { message_id: String, prior_input: String },
+}
+
+impl ComposerMode {
+    // Escape drops back to plain messages. Returns the input to restore when
+    // the mode had replaced what the user typed.
+    pub fn cancel(&mut self) -> Option<String> {
+        match std::mem::replace(self, ComposerMode::Normal) {
+            ComposerMode::Quote { prior_input, .. } | ComposerMode::Edit { prior_input, .. } => Some(prior_input),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct UserProfile {
+    // Stable identity, matched against a message's `from`. The server only
+    // knows one name per user today, so this is that name for now.
+    pub id: String,
+    // What to show, which may change while `id` stays put.
+    pub name: String,
+    pub avatar: String,
+}
+
+// Everything incoming frames update, kept apart from the component so the
+// protocol handling runs without a `Context` or a DOM and can be unit tested.
+#[derive(Default)]
+pub struct ChatState {
+    pub users: Vec<UserProfile>,
+    // Room size the server advertises with the user list, if any.
+    pub capacity: Option<u32>,
+    pub messages: Vec<MessageData>,
+    pub seen_ids: HashSet<String>,
+    pub typing_users: Vec<String>,
+    // Last non-empty typing list, kept on screen while the indicator fades out.
+    pub typing_display: Vec<String>,
+    // Id of the message appended by the last update, animated on its first render.
+    pub just_added: Option<String>,
+    pub custom_emoji: CustomEmoji,
+    pub presence: HashMap<String, PresenceStatus>,
+    pub receipts: ReadReceipts,
+    pub reactions: Reactions,
+    pub pinned_ids: Vec<String>,
+    // Keyed by the poll's message id.
+    pub poll_tallies: HashMap<String, PollTally>,
+    // Whether the server said it has messages older than the ones loaded.
+    pub has_more_history: bool,
+}
+
+// What applying a frame changed, for `update` to follow up on with anything
+// that needs the browser or the component's `Context`.
+#[derive(Debug, PartialEq)]
+pub enum Incoming {
+    Unchanged,
+    // Needs a re-render and nothing else.
+    Changed,
+    // A user list arrived; `changed` if it differs from the last one.
+    Users { changed: bool },
+    // A new message was added to the list, filed under `conversation`.
+    Message { id: Option<String>, from: String, text: String, conversation: String },
+    // The typing list changed.
+    Typing,
+    RegisterError(String),
+    // A page of older messages arrived, `added` of which were new.
+    History { added: usize },
+    // The server's clock read, for a sync we sent at `sent`.
+    TimeSync { sent: f64, server: f64 },
+}
+
+impl ChatState {
+    // `now` stands in for messages without a usable time, and `placeholders`
+    // shows unreadable messages instead of dropping them.
+    pub fn apply_incoming(&mut self, msg: WebSocketMessage, now: f64, placeholders: bool) -> Incoming {
+        match msg.message_type {
+            MsgTypes::Users => {
+                let mut names = msg.data_array.unwrap_or_default();
+                if names.len() > MAX_USERS {
+                    log::warn!("user list of {} capped at {}", names.len(), MAX_USERS);
+                    names.truncate(MAX_USERS);
+                }
+                let users: Vec<UserProfile> = names
+                    .iter()
+                    .map(|u| UserProfile {
+                        id: u.into(),
+                        name: u.into(),
+                        avatar: avatar_url(u),
+                    })
+                    .collect();
+                let capacity = msg.data.and_then(|data| data.parse().ok());
+                let changed = users != self.users || capacity != self.capacity;
+                self.users = users;
+                self.capacity = capacity;
+                Incoming::Users { changed }
+            }
+            MsgTypes::Message => {
+                let mut message = match parse_message_data(msg.data.as_deref(), placeholders, now) {
+                    Some(message) => message,
+                    None => return Incoming::Unchanged,
+                };
+                let length = message.message.chars().count();
+                if truncate_chars(&mut message.message, MAX_INCOMING_MESSAGE_CHARS) {
+                    log::warn!("message of {} chars cut to {}", length, MAX_INCOMING_MESSAGE_CHARS);
+                }
+                // The complete text of a streamed message replaces whatever
+                // the chunks added up to.
+                let id = message.id.clone();
+                let streamed = self.messages.iter_mut().find(|m| {
+                    m.stream != StreamState::Whole && m.id.is_some() && m.id == id && m.from == message.from
+                });
+                if let Some(streamed) = streamed {
+                    let finished = streamed.stream == StreamState::Streaming;
+                    let changed = finished || streamed.message != message.message;
+                    streamed.message = message.message.clone();
+                    streamed.stream = StreamState::Streamed;
+                    if finished {
+                        return Incoming::Message {
+                            id,
+                            from: message.from,
+                            text: message.message,
+                            conversation: streamed.conversation().to_string(),
+                        };
+                    }
+                    return changed.into();
+                }
+                if message.unsupported {
+                    return self.push_message(message).into();
+                }
+                let (id, from, text) = (message.id.clone(), message.from.clone(), message.message.clone());
+                let conversation = message.conversation().to_string();
+                let replaces_pending = self.take_pending(&message);
+                if !self.push_message(message) {
+                    return Incoming::Unchanged;
+                }
+                if replaces_pending {
+                    // Already animated in when it was sent.
+                    self.just_added = None;
+                }
+                Incoming::Message { id, from, text, conversation }
+            }
+            MsgTypes::Typing => {
+                let status = msg
+                    .data
+                    .and_then(|data| serde_json::from_str::<TypingStatus>(&data).ok());
+                let status = match status {
+                    Some(status) => status,
+                    None => return Incoming::Unchanged,
+                };
+                let changed = if status.is_typing {
+                    // Add user to typing list if not already there
+                    let is_new = !self.typing_users.contains(&status.username);
+                    if is_new {
+                        self.typing_users.push(status.username);
+                    }
+                    is_new
+                } else {
+                    // Remove user from typing list
+                    let before = self.typing_users.len();
+                    self.typing_users.retain(|u| u != &status.username);
+                    self.typing_users.len() != before
+                };
+                if !changed {
+                    return Incoming::Unchanged;
+                }
+                if !self.typing_users.is_empty() {
+                    self.typing_display = self.typing_users.clone();
+                }
+                Incoming::Typing
+            }
+            MsgTypes::Presence => {
+                let update = msg
+                    .data
+                    .and_then(|data| serde_json::from_str::<PresenceUpdate>(&data).ok());
+                match update {
+                    Some(update) => {
+                        let previous = self.presence.insert(update.username, update.status);
+                        (previous != Some(update.status)).into()
+                    }
+                    None => Incoming::Unchanged,
+                }
+            }
+            MsgTypes::Read => {
+                let receipt = msg
+                    .data
+                    .and_then(|data| serde_json::from_str::<ReadReceipt>(&data).ok());
+                match receipt {
+                    Some(receipt) => {
+                        let messages = &self.messages;
+                        self.receipts
+                            .record(&receipt.username, &receipt.message_id, |id| {
+                                messages.iter().position(|m| m.id.as_deref() == Some(id))
+                            })
+                            .into()
+                    }
+                    None => Incoming::Unchanged,
+                }
+            }
+            MsgTypes::Reaction => {
+                let update = msg
+                    .data
+                    .and_then(|data| serde_json::from_str::<ReactionUpdate>(&data).ok());
+                match update {
+                    Some(update) => self
+                        .reactions
+                        .apply(&update.message_id, &update.emoji, &update.username, update.added)
+                        .into(),
+                    None => Incoming::Unchanged,
+                }
+            }
+            MsgTypes::Poll => {
+                let frame = msg
+                    .data
+                    .and_then(|data| serde_json::from_str::<PollFrame>(&data).ok());
+                match frame {
+                    Some(frame) => {
+                        let poll = MessageData {
+                            id: Some(frame.id),
+                            pending: false,
+                            unsupported: false,
+                            edited: false,
+                            channel: None,
+                            stream: StreamState::Whole,
+                            attachment: None,
+                            poll: Some(Poll {
+                                question: frame.question.clone(),
+                                options: frame.options,
+                            }),
+                            from: frame.from,
+                            message: frame.question,
+                            timestamp: None,
+                            time: frame.time.or(Some(now)),
+                        };
+                        self.push_message(poll).into()
+                    }
+                    None => Incoming::Unchanged,
+                }
+            }
+            MsgTypes::Vote => {
+                let vote = msg
+                    .data
+                    .and_then(|data| serde_json::from_str::<VoteUpdate>(&data).ok());
+                match vote {
+                    Some(vote) => self
+                        .poll_tallies
+                        .entry(vote.poll_id)
+                        .or_default()
+                        .vote(&vote.username, vote.option)
+                        .into(),
+                    None => Incoming::Unchanged,
+                }
+            }
+            MsgTypes::RegisterError => Incoming::RegisterError(
+                msg.data
+                    .unwrap_or_else(|| String::from("That name can't be used.")),
+            ),
+            MsgTypes::CustomEmoji => {
+                let registry = msg
+                    .data
+                    .and_then(|data| serde_json::from_str::<HashMap<String, String>>(&data).ok());
+                match registry {
+                    Some(registry) => {
+                        let changed = *self.custom_emoji != registry;
+                        self.custom_emoji = CustomEmoji::new(registry);
+                        changed.into()
+                    }
+                    None => Incoming::Unchanged,
+                }
+            }
+            MsgTypes::Pin => {
+                let update = msg
+                    .data
+                    .and_then(|data| serde_json::from_str::<PinUpdate>(&data).ok());
+                match update {
+                    Some(update) => {
+                        set_pinned(&mut self.pinned_ids, &update.message_id, update.pinned).into()
+                    }
+                    None => Incoming::Unchanged,
+                }
+            }
+            MsgTypes::Edit => {
+                let update = match msg
+                    .data
+                    .and_then(|data| serde_json::from_str::<EditUpdate>(&data).ok())
+                {
+                    Some(update) => update,
+                    None => return Incoming::Unchanged,
+                };
+                // Only the sender may change a message.
+                let message = self
+                    .messages
+                    .iter_mut()
+                    .find(|m| m.id.as_ref() == Some(&update.message_id) && m.from == update.username);
+                match message {
+                    Some(message) => {
+                        message.message = update.text;
+                        truncate_chars(&mut message.message, MAX_INCOMING_MESSAGE_CHARS);
+                        message.edited = true;
+                        Incoming::Changed
+                    }
+                    None => Incoming::Unchanged,
+                }
+            }
+            MsgTypes::Delete => {
+                let update = match msg
+                    .data
+                    .and_then(|data| serde_json::from_str::<DeleteUpdate>(&data).ok())
+                {
+                    Some(update) => update,
+                    None => return Incoming::Unchanged,
+                };
+                // As with edits, only the sender's own messages go.
+                let before = self.messages.len();
+                self.messages.retain(|m| {
+                    m.from != update.username || !m.id.as_ref().is_some_and(|id| update.message_ids.contains(id))
+                });
+                (self.messages.len() != before).into()
+            }
+            MsgTypes::MessageChunk => {
+                let chunk = msg
+                    .data
+                    .and_then(|data| serde_json::from_str::<MessageChunk>(&data).ok());
+                match chunk {
+                    Some(chunk) => self.apply_chunk(chunk, now),
+                    None => Incoming::Unchanged,
+                }
+            }
+            MsgTypes::History => {
+                let page = msg
+                    .data
+                    .and_then(|data| serde_json::from_str::<HistoryPage>(&data).ok())
+                    .unwrap_or_default();
+                let mut added = 0;
+                for data in msg.data_array.unwrap_or_default() {
+                    // Older messages would only be trimmed again straight away.
+                    if self.messages.len() >= MAX_MESSAGES {
+                        break;
+                    }
+                    let mut message = match parse_message_data(Some(&data), false, now) {
+                        Some(message) => message,
+                        None => continue,
+                    };
+                    truncate_chars(&mut message.message, MAX_INCOMING_MESSAGE_CHARS);
+                    if self.push_message(message) {
+                        added += 1;
+                    }
+                }
+                self.just_added = None;
+                self.has_more_history = page.has_more && self.messages.len() < MAX_MESSAGES;
+                Incoming::History { added }
+            }
+            MsgTypes::TimeSync => {
+                let sync = msg
+                    .data
+                    .and_then(|data| serde_json::from_str::<TimeSync>(&data).ok());
+                match sync {
+                    Some(TimeSync {
+                        client_time,
+                        server_time: Some(server_time),
+                    }) => Incoming::TimeSync {
+                        sent: client_time,
+                        server: server_time,
+                    },
+                    _ => Incoming::Unchanged,
+                }
+            }
+            MsgTypes::Register | MsgTypes::Report => Incoming::Unchanged,
+        }
+    }
+
+    // Empties the local view: messages along with everything recorded about
+    // them. Other users are not affected.
+    pub fn clear(&mut self) {
+        self.messages.clear();
+        self.seen_ids.clear();
+        self.just_added = None;
+        self.receipts.clear();
+        self.reactions.clear();
+        self.poll_tallies.clear();
+    }
+
+    // Drops our optimistic copy of a message once the server echoes it, so
+    // the echo takes its place instead of showing up twice. Only an echo from
+    // the same sender counts; anyone else reusing the id can't replace it.
+    pub fn take_pending(&mut self, echo: &MessageData) -> bool {
+        let id = match &echo.id {
+            Some(id) => id,
+            None => return false,
+        };
+        match self
+            .messages
+            .iter()
+            .position(|m| m.pending && m.id.as_ref() == Some(id) && m.from == echo.from)
+        {
+            Some(index) => {
+                self.messages.remove(index);
+                self.seen_ids.remove(id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Our not yet echoed copy of message `id`.
+    pub fn pending(&self, id: &str) -> Option<&MessageData> {
+        self.messages.iter().find(|m| m.pending && m.id.as_deref() == Some(id))
+    }
+
+    // Re-stamps the pending copy of a message just sent from the outbox with
+    // its real send time and moves it into place, so it sorts after whatever
+    // the server replayed while we were away and its echo still replaces it.
+    pub fn resend_pending(&mut self, id: &str, now: f64) {
+        let index = match self.messages.iter().position(|m| m.pending && m.id.as_deref() == Some(id)) {
+            Some(index) => index,
+            None => return,
+        };
+        let mut message = self.messages.remove(index);
+        self.seen_ids.remove(id);
+        message.time = Some(now);
+        self.push_message(message);
+    }
+
+    // Inserts a message in timestamp order unless its id was already seen,
+    // e.g. when the server replays recent history after a reconnect. Messages
+    // with equal timestamps keep their arrival order. Returns whether it was
+    // added.
+    pub fn push_message(&mut self, mut message: MessageData) -> bool {
+        if let Some(id) = &message.id {
+            if !self.seen_ids.insert(id.clone()) {
+                return false;
+            }
+        }
+
+        let time = message.time.unwrap_or_else(js_sys::Date::now);
+        message.time = Some(time);
+        let index = self
+            .messages
+            .partition_point(|m| m.time.unwrap_or_default() <= time);
+        // Only messages landing at the end animate in; history filling in
+        // above would just be distracting.
+        self.just_added = message.id.clone().filter(|_| index == self.messages.len());
+        self.messages.insert(index, message);
+
+        while self.messages.len() > MAX_MESSAGES {
+            let dropped = self.messages.remove(0);
+            if let Some(id) = dropped.id {
+                self.seen_ids.remove(&id);
+            }
+        }
+        true
+    }
+}
+
+impl ChatState {
+    // Appends a chunk to its message, starting the message if this is the
+    // first. Reports a new message once the last chunk is in, so it notifies
+    // like any other; earlier chunks just re-render.
+    fn apply_chunk(&mut self, chunk: MessageChunk, now: f64) -> Incoming {
+        let position = |messages: &[MessageData]| {
+            messages.iter().position(|m| m.id.as_deref() == Some(chunk.id.as_str()))
+        };
+        let index = match position(&self.messages) {
+            Some(index) => {
+                let message = &mut self.messages[index];
+                // Already complete, or the id belongs to a different message.
+                if message.stream != StreamState::Streaming || message.from != chunk.from {
+                    return Incoming::Unchanged;
+                }
+                message.message.push_str(&chunk.delta);
+                truncate_chars(&mut message.message, MAX_INCOMING_MESSAGE_CHARS);
+                index
+            }
+            None => {
+                let mut text = chunk.delta.clone();
+                truncate_chars(&mut text, MAX_INCOMING_MESSAGE_CHARS);
+                let started = self.push_message(MessageData {
+                    id: Some(chunk.id.clone()),
+                    pending: false,
+                    unsupported: false,
+                    edited: false,
+                    channel: None,
+                    stream: StreamState::Streaming,
+                    attachment: None,
+                    poll: None,
+                    from: chunk.from.clone(),
+                    message: text,
+                    timestamp: None,
+                    time: chunk.time.or(Some(now)),
+                });
+                match position(&self.messages).filter(|_| started) {
+                    Some(index) => index,
+                    None => return started.into(),
+                }
+            }
+        };
+        if !chunk.done {
+            return Incoming::Changed;
+        }
+        let message = &mut self.messages[index];
+        message.stream = StreamState::Streamed;
+        Incoming::Message {
+            id: message.id.clone(),
+            from: message.from.clone(),
+            text: message.message.clone(),
+            conversation: message.conversation().to_string(),
+        }
+    }
+
+    // The sidebar entry for sender `from`, if they're still here.
+    pub fn sidebar_entry(&self, from: &str) -> Option<&UserProfile> {
+        self.users.iter().find(|u| u.id == from)
+    }
+}
+
+impl From<bool> for Incoming {
+    fn from(changed: bool) -> Self {
+        if changed {
+            Incoming::Changed
+        } else {
+            Incoming::Unchanged
+        }
+    }
+}
+
+// The current name for sender `from`, or `from` itself once they've left
+// and are no longer in the user list, or have no name to show.
+pub fn display_name<'a>(from: &'a str, users: &'a [UserProfile]) -> &'a str {
+    users
+        .iter()
+        .find(|u| u.id == from)
+        .map(|u| u.name.as_str())
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or(from)
+}
+
+pub fn register_frame(username: &str) -> WebSocketMessage {
+    WebSocketMessage {
+        message_type: MsgTypes::Register,
+        data: Some(username.to_string()),
+        data_array: None,
+        id: None,
+        attachment: None,
+        channel: None,
+    }
+}
+
+pub fn presence_frame(username: &str, status: PresenceStatus) -> Option<WebSocketMessage> {
+    let update = PresenceUpdate {
+        username: username.to_string(),
+        status,
+    };
+    match serde_json::to_string(&update) {
+        Ok(data) => Some(WebSocketMessage {
+            message_type: MsgTypes::Presence,
+            data: Some(data),
+            data_array: None,
+            id: None,
+            attachment: None,
+            channel: None,
+        }),
+        Err(e) => {
+            log::error!("failed to serialize presence: {}", e);
+            None
+        }
+    }
+}
+
+// What a reconnected socket sends: the registration, our presence so others
+// stop showing us as gone before the next user list, and a request for that
+// list in case it changed meanwhile.
+pub fn rejoin_frames(username: &str, status: PresenceStatus) -> Vec<WebSocketMessage> {
+    let users = WebSocketMessage {
+        message_type: MsgTypes::Users,
+        data: None,
+        data_array: None,
+        id: None,
+        attachment: None,
+        channel: None,
+    };
+    let mut frames = vec![register_frame(username)];
+    frames.extend(presence_frame(username, status));
+    frames.push(users);
+    frames
+}
+
+// Whether a message may still be edited `now`, up to `window` ms after it was
+// sent. Without a known send time its age can't be told, so it can't be.
+pub fn can_edit(message: &MessageData, now: f64, window: f64) -> bool {
+    !message.pending && message.time.is_some_and(|time| now - time <= window)
+}
+
+// Adds `id` to the selection, or takes it out if it was already there.
+pub fn toggle_selection(selected: &mut HashSet<String>, id: String) {
+    if !selected.remove(&id) {
+        selected.insert(id);
+    }
+}
+
+// The selected messages that are ours to delete: sent by `username` and
+// already confirmed by the server.
+pub fn deletable_ids(messages: &[MessageData], selected: &HashSet<String>, username: &str) -> Vec<String> {
+    messages
+        .iter()
+        .filter(|m| m.from == username && !m.pending)
+        .filter_map(|m| m.id.clone())
+        .filter(|id| selected.contains(id))
+        .collect()
+}
+
+// Composer prefill quoting each selected message in turn, in the order
+// they're shown, with a blank line between the quotes.
+pub fn quote_prefill(messages: &[MessageData], selected: &HashSet<String>) -> String {
+    messages
+        .iter()
+        .filter(|m| m.id.as_ref().is_some_and(|id| selected.contains(id)))
+        .map(|m| format_quote(&m.from, &m.message))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Closes the clear dialog, emptying `state` if it was confirmed. Returns
+// whether the dialog was open.
+pub fn answer_clear(confirm_clear: &mut bool, state: &mut ChatState, confirmed: bool) -> bool {
+    let was_open = std::mem::take(confirm_clear);
+    if was_open && confirmed {
+        state.clear();
+    }
+    was_open
+}
+
+// `names` minus anyone in `blocked`, in the same order.
+pub fn without_blocked(names: &[String], blocked: &HashSet<String>) -> Vec<String> {
+    names.iter().filter(|name| !blocked.contains(*name)).cloned().collect()
+}
+
+pub fn is_typing(input: &str) -> bool {
+    !input.trim().is_empty()
+}
+
+// The composer is focused once, on the first render after the initial load,
+// so it doesn't grab focus while the chat is still connecting. Touch devices
+// are skipped, where it would pop up the keyboard, and so is a page where
+// the user already put the focus somewhere else.
+pub fn should_autofocus(already_focused: bool, loaded: bool, touch: bool, focus_taken: bool) -> bool {
+    loaded && !already_focused && !touch && !focus_taken
+}
+
+// Whether to report the first connection as stuck: the socket hasn't
+// dropped (the reconnect banner covers that), no user list has registered
+// us, and the startup timeout has run out.
+pub fn startup_timed_out(connected: bool, registered: bool, elapsed_ms: f64) -> bool {
+    connected && !registered && elapsed_ms >= STARTUP_TIMEOUT_MS as f64
+}
+
+// Forgets the name the server turned down, keeping why for the login page
+// to show.
+pub fn reject_name(username: &mut String, login_error: &mut Option<String>, error: String) {
+    username.clear();
+    *login_error = Some(error);
+}
+
+pub fn message_frame(
+    id: &str,
+    text: &str,
+    attachment: Option<&Attachment>,
+    channel: Option<&str>,
+) -> WebSocketMessage {
+    WebSocketMessage {
+        message_type: MsgTypes::Message,
+        data: Some(text.to_string()),
+        data_array: None,
+        id: Some(id.to_string()),
+        attachment: attachment.cloned(),
+        channel: channel.map(String::from),
+    }
+}
+
+// Serializes a frame and queues it on `tx` for the socket. Any failure is
+// logged and the frame dropped instead of panicking. Returns whether it was
+// queued.
+pub fn queue_frame(tx: &Sender<String>, message: &WebSocketMessage) -> bool {
+    let payload = match serde_json::to_string(message) {
+        Ok(payload) => payload,
+        Err(e) => {
+            log::error!("failed to serialize {:?} frame: {}", message.message_type, e);
+            return false;
+        }
+    };
+
+    if let Err(e) = tx.clone().try_send(payload) {
+        log::debug!("error sending to channel: {:?}", e);
+        return false;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::outbox::Outbox;
+    use futures::StreamExt;
+
+    fn frame(message_type: MsgTypes, data: Option<&str>, data_array: Option<&[&str]>) -> WebSocketMessage {
+        WebSocketMessage {
+            message_type,
+            data: data.map(String::from),
+            data_array: data_array.map(|names| names.iter().map(|n| n.to_string()).collect()),
+            id: None,
+            attachment: None,
+            channel: None,
+        }
+    }
+
+    fn message(id: &str, from: &str, text: &str, time: f64) -> WebSocketMessage {
+        let data = format!(
+            r#"{{"id":"{}","from":"{}","message":"{}","time":{}}}"#,
+            id, from, text, time
+        );
+        frame(MsgTypes::Message, Some(&data), None)
+    }
+
+    fn typing(username: &str, is_typing: bool) -> WebSocketMessage {
+        let data = format!(r#"{{"username":"{}","is_typing":{}}}"#, username, is_typing);
+        frame(MsgTypes::Typing, Some(&data), None)
+    }
+
+    fn chunk(id: &str, from: &str, delta: &str, done: bool) -> WebSocketMessage {
+        let data = format!(
+            r#"{{"id":"{}","from":"{}","delta":"{}","done":{},"time":1000}}"#,
+            id, from, delta, done
+        );
+        frame(MsgTypes::MessageChunk, Some(&data), None)
+    }
+
+    fn texts(state: &ChatState) -> Vec<&str> {
+        state.messages.iter().map(|m| m.message.as_str()).collect()
+    }
+
+    #[test]
+    fn users_frame_replaces_the_list() {
+        let mut state = ChatState::default();
+        let incoming = state.apply_incoming(frame(MsgTypes::Users, None, Some(&["alice", "bob"])), 0.0, false);
+        assert_eq!(incoming, Incoming::Users { changed: true });
+        let names: Vec<&str> = state.users.iter().map(|u| u.name.as_str()).collect();
+        assert_eq!(names, ["alice", "bob"]);
+        assert_eq!(state.users[0].avatar, avatar_url("alice"));
+
+        let incoming = state.apply_incoming(frame(MsgTypes::Users, None, Some(&["alice", "bob"])), 0.0, false);
+        assert_eq!(incoming, Incoming::Users { changed: false });
+
+        let incoming = state.apply_incoming(frame(MsgTypes::Users, None, None), 0.0, false);
+        assert_eq!(incoming, Incoming::Users { changed: true });
+        assert!(state.users.is_empty());
+    }
+
+    #[test]
+    fn users_frame_carries_the_room_capacity() {
+        let mut state = ChatState::default();
+        state.apply_incoming(frame(MsgTypes::Users, Some("20"), Some(&["alice"])), 0.0, false);
+        assert_eq!(state.capacity, Some(20));
+
+        let incoming = state.apply_incoming(frame(MsgTypes::Users, None, Some(&["alice"])), 0.0, false);
+        assert_eq!(incoming, Incoming::Users { changed: true });
+        assert_eq!(state.capacity, None);
+    }
+
+    #[test]
+    fn message_frame_adds_the_message() {
+        let mut state = ChatState::default();
+        let incoming = state.apply_incoming(message("m1", "alice", "hi", 1_000.0), 5_000.0, false);
+        assert_eq!(
+            incoming,
+            Incoming::Message {
+                id: Some("m1".into()),
+                from: "alice".into(),
+                text: "hi".into(),
+                conversation: GENERAL.into(),
+            }
+        );
+        assert_eq!(texts(&state), ["hi"]);
+        assert_eq!(state.messages[0].time, Some(1_000.0));
+        assert_eq!(state.just_added.as_deref(), Some("m1"));
+    }
+
+    #[test]
+    fn repeated_message_ids_are_dropped() {
+        let mut state = ChatState::default();
+        state.apply_incoming(message("m1", "alice", "hi", 1_000.0), 0.0, false);
+        let incoming = state.apply_incoming(message("m1", "alice", "hi", 1_000.0), 0.0, false);
+        assert_eq!(incoming, Incoming::Unchanged);
+        assert_eq!(texts(&state), ["hi"]);
+    }
+
+    #[test]
+    fn messages_are_kept_in_time_order() {
+        let mut state = ChatState::default();
+        state.apply_incoming(message("m2", "bob", "second", 2_000.0), 0.0, false);
+        state.apply_incoming(message("m1", "alice", "first", 1_000.0), 0.0, false);
+        state.apply_incoming(message("m3", "alice", "third", 3_000.0), 0.0, false);
+        assert_eq!(texts(&state), ["first", "second", "third"]);
+        // Only the message that landed at the end animates in.
+        assert_eq!(state.just_added.as_deref(), Some("m3"));
+    }
+
+    #[test]
+    fn echo_replaces_the_pending_copy() {
+        let mut state = ChatState::default();
+        state.push_message(MessageData {
+            id: Some("m1".into()),
+            pending: true,
+            unsupported: false,
+            edited: false,
+            channel: None,
+            stream: StreamState::Whole,
+            attachment: None,
+            poll: None,
+            from: "alice".into(),
+            message: "hi".into(),
+            timestamp: None,
+            time: Some(1_000.0),
+        });
+        let incoming = state.apply_incoming(message("m1", "alice", "hi", 1_500.0), 0.0, false);
+        assert!(matches!(incoming, Incoming::Message { .. }));
+        assert_eq!(state.messages.len(), 1);
+        assert!(!state.messages[0].pending);
+        assert_eq!(state.messages[0].time, Some(1_500.0));
+        // It already animated in when it was sent.
+        assert_eq!(state.just_added, None);
+    }
+
+    #[test]
+    fn echo_from_someone_else_keeps_the_pending_copy() {
+        let mut state = ChatState::default();
+        state.push_message(MessageData {
+            id: Some("m1".into()),
+            pending: true,
+            unsupported: false,
+            edited: false,
+            channel: None,
+            stream: StreamState::Whole,
+            attachment: None,
+            poll: None,
+            from: "alice".into(),
+            message: "hi".into(),
+            timestamp: None,
+            time: Some(1_000.0),
+        });
+        state.apply_incoming(message("m1", "mallory", "spoofed", 1_500.0), 0.0, false);
+        assert_eq!(state.messages.len(), 1);
+        assert!(state.messages[0].pending);
+        assert_eq!(texts(&state), ["hi"]);
+    }
+
+    #[test]
+    fn message_without_a_time_uses_now() {
+        let mut state = ChatState::default();
+        let data = r#"{"id":"m1","from":"alice","message":"hi"}"#;
+        state.apply_incoming(frame(MsgTypes::Message, Some(data), None), 7_000.0, false);
+        assert_eq!(state.messages[0].time, Some(7_000.0));
+    }
+
+    #[test]
+    fn unreadable_message_is_dropped_or_shown_as_placeholder() {
+        let mut state = ChatState::default();
+        let incoming = state.apply_incoming(frame(MsgTypes::Message, Some("{"), None), 0.0, false);
+        assert_eq!(incoming, Incoming::Unchanged);
+        assert!(state.messages.is_empty());
+
+        let mut state = ChatState::default();
+        let incoming = state.apply_incoming(frame(MsgTypes::Message, None, None), 0.0, true);
+        assert_eq!(incoming, Incoming::Changed);
+        assert!(state.messages[0].unsupported);
+    }
+
+    #[test]
+    fn oversized_message_is_cut_short() {
+        let mut state = ChatState::default();
+        let long = "a".repeat(MAX_INCOMING_MESSAGE_CHARS + 50);
+        state.apply_incoming(message("m1", "alice", &long, 1_000.0), 0.0, false);
+        let text = &state.messages[0].message;
+        assert_eq!(text.chars().count(), MAX_INCOMING_MESSAGE_CHARS);
+        assert!(text.ends_with('…'));
+
+        let exact = "b".repeat(MAX_INCOMING_MESSAGE_CHARS);
+        state.apply_incoming(message("m2", "alice", &exact, 2_000.0), 0.0, false);
+        assert_eq!(state.messages[1].message, exact);
+    }
+
+    #[test]
+    fn oversized_user_list_is_capped() {
+        let mut state = ChatState::default();
+        let names: Vec<String> = (0..MAX_USERS + 5).map(|i| format!("user{}", i)).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        state.apply_incoming(frame(MsgTypes::Users, None, Some(&names)), 0.0, false);
+        assert_eq!(state.users.len(), MAX_USERS);
+        assert_eq!(state.users[0].name, "user0");
+    }
+
+    #[test]
+    fn sidebar_entry_is_found_by_sender_id() {
+        let mut state = ChatState::default();
+        state.apply_incoming(frame(MsgTypes::Users, None, Some(&["alice", "bob"])), 0.0, false);
+        assert_eq!(state.sidebar_entry("bob").map(|u| u.id.as_str()), Some("bob"));
+        // Gone from the user list, so there's nothing to jump to.
+        assert!(state.sidebar_entry("carol").is_none());
+    }
+
+    #[test]
+    fn chunks_assemble_into_one_message() {
+        let mut state = ChatState::default();
+        assert_eq!(state.apply_incoming(chunk("s1", "bot", "Hel", false), 0.0, false), Incoming::Changed);
+        assert_eq!(state.apply_incoming(chunk("s1", "bot", "lo", false), 0.0, false), Incoming::Changed);
+        assert_eq!(texts(&state), ["Hello"]);
+        assert_eq!(state.messages[0].stream, StreamState::Streaming);
+
+        let incoming = state.apply_incoming(chunk("s1", "bot", "!", true), 0.0, false);
+        assert_eq!(
+            incoming,
+            Incoming::Message {
+                id: Some("s1".into()),
+                from: "bot".into(),
+                text: "Hello!".into(),
+                conversation: GENERAL.into(),
+            }
+        );
+        assert_eq!(state.messages[0].stream, StreamState::Streamed);
+
+        // Nothing more is added once it's complete.
+        assert_eq!(state.apply_incoming(chunk("s1", "bot", "?", false), 0.0, false), Incoming::Unchanged);
+        assert_eq!(texts(&state), ["Hello!"]);
+    }
+
+    #[test]
+    fn chunks_from_another_sender_are_ignored() {
+        let mut state = ChatState::default();
+        state.apply_incoming(chunk("s1", "bot", "Hi", false), 0.0, false);
+        assert_eq!(state.apply_incoming(chunk("s1", "mallory", " there", false), 0.0, false), Incoming::Unchanged);
+        assert_eq!(texts(&state), ["Hi"]);
+    }
+
+    #[test]
+    fn complete_message_replaces_streamed_text() {
+        let mut state = ChatState::default();
+        state.apply_incoming(chunk("s1", "bot", "Hel", false), 0.0, false);
+        let incoming = state.apply_incoming(message("s1", "bot", "Hello", 1_000.0), 0.0, false);
+        assert!(matches!(incoming, Incoming::Message { .. }));
+        assert_eq!(texts(&state), ["Hello"]);
+        assert_eq!(state.messages[0].stream, StreamState::Streamed);
+
+        // The same text again after the stream finished changes nothing.
+        let incoming = state.apply_incoming(message("s1", "bot", "Hello", 1_000.0), 0.0, false);
+        assert_eq!(incoming, Incoming::Unchanged);
+        assert_eq!(state.messages.len(), 1);
+    }
+
+    #[test]
+    fn frames_are_queued_as_json() {
+        let (tx, mut rx) = futures::channel::mpsc::channel::<String>(4);
+        assert!(queue_frame(&tx, &register_frame("alice")));
+        let queued = futures::executor::block_on(rx.next()).unwrap();
+        let frame: WebSocketMessage = serde_json::from_str(&queued).unwrap();
+        assert_eq!(frame.data.as_deref(), Some("alice"));
+    }
+
+    #[test]
+    fn frames_for_a_closed_socket_are_dropped() {
+        let (mut tx, _rx) = futures::channel::mpsc::channel::<String>(4);
+        tx.close_channel();
+        assert!(!queue_frame(&tx, &register_frame("alice")));
+    }
+
+    #[test]
+    fn whitespace_alone_isnt_typing() {
+        assert!(!is_typing(""));
+        assert!(!is_typing("  \n\t"));
+        assert!(is_typing(" hi "));
+    }
+
+    #[test]
+    fn typing_frames_add_and_remove_users() {
+        let mut state = ChatState::default();
+        assert_eq!(state.apply_incoming(typing("alice", true), 0.0, false), Incoming::Typing);
+        assert_eq!(state.apply_incoming(typing("bob", true), 0.0, false), Incoming::Typing);
+        assert_eq!(state.apply_incoming(typing("alice", true), 0.0, false), Incoming::Unchanged);
+        assert_eq!(state.typing_users, ["alice", "bob"]);
+
+        assert_eq!(state.apply_incoming(typing("alice", false), 0.0, false), Incoming::Typing);
+        assert_eq!(state.apply_incoming(typing("alice", false), 0.0, false), Incoming::Unchanged);
+        assert_eq!(state.typing_users, ["bob"]);
+    }
+
+    #[test]
+    fn typing_display_outlives_the_last_typist() {
+        let mut state = ChatState::default();
+        state.apply_incoming(typing("alice", true), 0.0, false);
+        state.apply_incoming(typing("alice", false), 0.0, false);
+        assert!(state.typing_users.is_empty());
+        // Kept so the indicator can fade out with the name still showing.
+        assert_eq!(state.typing_display, ["alice"]);
+    }
+
+    #[test]
+    fn malformed_typing_frame_is_ignored() {
+        let mut state = ChatState::default();
+        let incoming = state.apply_incoming(frame(MsgTypes::Typing, Some("not json"), None), 0.0, false);
+        assert_eq!(incoming, Incoming::Unchanged);
+        assert!(state.typing_users.is_empty());
+    }
+
+    fn history(messages: &[(&str, &str, f64)], has_more: bool) -> WebSocketMessage {
+        let data: Vec<String> = messages
+            .iter()
+            .map(|(id, text, time)| format!(r#"{{"id":"{}","from":"alice","message":"{}","time":{}}}"#, id, text, time))
+            .collect();
+        let data: Vec<&str> = data.iter().map(String::as_str).collect();
+        let page = format!(r#"{{"hasMore":{}}}"#, has_more);
+        frame(MsgTypes::History, Some(&page), Some(&data))
+    }
+
+    #[test]
+    fn history_page_goes_above_loaded_messages() {
+        let mut state = ChatState::default();
+        state.apply_incoming(message("m3", "alice", "third", 3_000.0), 0.0, false);
+        let incoming = state.apply_incoming(
+            history(&[("m1", "first", 1_000.0), ("m2", "second", 2_000.0)], true),
+            0.0,
+            false,
+        );
+        assert_eq!(incoming, Incoming::History { added: 2 });
+        assert_eq!(texts(&state), ["first", "second", "third"]);
+        assert!(state.has_more_history);
+        assert_eq!(state.just_added, None);
+    }
+
+    #[test]
+    fn history_skips_messages_already_shown() {
+        let mut state = ChatState::default();
+        state.apply_incoming(message("m2", "alice", "second", 2_000.0), 0.0, false);
+        let incoming = state.apply_incoming(
+            history(&[("m1", "first", 1_000.0), ("m2", "second", 2_000.0)], false),
+            0.0,
+            false,
+        );
+        assert_eq!(incoming, Incoming::History { added: 1 });
+        assert_eq!(texts(&state), ["first", "second"]);
+        assert!(!state.has_more_history);
+    }
+
+    #[test]
+    fn rejoining_registers_and_announces_presence() {
+        let frames = rejoin_frames("alice", PresenceStatus::Away);
+        let types: Vec<String> = frames.iter().map(|f| format!("{:?}", f.message_type)).collect();
+        assert_eq!(types, ["Register", "Presence", "Users"]);
+        assert_eq!(frames[0].data.as_deref(), Some("alice"));
+
+        let presence: PresenceUpdate = serde_json::from_str(frames[1].data.as_deref().unwrap()).unwrap();
+        assert_eq!(presence.username, "alice");
+        assert_eq!(presence.status, PresenceStatus::Away);
+    }
+
+    #[test]
+    fn time_sync_reply_reports_both_clocks() {
+        let mut state = ChatState::default();
+        let reply = frame(MsgTypes::TimeSync, Some(r#"{"clientTime":1000,"serverTime":1600}"#), None);
+        assert_eq!(
+            state.apply_incoming(reply, 0.0, false),
+            Incoming::TimeSync {
+                sent: 1_000.0,
+                server: 1_600.0,
+            }
+        );
+
+        // Our own request echoed back without the server's time.
+        let echo = frame(MsgTypes::TimeSync, Some(r#"{"clientTime":1000}"#), None);
+        assert_eq!(state.apply_incoming(echo, 0.0, false), Incoming::Unchanged);
+    }
+
+    #[test]
+    fn edits_only_within_the_window() {
+        let mut state = ChatState::default();
+        state.apply_incoming(message("m1", "alice", "hi", 1_000.0), 0.0, false);
+        let sent = &state.messages[0];
+        assert!(can_edit(sent, 1_000.0 + 60_000.0, 60_000.0));
+        assert!(!can_edit(sent, 1_000.0 + 60_001.0, 60_000.0));
+
+        let undated = MessageData {
+            time: None,
+            ..sent.clone()
+        };
+        assert!(!can_edit(&undated, 1_000.0, 60_000.0));
+    }
+
+    #[test]
+    fn edit_frames_only_change_the_senders_message() {
+        let mut state = ChatState::default();
+        state.apply_incoming(message("m1", "alice", "helo", 1_000.0), 0.0, false);
+
+        let by_other = frame(
+            MsgTypes::Edit,
+            Some(r#"{"username":"bob","message_id":"m1","text":"hacked"}"#),
+            None,
+        );
+        assert_eq!(state.apply_incoming(by_other, 0.0, false), Incoming::Unchanged);
+        assert_eq!(texts(&state), ["helo"]);
+
+        let by_sender = frame(
+            MsgTypes::Edit,
+            Some(r#"{"username":"alice","message_id":"m1","text":"hello"}"#),
+            None,
+        );
+        assert_eq!(state.apply_incoming(by_sender, 0.0, false), Incoming::Changed);
+        assert_eq!(texts(&state), ["hello"]);
+        assert!(state.messages[0].edited);
+    }
+
+    #[test]
+    fn toggling_a_selection_adds_then_removes_it() {
+        let mut selected = HashSet::new();
+        toggle_selection(&mut selected, "m1".into());
+        toggle_selection(&mut selected, "m2".into());
+        assert!(selected.contains("m1") && selected.contains("m2"));
+        toggle_selection(&mut selected, "m1".into());
+        assert!(!selected.contains("m1"));
+        assert_eq!(selected.len(), 1);
+    }
+
+    #[test]
+    fn only_own_selected_messages_are_deletable() {
+        let mut state = ChatState::default();
+        state.apply_incoming(message("m1", "alice", "mine", 1_000.0), 0.0, false);
+        state.apply_incoming(message("m2", "bob", "theirs", 2_000.0), 0.0, false);
+        state.apply_incoming(message("m3", "alice", "also mine", 3_000.0), 0.0, false);
+        let selected: HashSet<String> = ["m1", "m2"].iter().map(|id| id.to_string()).collect();
+        assert_eq!(deletable_ids(&state.messages, &selected, "alice"), ["m1"]);
+    }
+
+    #[test]
+    fn delete_frames_only_remove_the_senders_messages() {
+        let mut state = ChatState::default();
+        state.apply_incoming(message("m1", "alice", "mine", 1_000.0), 0.0, false);
+        state.apply_incoming(message("m2", "bob", "theirs", 2_000.0), 0.0, false);
+        let delete = frame(
+            MsgTypes::Delete,
+            Some(r#"{"username":"alice","message_ids":["m1","m2"]}"#),
+            None,
+        );
+        assert_eq!(state.apply_incoming(delete, 0.0, false), Incoming::Changed);
+        assert_eq!(texts(&state), ["theirs"]);
+    }
+
+    #[test]
+    fn quoting_a_selection_stacks_the_quotes_in_order() {
+        let mut state = ChatState::default();
+        state.apply_incoming(message("m1", "alice", "first", 1_000.0), 0.0, false);
+        state.apply_incoming(message("m2", "bob", "second", 2_000.0), 0.0, false);
+        state.apply_incoming(message("m3", "carol", "third", 3_000.0), 0.0, false);
+        let selected: HashSet<String> = ["m3", "m1"].iter().map(|id| id.to_string()).collect();
+        assert_eq!(
+            quote_prefill(&state.messages, &selected),
+            "alice wrote:\n> first\n\ncarol wrote:\n> third\n"
+        );
+        assert_eq!(quote_prefill(&state.messages, &HashSet::new()), "");
+    }
+
+    #[test]
+    fn messages_are_filed_under_their_channel() {
+        let mut state = ChatState::default();
+        let data = r#"{"id":"m1","from":"alice","message":"hi","time":1000,"channel":"random"}"#;
+        let incoming = state.apply_incoming(frame(MsgTypes::Message, Some(data), None), 0.0, false);
+        assert!(matches!(incoming, Incoming::Message { conversation, .. } if conversation == "random"));
+
+        let incoming = state.apply_incoming(message("m2", "bob", "hello", 2_000.0), 0.0, false);
+        assert!(matches!(incoming, Incoming::Message { conversation, .. } if conversation == GENERAL));
+    }
+
+    #[test]
+    fn confirming_clear_forgets_messages_and_receipts() {
+        let mut state = ChatState::default();
+        state.apply_incoming(message("m1", "alice", "hi", 1_000.0), 0.0, false);
+        let read = frame(MsgTypes::Read, Some(r#"{"username":"bob","message_id":"m1"}"#), None);
+        state.apply_incoming(read, 0.0, false);
+
+        let mut confirm_clear = true;
+        assert!(answer_clear(&mut confirm_clear, &mut state, true));
+        assert!(!confirm_clear);
+        assert!(state.messages.is_empty());
+        assert!(state.receipts.readers_of("m1").is_empty());
+
+        // Nothing is remembered, so the same message can show up again.
+        state.apply_incoming(message("m1", "alice", "hi", 1_000.0), 0.0, false);
+        assert_eq!(texts(&state), ["hi"]);
+    }
+
+    #[test]
+    fn cancelling_clear_keeps_everything() {
+        let mut state = ChatState::default();
+        state.apply_incoming(message("m1", "alice", "hi", 1_000.0), 0.0, false);
+
+        let mut confirm_clear = true;
+        assert!(answer_clear(&mut confirm_clear, &mut state, false));
+        assert!(!confirm_clear);
+        assert_eq!(texts(&state), ["hi"]);
+
+        // With the dialog already closed, confirming does nothing.
+        assert!(!answer_clear(&mut confirm_clear, &mut state, true));
+        assert_eq!(texts(&state), ["hi"]);
+    }
+
+    #[test]
+    fn blocked_names_are_filtered_out_in_order() {
+        let names: Vec<String> = ["alice", "mallory", "bob", "Mallory"].iter().map(|n| n.to_string()).collect();
+        let blocked: HashSet<String> = ["mallory".to_string()].into_iter().collect();
+        // Names are case-sensitive ids, so "Mallory" is someone else.
+        assert_eq!(without_blocked(&names, &blocked), ["alice", "bob", "Mallory"]);
+        assert_eq!(without_blocked(&names, &HashSet::new()), names);
+    }
+
+    #[test]
+    fn display_name_falls_back_to_the_sender_id() {
+        let profile = |id: &str, name: &str| UserProfile {
+            id: id.into(),
+            name: name.into(),
+            avatar: avatar_url(id),
+        };
+        let users = [profile("u1", "Alice"), profile("u2", ""), profile("u3", "  ")];
+        assert_eq!(display_name("u1", &users), "Alice");
+        assert_eq!(display_name("u2", &users), "u2");
+        assert_eq!(display_name("u3", &users), "u3");
+        assert_eq!(display_name("gone", &users), "gone");
+    }
+
+    #[test]
+    fn escape_returns_every_mode_to_normal() {
+        let mut quote = ComposerMode::Quote {
+            from: "bob".into(),
+            prior_input: "draft".into(),
+        };
+        assert_eq!(quote.cancel().as_deref(), Some("draft"));
+        assert_eq!(quote, ComposerMode::Normal);
+
+        let mut edit = ComposerMode::Edit {
+            message_id: "m1".into(),
+            prior_input: "before".into(),
+        };
+        assert_eq!(edit.cancel().as_deref(), Some("before"));
+        assert_eq!(edit, ComposerMode::Normal);
+
+        let mut code = ComposerMode::Code;
+        assert_eq!(code.cancel(), None);
+        assert_eq!(code, ComposerMode::Normal);
+
+        let mut normal = ComposerMode::Normal;
+        assert_eq!(normal.cancel(), None);
+        assert_eq!(normal, ComposerMode::Normal);
+    }
+
+    #[test]
+    fn composer_is_focused_once_after_loading() {
+        assert!(should_autofocus(false, true, false, false));
+        assert!(!should_autofocus(false, false, false, false));
+        assert!(!should_autofocus(true, true, false, false));
+    }
+
+    #[test]
+    fn autofocus_leaves_touch_devices_and_taken_focus_alone() {
+        assert!(!should_autofocus(false, true, true, false));
+        assert!(!should_autofocus(false, true, false, true));
+    }
+
+    #[test]
+    fn register_error_frame_sends_the_user_back_to_login() {
+        let mut state = ChatState::default();
+        let refused: WebSocketMessage =
+            serde_json::from_str(r#"{"messageType":"registererror","data":"That name is taken."}"#).unwrap();
+        let error = match state.apply_incoming(refused, 0.0, false) {
+            Incoming::RegisterError(error) => error,
+            other => panic!("expected a register error, got {:?}", other),
+        };
+        let (mut username, mut login_error) = (String::from("alice"), None);
+        reject_name(&mut username, &mut login_error, error);
+        assert!(username.is_empty());
+        assert_eq!(login_error.as_deref(), Some("That name is taken."));
+
+        let bare = frame(MsgTypes::RegisterError, None, None);
+        assert_eq!(
+            state.apply_incoming(bare, 0.0, false),
+            Incoming::RegisterError(String::from("That name can't be used."))
+        );
+    }
+
+    #[test]
+    fn startup_times_out_only_while_still_waiting_to_register() {
+        let timeout = STARTUP_TIMEOUT_MS as f64;
+        assert!(startup_timed_out(true, false, timeout));
+        assert!(!startup_timed_out(true, false, timeout - 1.0));
+        assert!(!startup_timed_out(true, true, timeout));
+        assert!(!startup_timed_out(false, false, timeout));
+    }
+
+    #[test]
+    fn rows_are_keyed_by_message_id() {
+        let mut message = MessageData::unsupported();
+        assert_eq!(message.row_key(3), "at:3");
+        message.id = Some(String::from("m1"));
+        assert_eq!(message.row_key(3), "id:m1");
+        assert_eq!(message.row_key(40), message.row_key(3));
+    }
+
+    #[test]
+    fn queued_messages_go_out_after_reconnecting_without_duplicates() {
+        let mut state = ChatState::default();
+        let mut outbox = Outbox::default();
+
+        // Written while disconnected: shown as pending and queued.
+        for (id, text, time) in [("m1", "first", 1_000.0), ("m2", "second", 1_100.0)] {
+            outbox.push(String::from(id), String::from(text));
+            state.push_message(MessageData {
+                id: Some(id.into()),
+                pending: true,
+                unsupported: false,
+                edited: false,
+                channel: None,
+                stream: StreamState::Whole,
+                attachment: None,
+                poll: None,
+                from: "alice".into(),
+                message: text.into(),
+                timestamp: None,
+                time: Some(time),
+            });
+        }
+        // Reconnecting replays what bob said in the meantime.
+        state.apply_incoming(message("b1", "bob", "while you were away", 2_000.0), 0.0, false);
+
+        let mut sent_frames = vec![];
+        let sent = outbox.flush(|id, text| {
+            let pending = state.pending(id);
+            sent_frames.push(message_frame(id, text, None, pending.and_then(|m| m.channel.as_deref())));
+            true
+        });
+        for id in &sent {
+            state.resend_pending(id, 3_000.0);
+        }
+        assert!(outbox.is_empty());
+        let sent_ids: Vec<_> = sent_frames.iter().map(|f| f.id.as_deref().unwrap()).collect();
+        assert_eq!(sent_ids, ["m1", "m2"]);
+        assert_eq!(texts(&state), ["while you were away", "first", "second"]);
+
+        // The echoes replace the pending copies in place.
+        state.apply_incoming(message("m1", "alice", "first", 3_050.0), 0.0, false);
+        state.apply_incoming(message("m2", "alice", "second", 3_060.0), 0.0, false);
+        assert_eq!(texts(&state), ["while you were away", "first", "second"]);
+        assert!(state.messages.iter().all(|m| !m.pending));
+    }
+}
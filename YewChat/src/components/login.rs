@@ -23,8 +23,12 @@ pub fn login() -> Html {
     let onclick = {
         let username = username.clone();
         let user = user.clone();
-        Callback::from(move |_| *user.username.borrow_mut() = (*username).clone())
+        Callback::from(move |_| {
+            *user.username.borrow_mut() = (*username).clone();
+            *user.login_error.borrow_mut() = None;
+        })
     };
+    let login_error = user.login_error.borrow().clone();
 
     html! {
         <div class="bg-gray-800 flex w-screen">
@@ -33,6 +37,12 @@ pub fn login() -> Html {
                     <input {oninput} class="rounded-l-lg p-4 border-t mr-0 border-b border-l text-gray-800 border-gray-200 bg-white" placeholder="Username"/>
                     <Link<Route> to={Route::Chat}> <button {onclick} disabled={username.len()<1} class="px-8 rounded-r-lg bg-violet-600	  text-white font-bold p-4 uppercase border-violet-600 border-t border-b border-r" >{"Go Chatting!"}</button></Link<Route>>
                 </form>
+                {
+                    match login_error {
+                        Some(error) => html! { <div class="text-sm text-red-400">{error}</div> },
+                        None => html! {},
+                    }
+                }
             </div>
         </div>
     }
@@ -0,0 +1,133 @@
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::utils::emoji::{split_custom_emoji, CustomEmoji, EmojiPart};
+use crate::utils::text::{
+    is_image_url, is_safe_url, linkify, match_ranges, parse_fenced, split_spoilers, Segment,
+    SpoilerPart,
+};
+
+// Renders the text of a message. A message that is a single fenced code
+// block is shown as preformatted code, a bare image link as an image and
+// anything else as text with `||spoilers||` hidden, http(s) and mailto links
+// made clickable and registered `:custom:` emoji shown as images. Matches of the
+// active search `query` are highlighted, except inside spoilers where that
+// would give them away. Message text
+// only ever ends up in text nodes, in `src` values that passed `is_safe_url`
+// and in `href` values that passed `is_linkable_url`, never as markup or
+// other attributes.
+pub fn render_body(text: &str, custom_emoji: &CustomEmoji, query: &str) -> Html {
+    if let Some(block) = parse_fenced(text) {
+        return html! {
+            <div class="mt-1">
+                {
+                    match block.language {
+                        Some(language) => html! {
+                            <div class="mb-1 text-xs text-gray-500">{language}</div>
+                        },
+                        None => html! {},
+                    }
+                }
+                <pre class="p-2 overflow-x-auto text-xs text-gray-100 bg-gray-800 rounded"><code>{block.body}</code></pre>
+            </div>
+        };
+    }
+
+    if is_image_url(text) {
+        return html! {
+            <img class="mt-3" src={text.to_string()}/>
+        };
+    }
+
+    split_spoilers(text)
+        .into_iter()
+        .map(|part| match part {
+            SpoilerPart::Plain(text) => render_links(text, custom_emoji, query),
+            SpoilerPart::Spoiler(text) => html! {
+                <Spoiler text={text.to_string()} custom_emoji={custom_emoji.clone()}/>
+            },
+        })
+        .collect::<Html>()
+}
+
+fn render_links(text: &str, custom_emoji: &CustomEmoji, query: &str) -> Html {
+    linkify(text)
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Text(text) => render_emoji(text, custom_emoji, query),
+            Segment::Link(url) => html! {
+                <a
+                    href={url.to_string()}
+                    target="_blank"
+                    rel="noopener noreferrer nofollow"
+                    class="text-blue-600 underline break-all"
+                >
+                    {highlight_matches(url, query)}
+                </a>
+            },
+        })
+        .collect::<Html>()
+}
+
+fn render_emoji(text: &str, custom_emoji: &CustomEmoji, query: &str) -> Html {
+    split_custom_emoji(text, custom_emoji)
+        .into_iter()
+        .map(|part| match part {
+            EmojiPart::Image { code, url } if is_safe_url(url) => html! {
+                <img class="inline w-5 h-5 align-text-bottom" src={url.to_string()} alt={format!(":{}:", code)} title={format!(":{}:", code)}/>
+            },
+            EmojiPart::Image { code, .. } => html! { {format!(":{}:", code)} },
+            EmojiPart::Text(text) => highlight_matches(text, query),
+        })
+        .collect::<Html>()
+}
+
+// `text` with every case-insensitive match of `query` wrapped in `<mark>`.
+pub fn highlight_matches(text: &str, query: &str) -> Html {
+    let mut nodes = Vec::new();
+    let mut last = 0;
+    for (start, end) in match_ranges(text, query) {
+        if last < start {
+            nodes.push(html! { {text[last..start].to_string()} });
+        }
+        nodes.push(html! { <mark class="bg-yellow-200 rounded">{text[start..end].to_string()}</mark> });
+        last = end;
+    }
+    if last < text.len() {
+        nodes.push(html! { {text[last..].to_string()} });
+    }
+    nodes.into_iter().collect::<Html>()
+}
+
+#[derive(Properties, PartialEq)]
+pub struct SpoilerProps {
+    pub text: String,
+    pub custom_emoji: CustomEmoji,
+}
+
+// Blacked out until clicked. Each spoiler keeps its own state, so several in
+// one message are revealed independently.
+#[function_component(Spoiler)]
+pub fn spoiler(props: &SpoilerProps) -> Html {
+    let revealed = use_state(|| false);
+
+    if *revealed {
+        return html! {
+            <span class="px-1 bg-gray-200 rounded">{render_links(&props.text, &props.custom_emoji, "")}</span>
+        };
+    }
+
+    let onclick = {
+        let revealed = revealed.clone();
+        Callback::from(move |_| revealed.set(true))
+    };
+    html! {
+        <span
+            {onclick}
+            title="Spoiler, click to reveal"
+            class="px-1 text-gray-800 bg-gray-800 rounded cursor-pointer select-none"
+        >
+            {props.text.clone()}
+        </span>
+    }
+}
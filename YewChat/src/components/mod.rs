@@ -1,2 +1,4 @@
 pub mod chat;
-pub mod login;
\ No newline at end of file
+mod chat_state;
+pub mod login;
+pub mod message_body;
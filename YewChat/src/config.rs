@@ -0,0 +1,143 @@
+// Socket endpoint used when nothing else is configured. Can be injected at
+// build time, e.g. `YEWCHAT_WS_URL=wss://chat.example.com npm run build`.
+pub const DEFAULT_WS_URL: &str = match option_env!("YEWCHAT_WS_URL") {
+    Some(url) => url,
+    None => "ws://127.0.0.1:8080",
+};
+
+// Soft limit shown in the composer's character counter.
+pub const MAX_MESSAGE_LENGTH: usize = 2000;
+
+// Hard limits on what the server sends, so a broken or hostile server can't
+// freeze rendering. Longer messages are cut short and extra users dropped.
+pub const MAX_INCOMING_MESSAGE_CHARS: usize = 10_000;
+pub const MAX_USERS: usize = 1_000;
+
+// How many older messages to ask for each time the list is scrolled to the top.
+pub const HISTORY_PAGE_SIZE: u32 = 50;
+
+// Largest image that may be dropped into the chat, in bytes.
+pub const MAX_ATTACHMENT_BYTES: f64 = 5.0 * 1024.0 * 1024.0;
+
+// Messages longer than either limit are collapsed behind "Show more".
+pub const COLLAPSE_MAX_LINES: usize = 8;
+pub const COLLAPSE_MAX_CHARS: usize = 600;
+
+// Timings, in milliseconds. The presence ones can be overridden per page load
+// (see `idle_after_ms` and `presence_heartbeat_ms`) to trade chattiness for
+// accuracy without a rebuild.
+pub const IDLE_AFTER_MS: u32 = 5 * 60 * 1000;
+pub const IDLE_CHECK_MS: u32 = 30_000;
+pub const PRESENCE_HEARTBEAT_MS: u32 = 60_000;
+pub const READ_RECEIPT_THROTTLE_MS: u32 = 1_000;
+pub const SCROLL_THROTTLE_MS: u32 = 100;
+// How often the header's "Online for" duration is redrawn.
+pub const SESSION_TICK_MS: u32 = 60_000;
+pub const TOAST_DURATION_MS: u32 = 4_000;
+// Typing shows as stopped after this long without a keystroke.
+pub const TYPING_IDLE_MS: u32 = 3_000;
+pub const INITIAL_RECONNECT_DELAY_MS: u32 = 1_000;
+pub const MAX_RECONNECT_DELAY_MS: u32 = 30_000;
+// How long the first connection gets to answer our registration before the
+// chat says it can't reach the server.
+pub const STARTUP_TIMEOUT_MS: u32 = 8_000;
+// After this long without any input the chat asks "Still there?", and closes
+// the connection if nobody answers within the grace period.
+pub const INACTIVITY_PROMPT_MS: u32 = 30 * 60 * 1000;
+pub const INACTIVITY_GRACE_MS: u32 = 60_000;
+
+// Automatic reconnects stop after this many failures in a row so a server
+// that is down isn't hammered forever. `?max_reconnects=` overrides it.
+pub const MAX_RECONNECT_ATTEMPTS: u32 = 10;
+
+// Own messages can be edited for this long after they're sent.
+// `?edit_window_ms=` overrides it.
+pub const EDIT_WINDOW_MS: u32 = 15 * 60 * 1000;
+
+pub fn edit_window_ms() -> u32 {
+    query_ms("edit_window_ms").unwrap_or(EDIT_WINDOW_MS)
+}
+
+pub fn max_reconnect_attempts() -> u32 {
+    query_param("max_reconnects")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(MAX_RECONNECT_ATTEMPTS)
+}
+
+// How long without input before the user shows as away. `?idle_after_ms=`
+// overrides it.
+pub fn idle_after_ms() -> u32 {
+    query_ms("idle_after_ms").unwrap_or(IDLE_AFTER_MS)
+}
+
+// How often our presence is re-sent even when it hasn't changed, so a long
+// idle read doesn't look like we've gone. `?heartbeat_ms=` overrides it.
+pub fn presence_heartbeat_ms() -> u32 {
+    heartbeat_ms_from(query_param("heartbeat_ms").as_deref())
+}
+
+// The heartbeat interval given the `?heartbeat_ms=` value, if any.
+pub fn heartbeat_ms_from(query: Option<&str>) -> u32 {
+    query.and_then(parse_ms).unwrap_or(PRESENCE_HEARTBEAT_MS)
+}
+
+fn query_ms(name: &str) -> Option<u32> {
+    parse_ms(&query_param(name)?)
+}
+
+// A positive number of milliseconds; anything else leaves the default.
+fn parse_ms(raw: &str) -> Option<u32> {
+    raw.parse().ok().filter(|ms| *ms > 0)
+}
+
+// Reads `?name=value` from the current page URL.
+pub fn query_param(name: &str) -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    web_sys::UrlSearchParams::new_with_str(&search).ok()?.get(name)
+}
+
+// `?debug` surfaces things that are normally dropped quietly, such as
+// messages this client can't read.
+pub fn debug_mode() -> bool {
+    matches!(query_param("debug").as_deref(), Some("" | "1" | "true"))
+}
+
+// Open Graph proxy used for link preview cards. Browsers can't fetch other
+// sites' metadata directly because of CORS, so previews stay off unless a
+// proxy is set with `?preview_proxy=` or `YEWCHAT_PREVIEW_URL` at build time.
+pub fn link_preview_endpoint() -> Option<String> {
+    query_param("preview_proxy").or_else(|| option_env!("YEWCHAT_PREVIEW_URL").map(String::from))
+}
+
+// Translates messages on request: it gets `{ "text", "target" }` POSTed and
+// answers `{ "text": "..." }`. Set with `?translate_url=` or
+// `YEWCHAT_TRANSLATE_URL` at build time; without one there's no Translate
+// action.
+pub fn translate_endpoint() -> Option<String> {
+    query_param("translate_url").or_else(|| option_env!("YEWCHAT_TRANSLATE_URL").map(String::from))
+}
+
+// Endpoint dropped images are POSTed to before their URL is sent as a
+// message. Set with `?upload_url=` or `YEWCHAT_UPLOAD_URL` at build time;
+// without one, dropping a file just explains that uploads are off.
+pub fn upload_endpoint() -> Option<String> {
+    query_param("upload_url").or_else(|| option_env!("YEWCHAT_UPLOAD_URL").map(String::from))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heartbeat_defaults_to_the_configured_interval() {
+        assert_eq!(heartbeat_ms_from(None), PRESENCE_HEARTBEAT_MS);
+        assert_eq!(heartbeat_ms_from(Some("5000")), 5_000);
+    }
+
+    #[test]
+    fn unusable_heartbeat_overrides_are_ignored() {
+        for raw in ["", "0", "-5", "soon", "1.5"] {
+            assert_eq!(heartbeat_ms_from(Some(raw)), PRESENCE_HEARTBEAT_MS, "{}", raw);
+        }
+    }
+}
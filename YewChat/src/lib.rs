@@ -1,7 +1,9 @@
 #![recursion_limit = "512"]
 
 mod components;
+mod config;
 mod services;
+mod utils;
 
 use components::login::Login;
 use components::chat::Chat;
@@ -17,6 +19,8 @@ pub type User = Rc<UserInner>;
 #[derive(Debug, PartialEq)]
 pub struct UserInner {
     pub username: RefCell<String>,
+    // Why the last attempt to join was turned down, shown on the login page.
+    pub login_error: RefCell<Option<String>>,
 }
 
 // When the `wee_alloc` feature is enabled, this uses `wee_alloc` as the global
@@ -52,6 +56,7 @@ fn main() -> Html {
     let ctx = use_state(|| {
         Rc::new(UserInner {
             username: RefCell::new("initial".into()),
+            login_error: RefCell::new(None),
         })
     });
 
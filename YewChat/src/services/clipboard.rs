@@ -0,0 +1,21 @@
+use js_sys::{Function, Reflect};
+use wasm_bindgen::{JsCast, JsValue};
+
+// Copies `text` with the async Clipboard API. web-sys only exposes that API
+// behind `web_sys_unstable_apis`, so it is looked up through `Reflect`.
+// Returns whether the copy was started.
+pub fn copy_text(text: &str) -> bool {
+    let result = (|| -> Result<(), JsValue> {
+        let window = web_sys::window().ok_or_else(|| JsValue::from_str("no window"))?;
+        let navigator = Reflect::get(&window, &JsValue::from_str("navigator"))?;
+        let clipboard = Reflect::get(&navigator, &JsValue::from_str("clipboard"))?;
+        let write_text: Function = Reflect::get(&clipboard, &JsValue::from_str("writeText"))?.dyn_into()?;
+        write_text.call1(&clipboard, &JsValue::from_str(text))?;
+        Ok(())
+    })();
+
+    if let Err(e) = &result {
+        log::debug!("clipboard unavailable: {:?}", e);
+    }
+    result.is_ok()
+}
@@ -0,0 +1,25 @@
+use wasm_bindgen::{JsCast, JsValue};
+
+// Saves `text` as a file named `filename` by clicking a temporary link to a
+// `data:` URL. Returns whether the download was started.
+pub fn download_text(filename: &str, text: &str) -> bool {
+    let result = (|| -> Result<(), JsValue> {
+        let document = web_sys::window()
+            .and_then(|w| w.document())
+            .ok_or_else(|| JsValue::from_str("no document"))?;
+        let href = format!(
+            "data:text/plain;charset=utf-8,{}",
+            String::from(js_sys::encode_uri_component(text))
+        );
+        let link: web_sys::HtmlElement = document.create_element("a")?.dyn_into()?;
+        link.set_attribute("href", &href)?;
+        link.set_attribute("download", filename)?;
+        link.click();
+        Ok(())
+    })();
+
+    if let Err(e) = &result {
+        log::debug!("download failed: {:?}", e);
+    }
+    result.is_ok()
+}
@@ -0,0 +1,55 @@
+use wasm_bindgen::JsCast;
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
+
+const PROBE_SIZE: u32 = 16;
+// A private-use code point no font draws, so it comes out as the platform's
+// missing-glyph box.
+const MISSING_GLYPH: &str = "\u{10FFFD}";
+
+struct Probe {
+    context: CanvasRenderingContext2d,
+    missing: Option<Vec<u8>>,
+}
+
+thread_local! {
+    static PROBE: Option<Probe> = probe();
+}
+
+fn probe() -> Option<Probe> {
+    let canvas: HtmlCanvasElement = web_sys::window()?
+        .document()?
+        .create_element("canvas")
+        .ok()?
+        .dyn_into()
+        .ok()?;
+    canvas.set_width(PROBE_SIZE);
+    canvas.set_height(PROBE_SIZE);
+    let context: CanvasRenderingContext2d = canvas.get_context("2d").ok()??.dyn_into().ok()?;
+    context.set_font(&format!("{}px sans-serif", PROBE_SIZE - 2));
+    context.set_text_baseline("top");
+    let missing = pixels(&context, MISSING_GLYPH);
+    Some(Probe { context, missing })
+}
+
+fn pixels(context: &CanvasRenderingContext2d, text: &str) -> Option<Vec<u8>> {
+    let size = PROBE_SIZE as f64;
+    context.clear_rect(0.0, 0.0, size, size);
+    context.fill_text(text, 0.0, 0.0).ok()?;
+    Some(context.get_image_data(0.0, 0.0, size, size).ok()?.data().0)
+}
+
+// Whether this platform has a glyph for `glyph`, judged by drawing it to a
+// small canvas: blank, or identical to the missing-glyph box, means no. Only
+// approximate, so anything that can't be checked counts as supported.
+pub fn is_emoji_supported(glyph: &str) -> bool {
+    PROBE.with(|probe| {
+        let probe = match probe {
+            Some(probe) => probe,
+            None => return true,
+        };
+        match pixels(&probe.context, glyph) {
+            Some(drawn) => drawn.iter().any(|&b| b != 0) && Some(&drawn) != probe.missing.as_ref(),
+            None => true,
+        }
+    })
+}
@@ -5,6 +5,37 @@ use yew_agent::{Agent, AgentLink, Context, HandlerId};
 #[derive(Serialize, Deserialize, Debug)]
 pub enum Request {
     EventBusMsg(String),
+    Publish(ChatEvent),
+}
+
+// Structured events the chat publishes so other components (a header badge,
+// a notifier, ...) can react without talking to the Chat component directly.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum ChatEvent {
+    NewMessage { from: String, message: String },
+    TypingChanged(Vec<String>),
+    // `detail` says why a connection was lost, when that is known.
+    ConnectionChanged {
+        connection: u32,
+        connected: bool,
+        detail: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum BusEvent {
+    // A raw frame received from the websocket.
+    Socket(String),
+    Chat(ChatEvent),
+}
+
+impl From<Request> for BusEvent {
+    fn from(request: Request) -> Self {
+        match request {
+            Request::EventBusMsg(s) => BusEvent::Socket(s),
+            Request::Publish(event) => BusEvent::Chat(event),
+        }
+    }
 }
 
 pub struct EventBus {
@@ -16,7 +47,7 @@ impl Agent for EventBus {
     type Reach = Context<Self>;
     type Message = ();
     type Input = Request;
-    type Output = String;
+    type Output = BusEvent;
 
     fn create(link: AgentLink<Self>) -> Self {
         Self {
@@ -28,13 +59,7 @@ impl Agent for EventBus {
     fn update(&mut self, _msg: Self::Message) {}
 
     fn handle_input(&mut self, msg: Self::Input, _id: HandlerId) {
-        match msg {
-            Request::EventBusMsg(s) => {
-                for sub in self.subscribers.iter() {
-                    self.link.respond(*sub, s.clone())
-                }
-            }
-        }
+        self.broadcast(msg.into());
     }
 
     fn connected(&mut self, id: HandlerId) {
@@ -44,4 +69,54 @@ impl Agent for EventBus {
     fn disconnected(&mut self, id: HandlerId) {
         self.subscribers.remove(&id);
     }
-}
\ No newline at end of file
+}
+
+impl EventBus {
+    fn broadcast(&self, event: BusEvent) {
+        fan_out(&self.subscribers, event, |sub, event| self.link.respond(sub, event));
+    }
+}
+
+// Hands every subscriber its own copy of `event`.
+fn fan_out<S: Copy>(subscribers: &HashSet<S>, event: BusEvent, mut respond: impl FnMut(S, BusEvent)) {
+    for sub in subscribers.iter() {
+        respond(*sub, event.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn socket_frames_and_chat_events_keep_their_kind() {
+        let frame = BusEvent::from(Request::EventBusMsg("{}".to_string()));
+        assert!(matches!(frame, BusEvent::Socket(s) if s == "{}"));
+
+        let event = BusEvent::from(Request::Publish(ChatEvent::TypingChanged(vec!["alice".to_string()])));
+        assert!(matches!(event, BusEvent::Chat(ChatEvent::TypingChanged(names)) if names == ["alice"]));
+    }
+
+    #[test]
+    fn every_subscriber_gets_the_event() {
+        let subscribers: HashSet<u32> = [1, 2, 3].into_iter().collect();
+        let mut received = Vec::new();
+        fan_out(&subscribers, BusEvent::Socket("hi".to_string()), |sub, event| {
+            if let BusEvent::Socket(s) = event {
+                received.push((sub, s));
+            }
+        });
+        received.sort();
+        assert_eq!(
+            received,
+            [(1, "hi".to_string()), (2, "hi".to_string()), (3, "hi".to_string())]
+        );
+    }
+
+    #[test]
+    fn nobody_subscribed_means_nothing_is_sent() {
+        let mut sent = 0;
+        fan_out(&HashSet::<u32>::new(), BusEvent::Socket("hi".to_string()), |_, _| sent += 1);
+        assert_eq!(sent, 0);
+    }
+}
@@ -0,0 +1,69 @@
+use reqwasm::http::Request;
+use serde::Deserialize;
+
+// Open Graph data returned by the preview proxy. Any field may be missing.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct LinkPreview {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+}
+
+// A preview is only worth a card if it at least has a title.
+pub fn parse_preview(body: &str) -> Option<LinkPreview> {
+    let preview: LinkPreview = serde_json::from_str(body).ok()?;
+    preview.title.as_ref()?;
+    Some(preview)
+}
+
+// Asks the proxy at `endpoint` for the Open Graph data of `url`. Any
+// failure just means no card is shown.
+pub async fn fetch_preview(endpoint: &str, url: &str) -> Option<LinkPreview> {
+    let encoded: String = js_sys::encode_uri_component(url).into();
+    let separator = if endpoint.contains('?') { '&' } else { '?' };
+    let request_url = format!("{}{}url={}", endpoint, separator, encoded);
+
+    let response = match Request::get(&request_url).send().await {
+        Ok(response) => response,
+        Err(e) => {
+            log::debug!("link preview request failed: {:?}", e);
+            return None;
+        }
+    };
+    if !response.ok() {
+        return None;
+    }
+    let body = response.text().await.ok()?;
+    parse_preview(&body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_every_field() {
+        let body = r#"{"title":"Yew","description":"A framework","image":"https://yew.rs/logo.png"}"#;
+        assert_eq!(
+            parse_preview(body),
+            Some(LinkPreview {
+                title: Some("Yew".to_string()),
+                description: Some("A framework".to_string()),
+                image: Some("https://yew.rs/logo.png".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn a_title_is_enough() {
+        let preview = parse_preview(r#"{"title":"Yew","extra":1}"#).unwrap();
+        assert_eq!(preview.description, None);
+        assert_eq!(preview.image, None);
+    }
+
+    #[test]
+    fn no_card_without_a_title_or_for_bad_json() {
+        assert_eq!(parse_preview(r#"{"description":"A framework"}"#), None);
+        assert_eq!(parse_preview("<html>"), None);
+    }
+}
@@ -1,2 +1,12 @@
 pub mod websocket;
-pub mod event_bus;
\ No newline at end of file
+pub mod event_bus;
+pub mod clipboard;
+pub mod download;
+pub mod emoji_support;
+pub mod link_preview;
+pub mod notification;
+pub mod preload;
+pub mod settings;
+pub mod storage;
+pub mod translate;
+pub mod upload;
\ No newline at end of file
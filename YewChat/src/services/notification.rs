@@ -0,0 +1,47 @@
+use web_sys::{HtmlAudioElement, Notification, NotificationOptions, NotificationPermission};
+
+use crate::utils::notify::NotificationSound;
+
+// Asks for permission to show desktop notifications. Browsers only allow
+// this in response to a user gesture, so call it from a click handler.
+pub fn request_permission() {
+    if Notification::permission() == NotificationPermission::Default {
+        if let Err(e) = Notification::request_permission() {
+            log::debug!("notification permission request failed: {:?}", e);
+        }
+    }
+}
+
+// Shows a desktop notification for a message, but only while the tab is in
+// the background and permission has been granted.
+pub fn notify_message(from: &str, text: &str) {
+    let hidden = web_sys::window()
+        .and_then(|w| w.document())
+        .map_or(false, |d| d.hidden());
+    if !hidden || Notification::permission() != NotificationPermission::Granted {
+        return;
+    }
+
+    let mut options = NotificationOptions::new();
+    options.body(text);
+    if let Err(e) = Notification::new_with_options(&format!("{} in YewChat", from), &options) {
+        log::debug!("failed to show notification: {:?}", e);
+    }
+}
+
+// Plays `sound`, loading it when it's needed. Browsers refuse to play audio
+// before the page has had any user interaction, which is only logged.
+pub fn play_sound(sound: NotificationSound) {
+    let url = match sound.asset_url() {
+        Some(url) => url,
+        None => return,
+    };
+    match HtmlAudioElement::new_with_src(url) {
+        Ok(audio) => {
+            if let Err(e) = audio.play() {
+                log::debug!("failed to play {}: {:?}", url, e);
+            }
+        }
+        Err(e) => log::debug!("failed to load {}: {:?}", url, e),
+    }
+}
@@ -0,0 +1,10 @@
+use web_sys::HtmlImageElement;
+
+// Starts downloading an image so it's already cached by the time something
+// renders it.
+pub fn preload_image(url: &str) {
+    match HtmlImageElement::new() {
+        Ok(image) => image.set_src(url),
+        Err(e) => log::debug!("failed to preload {}: {:?}", url, e),
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::services::storage;
+use crate::utils::emoji::SkinTone;
+use crate::utils::grouping::GroupWindow;
+use crate::utils::notify::{NotificationSound, NotifyMode};
+use crate::utils::presence::UserSort;
+use crate::utils::send_key::SendKey;
+use crate::utils::style::{AvatarShape, Density};
+use crate::utils::time::{TimeFormat, TimestampMode};
+
+const SETTINGS_KEY: &str = "yewchat.settings";
+
+// Every user preference, persisted as a single localStorage entry. Fields
+// missing from an older saved blob fall back to their defaults.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub time_format: TimeFormat,
+    pub timestamps: TimestampMode,
+    pub density: Density,
+    pub notify: NotifyMode,
+    pub notification_sound: NotificationSound,
+    pub group_window: GroupWindow,
+    pub skin_tone: SkinTone,
+    pub send_key: SendKey,
+    pub user_sort: UserSort,
+    pub avatar_shape: AvatarShape,
+    // Whether "online for" starts over after a reconnect.
+    pub reset_session_on_reconnect: bool,
+    // Focus mode: the users sidebar is hidden.
+    pub sidebar_hidden: bool,
+    // People whose messages are collapsed and who don't show as typing.
+    pub blocked: HashSet<String>,
+    // Emoji picked most recently, newest first.
+    pub recent_emoji: Vec<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            time_format: TimeFormat::from_locale(),
+            timestamps: TimestampMode::default(),
+            density: Density::default(),
+            notify: NotifyMode::default(),
+            notification_sound: NotificationSound::default(),
+            group_window: GroupWindow::default(),
+            skin_tone: SkinTone::default(),
+            send_key: SendKey::default(),
+            user_sort: UserSort::default(),
+            avatar_shape: AvatarShape::default(),
+            reset_session_on_reconnect: false,
+            sidebar_hidden: false,
+            blocked: HashSet::new(),
+            recent_emoji: Vec::new(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        storage::get(SETTINGS_KEY).unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        if let Err(e) = storage::set(SETTINGS_KEY, self) {
+            log::warn!("settings only kept for this session: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_settings_load_back() {
+        let mut settings = Settings {
+            density: Density::Compact,
+            skin_tone: SkinTone::Dark,
+            ..Settings::default()
+        };
+        settings.blocked.insert("mallory".to_string());
+        settings.recent_emoji = vec!["🔥".to_string()];
+        settings.save();
+        assert_eq!(Settings::load(), settings);
+    }
+
+    #[test]
+    fn missing_fields_fall_back_to_defaults() {
+        let settings: Settings = serde_json::from_str(r#"{"sidebar_hidden":true}"#).unwrap();
+        assert!(settings.sidebar_hidden);
+        assert_eq!(
+            Settings {
+                sidebar_hidden: false,
+                ..settings
+            },
+            Settings::default()
+        );
+    }
+
+    #[test]
+    fn unreadable_settings_load_as_defaults() {
+        storage::set(SETTINGS_KEY, &"not settings").ok();
+        assert_eq!(Settings::load(), Settings::default());
+    }
+}
@@ -0,0 +1,72 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+// Everything written this session, so saved values still apply until the page
+// is closed when localStorage is missing, e.g. disabled or in private mode.
+thread_local! {
+    static MEMORY: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
+}
+
+// Browsers throw on `window.localStorage` when storage is disabled, so this
+// is `None` rather than a panic.
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+// Off the web (e.g. in unit tests) there is only the in-memory copy.
+#[cfg(not(target_arch = "wasm32"))]
+fn local_storage() -> Option<web_sys::Storage> {
+    None
+}
+
+// The value saved under `key`, if there is one and it still parses.
+pub fn get<T: DeserializeOwned>(key: &str) -> Option<T> {
+    let raw = MEMORY
+        .with(|memory| memory.borrow().get(key).cloned())
+        .or_else(|| local_storage()?.get_item(key).ok()?)?;
+    match serde_json::from_str(&raw) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            log::warn!("ignoring unreadable {}: {}", key, e);
+            None
+        }
+    }
+}
+
+// Saves `value` under `key`. It is kept in memory either way; the error says
+// why it won't outlive the page.
+pub fn set<T: Serialize>(key: &str, value: &T) -> Result<(), String> {
+    let raw = serde_json::to_string(value).map_err(|e| e.to_string())?;
+    MEMORY.with(|memory| memory.borrow_mut().insert(key.to_string(), raw.clone()));
+    local_storage()
+        .ok_or_else(|| String::from("localStorage is unavailable"))?
+        .set_item(key, &raw)
+        .map_err(|e| format!("{:?}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_are_kept_in_memory_without_local_storage() {
+        let saved = set("count", &3);
+        assert_eq!(saved, Err(String::from("localStorage is unavailable")));
+        assert_eq!(get::<i32>("count"), Some(3));
+    }
+
+    #[test]
+    fn missing_keys_read_as_none() {
+        assert_eq!(get::<i32>("never-saved"), None);
+    }
+
+    #[test]
+    fn values_that_no_longer_parse_read_as_none() {
+        set("count", &"three").ok();
+        assert_eq!(get::<i32>("count"), None);
+    }
+}
@@ -0,0 +1,113 @@
+use reqwasm::http::Request;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use wasm_bindgen::JsValue;
+
+#[derive(Serialize)]
+struct TranslateRequest<'a> {
+    text: &'a str,
+    target: &'a str,
+}
+
+#[derive(Deserialize)]
+struct Translated {
+    text: String,
+}
+
+// The translated text from the endpoint's `{ "text": "..." }` answer, unless
+// it came back empty.
+pub fn parse_translation(body: &str) -> Option<String> {
+    let translated: Translated = serde_json::from_str(body).ok()?;
+    (!translated.text.trim().is_empty()).then_some(translated.text)
+}
+
+// Finished translations by (text, target language), so text that was
+// translated once isn't sent to the endpoint again, e.g. a repeated message.
+#[derive(Default)]
+pub struct TranslationCache {
+    entries: HashMap<(String, String), String>,
+}
+
+impl TranslationCache {
+    // The cached translation of `text` into `target`. On a miss `fetch` is
+    // asked for one instead, and it's `None` until that gets `insert`ed.
+    pub fn cached_or(&self, text: &str, target: &str, fetch: impl FnOnce(&str, &str)) -> Option<String> {
+        match self.entries.get(&(text.to_string(), target.to_string())) {
+            Some(translated) => Some(translated.clone()),
+            None => {
+                fetch(text, target);
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, text: String, target: String, translated: String) {
+        self.entries.insert((text, target), translated);
+    }
+}
+
+// The browser's preferred language, e.g. "en-US", which translations target.
+pub fn browser_language() -> String {
+    web_sys::window()
+        .and_then(|w| w.navigator().language())
+        .unwrap_or_else(|| String::from("en"))
+}
+
+// POSTs `{ "text", "target" }` to the translation endpoint. `None` on any
+// failure, so the message just stays untranslated.
+pub async fn translate(endpoint: &str, text: &str, target: &str) -> Option<String> {
+    let body = serde_json::to_string(&TranslateRequest { text, target }).ok()?;
+    let response = Request::post(endpoint)
+        .header("Content-Type", "application/json")
+        .body(JsValue::from_str(&body))
+        .send()
+        .await;
+    let response = match response {
+        Ok(response) if response.ok() => response,
+        Ok(response) => {
+            log::debug!("translation rejected with status {}", response.status());
+            return None;
+        }
+        Err(e) => {
+            log::debug!("translation failed: {:?}", e);
+            return None;
+        }
+    };
+    let body = response.text().await.ok()?;
+    parse_translation(&body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_translated_text() {
+        assert_eq!(parse_translation(r#"{"text":"Hallo"}"#).as_deref(), Some("Hallo"));
+        assert_eq!(parse_translation(r#"{"text":"Hallo","source":"en"}"#).as_deref(), Some("Hallo"));
+    }
+
+    #[test]
+    fn empty_or_malformed_answers_are_no_translation() {
+        assert_eq!(parse_translation(r#"{"text":"  "}"#), None);
+        assert_eq!(parse_translation(r#"{"translation":"Hallo"}"#), None);
+        assert_eq!(parse_translation("Internal Server Error"), None);
+    }
+
+    #[test]
+    fn cached_translations_skip_the_endpoint() {
+        let fetches = std::cell::Cell::new(0);
+        let fetch = |_: &str, _: &str| fetches.set(fetches.get() + 1);
+        let mut cache = TranslationCache::default();
+
+        assert_eq!(cache.cached_or("Hello", "de", fetch), None);
+        assert_eq!(fetches.get(), 1);
+        cache.insert(String::from("Hello"), String::from("de"), String::from("Hallo"));
+
+        assert_eq!(cache.cached_or("Hello", "de", fetch).as_deref(), Some("Hallo"));
+        assert_eq!(fetches.get(), 1);
+
+        assert_eq!(cache.cached_or("Hello", "fr", fetch), None);
+        assert_eq!(fetches.get(), 2);
+    }
+}
@@ -0,0 +1,35 @@
+use reqwasm::http::Request;
+use serde::Deserialize;
+use wasm_bindgen::JsValue;
+
+use crate::utils::text::is_safe_url;
+
+#[derive(Deserialize)]
+struct Uploaded {
+    url: String,
+}
+
+// POSTs `file` to the upload endpoint, which answers `{ "url": "..." }` with
+// where the file can now be fetched from. `None` on any failure, or if that
+// URL isn't a plain web link.
+pub async fn upload_file(endpoint: &str, file: web_sys::File) -> Option<String> {
+    let response = Request::post(endpoint)
+        .header("Content-Type", &file.type_())
+        .body(JsValue::from(file))
+        .send()
+        .await;
+    let response = match response {
+        Ok(response) if response.ok() => response,
+        Ok(response) => {
+            log::debug!("upload rejected with status {}", response.status());
+            return None;
+        }
+        Err(e) => {
+            log::debug!("upload failed: {:?}", e);
+            return None;
+        }
+    };
+    let body = response.text().await.ok()?;
+    let uploaded: Uploaded = serde_json::from_str(&body).ok()?;
+    is_safe_url(&uploaded.url).then_some(uploaded.url)
+}
@@ -1,31 +1,181 @@
 use futures::{channel::mpsc::Sender, SinkExt, StreamExt};
-use reqwasm::websocket::{futures::WebSocket, Message};
+use reqwasm::websocket::{futures::WebSocket, Message, WebSocketError};
 use yew_agent::Dispatched;
-use crate::services::event_bus::{EventBus, Request};
+use crate::config::{
+    query_param, DEFAULT_WS_URL, INITIAL_RECONNECT_DELAY_MS, MAX_RECONNECT_DELAY_MS,
+};
+use crate::services::event_bus::{ChatEvent, EventBus, Request};
 
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU32, Ordering};
 use wasm_bindgen_futures::spawn_local;
 
+// Every socket gets its own id so connection events from a socket that has
+// since been replaced can be told apart from the current one.
+static NEXT_CONNECTION_ID: AtomicU32 = AtomicU32::new(1);
+
+thread_local! {
+    // The socket opened last, by id. Only one is meant to be open at a time,
+    // so opening another closes it; a chat that's mounted again can't leave
+    // its old connection registered with the server.
+    static CURRENT: RefCell<Option<(u32, Sender<String>)>> = const { RefCell::new(None) };
+}
+
 pub struct WebsocketService {
     pub tx: Sender<String>,
+    pub id: u32,
+    url: String,
 }
 
-impl WebsocketService {
+// Exponential backoff between reconnect attempts: 1s, 2s, 4s, ... up to 30s.
+pub struct Backoff {
+    attempt: u32,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backoff {
     pub fn new() -> Self {
-        let ws = WebSocket::open("ws://127.0.0.1:8080").unwrap();
+        Self { attempt: 0 }
+    }
+
+    pub fn next_delay(&mut self) -> u32 {
+        let delay = INITIAL_RECONNECT_DELAY_MS
+            .saturating_mul(2u32.saturating_pow(self.attempt))
+            .min(MAX_RECONNECT_DELAY_MS);
+        self.attempt += 1;
+        delay
+    }
+
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+
+    // Reconnects scheduled since the last successful connection.
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    // Whether `max_attempts` reconnects have been tried without success.
+    pub fn exhausted(&self, max_attempts: u32) -> bool {
+        self.attempt >= max_attempts
+    }
+}
+
+// Whole seconds left until `at`, rounded up so the countdown reads "1s"
+// rather than "0s" during its last second. Zero once `at` has passed.
+pub fn seconds_until(now: f64, at: f64) -> u32 {
+    ((at - now) / 1000.0).ceil().max(0.0) as u32
+}
+
+// Human-readable meaning of a WebSocket close code (RFC 6455, section 7.4).
+pub fn close_code_reason(code: u16) -> &'static str {
+    match code {
+        1000 => "Normal closure",
+        1001 => "Server going away",
+        1002 => "Protocol error",
+        1003 => "Unsupported data",
+        1005 => "No status given",
+        1006 => "Connection lost",
+        1007 => "Invalid message data",
+        1008 => "Policy violation",
+        1009 => "Message too big",
+        1010 => "Missing extension",
+        1011 => "Server error",
+        1012 => "Server restarting",
+        1013 => "Try again later",
+        1015 => "TLS handshake failed",
+        _ => "Unknown close code",
+    }
+}
+
+// e.g. "1006 Connection lost" or "1011 Server error: out of memory".
+pub fn describe_close(code: u16, reason: &str) -> String {
+    let mut detail = format!("{} {}", code, close_code_reason(code));
+    if !reason.is_empty() {
+        detail.push_str(": ");
+        detail.push_str(reason);
+    }
+    detail
+}
+
+pub fn is_websocket_url(url: &str) -> bool {
+    url.starts_with("ws://") || url.starts_with("wss://")
+}
+
+// Picks the endpoint to connect to: an explicit URL wins over the `?ws=`
+// query parameter, which wins over the default. Candidates without a
+// ws/wss scheme are skipped.
+pub fn resolve_url(explicit: Option<&str>, query: Option<&str>, default: &str) -> String {
+    [explicit, query]
+        .iter()
+        .flatten()
+        .find(|url| {
+            let valid = is_websocket_url(url);
+            if !valid {
+                log::warn!("ignoring websocket url without ws/wss scheme: {}", url);
+            }
+            valid
+        })
+        .map(|url| url.to_string())
+        .unwrap_or_else(|| default.to_string())
+}
+
+impl WebsocketService {
+    // Opens a socket to the configured endpoint.
+    pub fn new() -> Result<Self, String> {
+        Self::with_url(&Self::configured_url())
+    }
+
+    pub fn configured_url() -> String {
+        let query = query_param("ws");
+        resolve_url(None, query.as_deref(), DEFAULT_WS_URL)
+    }
+
+    // A service with no socket behind it: every send fails until `reconnect`
+    // manages to open one.
+    pub fn offline(url: &str) -> Self {
+        let (mut tx, _) = futures::channel::mpsc::channel::<String>(1);
+        tx.close_channel();
+        Self {
+            tx,
+            id: NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed),
+            url: url.to_string(),
+        }
+    }
+
+    pub fn with_url(url: &str) -> Result<Self, String> {
+        if !is_websocket_url(url) {
+            return Err(format!("websocket url must use ws or wss: {}", url));
+        }
+        let ws = WebSocket::open(url).map_err(|e| format!("failed to open {}: {:?}", url, e))?;
+        let id = NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed);
 
         let (mut write, mut read) = ws.split();
 
         let (in_tx, mut in_rx) = futures::channel::mpsc::channel::<String>(1000);
         let mut event_bus = EventBus::dispatcher();
 
+        make_current(id, in_tx.clone());
+
         spawn_local(async move {
             while let Some(s) = in_rx.next().await {
                 log::debug!("got event from channel! {}", s);
-                write.send(Message::Text(s)).await.unwrap();
+                if let Err(e) = write.send(Message::Text(s)).await {
+                    log::error!("ws send: {:?}", e);
+                    break;
+                }
             }
+            // The channel was closed, i.e. the service was dropped or replaced.
+            write.close().await.ok();
         });
 
         spawn_local(async move {
+            let mut detail = None;
             while let Some(msg) = read.next().await {
                 match msg {
                     Ok(Message::Text(data)) => {
@@ -39,14 +189,165 @@ impl WebsocketService {
                             event_bus.send(Request::EventBusMsg(val.into()));
                         }
                     }
+                    Err(WebSocketError::ConnectionClose(event)) => {
+                        log::debug!("ws closed: {} {}", event.code, event.reason);
+                        detail = Some(describe_close(event.code, &event.reason));
+                    }
                     Err(e) => {
                         log::error!("ws: {:?}", e);
+                        detail = Some(format!("{:?}", e));
                     }
                 }
             }
             log::debug!("WebSocket Closed");
+            event_bus.send(Request::Publish(ChatEvent::ConnectionChanged {
+                connection: id,
+                connected: false,
+                detail,
+            }));
         });
 
-        Self { tx: in_tx }
+        Ok(Self {
+            tx: in_tx,
+            id,
+            url: url.to_string(),
+        })
+    }
+
+    // Closes the socket for good; `reconnect` opens a new one.
+    pub fn close(&mut self) {
+        self.tx.close_channel();
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    // Replaces the socket with a fresh one to the same URL, closing the old.
+    pub fn reconnect(&mut self) -> Result<(), String> {
+        *self = Self::with_url(&self.url)?;
+        Ok(())
+    }
+}
+
+impl Drop for WebsocketService {
+    // Closes the socket rather than waiting for every clone of `tx` to go.
+    fn drop(&mut self) {
+        self.tx.close_channel();
+        forget_current(self.id);
     }
-}
\ No newline at end of file
+}
+
+// Makes connection `id` the current one, closing the one it replaces.
+fn make_current(id: u32, tx: Sender<String>) {
+    let previous = CURRENT.with(|current| current.replace(Some((id, tx))));
+    if let Some((previous_id, mut previous)) = previous {
+        log::debug!("closing connection {} replaced by {}", previous_id, id);
+        previous.close_channel();
+    }
+}
+
+// Forgets connection `id`, unless another has replaced it since.
+fn forget_current(id: u32) {
+    CURRENT.with(|current| {
+        let mut current = current.borrow_mut();
+        if current.as_ref().is_some_and(|(current_id, _)| *current_id == id) {
+            *current = None;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explicit_url_wins_over_the_query() {
+        let url = resolve_url(Some("wss://explicit"), Some("ws://query"), "ws://default");
+        assert_eq!(url, "wss://explicit");
+    }
+
+    #[test]
+    fn query_wins_over_the_default() {
+        assert_eq!(resolve_url(None, Some("ws://query"), "ws://default"), "ws://query");
+        assert_eq!(resolve_url(None, None, "ws://default"), "ws://default");
+    }
+
+    #[test]
+    fn urls_without_a_websocket_scheme_are_skipped() {
+        let url = resolve_url(Some("https://explicit"), Some("ws://query"), "ws://default");
+        assert_eq!(url, "ws://query");
+        assert_eq!(resolve_url(None, Some("javascript:x"), "ws://default"), "ws://default");
+        assert!(WebsocketService::with_url("http://example.com").is_err());
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_limit() {
+        let mut backoff = Backoff::new();
+        let delays: Vec<u32> = (0..7).map(|_| backoff.next_delay()).collect();
+        assert_eq!(delays, [1_000, 2_000, 4_000, 8_000, 16_000, 30_000, 30_000]);
+    }
+
+    #[test]
+    fn forcing_a_reconnect_starts_the_backoff_over() {
+        let mut backoff = Backoff::new();
+        backoff.next_delay();
+        backoff.next_delay();
+        backoff.reset();
+        assert_eq!(backoff.attempt(), 0);
+        assert_eq!(backoff.next_delay(), INITIAL_RECONNECT_DELAY_MS);
+    }
+
+    #[test]
+    fn common_close_codes_have_reasons() {
+        assert_eq!(close_code_reason(1000), "Normal closure");
+        assert_eq!(close_code_reason(1006), "Connection lost");
+        assert_eq!(close_code_reason(1011), "Server error");
+        assert_eq!(close_code_reason(4000), "Unknown close code");
+    }
+
+    #[test]
+    fn close_detail_adds_the_servers_reason() {
+        assert_eq!(describe_close(1006, ""), "1006 Connection lost");
+        assert_eq!(describe_close(1011, "out of memory"), "1011 Server error: out of memory");
+    }
+
+    #[test]
+    fn gives_up_after_the_attempt_cap() {
+        let mut backoff = Backoff::new();
+        for _ in 0..3 {
+            assert!(!backoff.exhausted(3));
+            backoff.next_delay();
+        }
+        assert!(backoff.exhausted(3));
+        backoff.reset();
+        assert!(!backoff.exhausted(3));
+    }
+
+    #[test]
+    fn countdown_rounds_up_to_whole_seconds() {
+        assert_eq!(seconds_until(0.0, 4_000.0), 4);
+        assert_eq!(seconds_until(0.0, 3_001.0), 4);
+        assert_eq!(seconds_until(3_500.0, 4_000.0), 1);
+        assert_eq!(seconds_until(4_000.0, 4_000.0), 0);
+        assert_eq!(seconds_until(9_000.0, 4_000.0), 0);
+    }
+
+    #[test]
+    fn opening_a_connection_closes_the_one_it_replaces() {
+        let (first, _first_rx) = futures::channel::mpsc::channel::<String>(1);
+        let (second, _second_rx) = futures::channel::mpsc::channel::<String>(1);
+        make_current(1, first.clone());
+        assert!(!first.is_closed());
+
+        make_current(2, second.clone());
+        assert!(first.is_closed());
+        assert!(!second.is_closed());
+
+        // The replaced connection going away leaves the new one current.
+        forget_current(1);
+        assert_eq!(CURRENT.with(|current| current.borrow().as_ref().map(|(id, _)| *id)), Some(2));
+        forget_current(2);
+        assert!(CURRENT.with(|current| current.borrow().is_none()));
+    }
+}
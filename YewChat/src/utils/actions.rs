@@ -0,0 +1,101 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MessageAction {
+    React,
+    Copy,
+    Quote,
+    Pin,
+    Report,
+    Block,
+    Translate,
+    CopyLink,
+    Edit,
+    Select,
+}
+
+impl MessageAction {
+    pub fn label(self, pinned: bool) -> &'static str {
+        match self {
+            MessageAction::React => "React",
+            MessageAction::Copy => "Copy text",
+            MessageAction::Quote => "Quote",
+            MessageAction::Pin if pinned => "Unpin",
+            MessageAction::Pin => "Pin",
+            MessageAction::Report => "Report…",
+            MessageAction::Block => "Block sender",
+            MessageAction::Translate => "Translate",
+            MessageAction::CopyLink => "Copy link",
+            MessageAction::Edit => "Edit",
+            MessageAction::Select => "Select",
+        }
+    }
+}
+
+// What the context menu offers for a message. A message still waiting for
+// the server can only be copied; editing is for our own messages, and
+// quoting, reporting and blocking for other people's. Translating also needs
+// an endpoint configured.
+pub fn available_actions(is_own: bool, pending: bool, can_translate: bool) -> Vec<MessageAction> {
+    if pending {
+        return vec![MessageAction::Copy];
+    }
+    let mut actions = vec![
+        MessageAction::React,
+        MessageAction::Copy,
+        MessageAction::CopyLink,
+        MessageAction::Select,
+    ];
+    if is_own {
+        actions.push(MessageAction::Edit);
+    } else {
+        actions.push(MessageAction::Quote);
+        if can_translate {
+            actions.push(MessageAction::Translate);
+        }
+    }
+    actions.push(MessageAction::Pin);
+    if !is_own {
+        actions.push(MessageAction::Report);
+        actions.push(MessageAction::Block);
+    }
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pending_messages_can_only_be_copied() {
+        assert_eq!(available_actions(true, true, true), [MessageAction::Copy]);
+        assert_eq!(available_actions(false, true, true), [MessageAction::Copy]);
+    }
+
+    #[test]
+    fn own_messages_can_be_edited_but_not_reported() {
+        let actions = available_actions(true, false, true);
+        assert!(actions.contains(&MessageAction::Edit));
+        assert!(actions.contains(&MessageAction::Pin));
+        assert!(!actions.contains(&MessageAction::Quote));
+        assert!(!actions.contains(&MessageAction::Translate));
+        assert!(!actions.contains(&MessageAction::Report));
+        assert!(!actions.contains(&MessageAction::Block));
+    }
+
+    #[test]
+    fn other_peoples_messages_can_be_quoted_and_reported() {
+        let actions = available_actions(false, false, false);
+        assert!(actions.contains(&MessageAction::Quote));
+        assert!(actions.contains(&MessageAction::Report));
+        assert!(actions.contains(&MessageAction::Block));
+        assert!(!actions.contains(&MessageAction::Edit));
+        assert!(!actions.contains(&MessageAction::Translate));
+        assert!(available_actions(false, false, true).contains(&MessageAction::Translate));
+    }
+
+    #[test]
+    fn pin_label_follows_the_pin_state() {
+        assert_eq!(MessageAction::Pin.label(false), "Pin");
+        assert_eq!(MessageAction::Pin.label(true), "Unpin");
+        assert_eq!(MessageAction::Copy.label(true), "Copy text");
+    }
+}
@@ -0,0 +1,106 @@
+use serde::{Deserialize, Serialize};
+
+// A file sent along with a message, shown as a chip instead of its raw URL.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub url: String,
+    pub name: String,
+    // In bytes.
+    pub size: f64,
+    pub mime: String,
+}
+
+impl Attachment {
+    pub fn is_image(&self) -> bool {
+        self.mime.starts_with("image/")
+    }
+}
+
+// Icon for a file chip, by broad MIME type.
+pub fn icon_for_mime(mime: &str) -> &'static str {
+    let (kind, subtype) = mime.split_once('/').unwrap_or((mime, ""));
+    match (kind, subtype) {
+        ("image", _) => "🖼️",
+        ("video", _) => "🎬",
+        ("audio", _) => "🎵",
+        ("text", _) => "📄",
+        (_, "pdf") => "📕",
+        (_, "zip") | (_, "gzip") | (_, "x-tar") | (_, "x-7z-compressed") => "🗜️",
+        _ => "📎",
+    }
+}
+
+// "512 B", "1.5 KB", "3.2 MB", using 1024-byte units.
+pub fn format_size(bytes: f64) -> String {
+    const KB: f64 = 1024.0;
+    const MB: f64 = KB * 1024.0;
+    if bytes < KB {
+        format!("{} B", bytes.max(0.0) as u64)
+    } else if bytes < MB {
+        format!("{:.1} KB", bytes / KB)
+    } else {
+        format!("{:.1} MB", bytes / MB)
+    }
+}
+
+// What to do with a file dropped onto the chat.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DropDecision {
+    Upload,
+    TooLarge,
+    Unsupported,
+}
+
+// Only images are sent, since that's all a message can show.
+pub fn classify_drop(mime: &str, size: f64, max_bytes: f64) -> DropDecision {
+    if !mime.starts_with("image/") {
+        DropDecision::Unsupported
+    } else if size > max_bytes {
+        DropDecision::TooLarge
+    } else {
+        DropDecision::Upload
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_images_within_the_limit_are_uploaded() {
+        assert_eq!(classify_drop("image/png", 1_000.0, 2_000.0), DropDecision::Upload);
+        assert_eq!(classify_drop("image/png", 2_000.0, 2_000.0), DropDecision::Upload);
+        assert_eq!(classify_drop("image/png", 2_001.0, 2_000.0), DropDecision::TooLarge);
+        assert_eq!(classify_drop("application/pdf", 10.0, 2_000.0), DropDecision::Unsupported);
+    }
+
+    #[test]
+    fn icons_follow_the_mime_type() {
+        assert_eq!(icon_for_mime("image/jpeg"), "🖼️");
+        assert_eq!(icon_for_mime("audio/ogg"), "🎵");
+        assert_eq!(icon_for_mime("application/pdf"), "📕");
+        assert_eq!(icon_for_mime("application/zip"), "🗜️");
+        assert_eq!(icon_for_mime("application/octet-stream"), "📎");
+        assert_eq!(icon_for_mime("nonsense"), "📎");
+    }
+
+    #[test]
+    fn sizes_use_1024_byte_units() {
+        assert_eq!(format_size(512.0), "512 B");
+        assert_eq!(format_size(1_536.0), "1.5 KB");
+        assert_eq!(format_size(3.2 * 1024.0 * 1024.0), "3.2 MB");
+    }
+
+    #[test]
+    fn only_image_attachments_are_images() {
+        let mut attachment = Attachment {
+            url: String::from("https://example.com/cat.png"),
+            name: String::from("cat.png"),
+            size: 10.0,
+            mime: String::from("image/png"),
+        };
+        assert!(attachment.is_image());
+        attachment.mime = String::from("text/plain");
+        assert!(!attachment.is_image());
+    }
+}
@@ -0,0 +1,101 @@
+pub fn avatar_url(name: &str) -> String {
+    format!(
+        "https://avatars.dicebear.com/api/adventurer-neutral/{}.svg",
+        name
+    )
+}
+
+// Avatars in `new` that weren't in `old`, in order and without repeats.
+pub fn added_avatar_urls(old: &[String], new: &[String]) -> Vec<String> {
+    let mut added: Vec<String> = Vec::new();
+    for url in new {
+        if !old.contains(url) && !added.contains(url) {
+            added.push(url.clone());
+        }
+    }
+    added
+}
+
+// How many avatars of a group fit in a row of `cap`, and the "+N" for the rest.
+#[derive(Debug, PartialEq)]
+pub struct AvatarOverflow {
+    pub shown: usize,
+    pub extra: usize,
+}
+
+pub fn avatar_overflow(count: usize, cap: usize) -> AvatarOverflow {
+    let shown = count.min(cap);
+    AvatarOverflow {
+        shown,
+        extra: count - shown,
+    }
+}
+
+// A user's accent: Tailwind classes for their name and their bubble's edge.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Accent {
+    pub text: &'static str,
+    pub border: &'static str,
+}
+
+pub const ACCENTS: [Accent; 8] = [
+    Accent { text: "text-red-600", border: "border-red-400" },
+    Accent { text: "text-orange-600", border: "border-orange-400" },
+    Accent { text: "text-amber-600", border: "border-amber-400" },
+    Accent { text: "text-green-600", border: "border-green-400" },
+    Accent { text: "text-teal-600", border: "border-teal-400" },
+    Accent { text: "text-blue-600", border: "border-blue-400" },
+    Accent { text: "text-indigo-600", border: "border-indigo-400" },
+    Accent { text: "text-pink-600", border: "border-pink-400" },
+];
+
+// Picks a user's accent from their name, so everyone sees the same colour for
+// the same person. FNV-1a is used rather than `DefaultHasher`, whose output
+// may change between Rust releases.
+pub fn accent_for_name(name: &str) -> Accent {
+    let hash = name.bytes().fold(0x811c_9dc5_u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    });
+    ACCENTS[hash as usize % ACCENTS.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overflow_beyond_the_cap_becomes_a_count() {
+        assert_eq!(avatar_overflow(2, 3), AvatarOverflow { shown: 2, extra: 0 });
+        assert_eq!(avatar_overflow(3, 3), AvatarOverflow { shown: 3, extra: 0 });
+        assert_eq!(avatar_overflow(7, 3), AvatarOverflow { shown: 3, extra: 4 });
+    }
+
+    #[test]
+    fn avatar_url_embeds_the_name() {
+        assert!(avatar_url("alice").ends_with("/alice.svg"));
+    }
+
+    #[test]
+    fn accent_is_stable_per_name() {
+        assert_eq!(accent_for_name("alice"), accent_for_name("alice"));
+        assert!(ACCENTS.contains(&accent_for_name("")));
+        let distinct: Vec<_> = ["alice", "bob", "carol", "dave", "erin", "frank"]
+            .iter()
+            .map(|name| accent_for_name(name).text)
+            .collect();
+        assert!(distinct.iter().any(|text| *text != distinct[0]));
+    }
+
+    #[test]
+    fn only_new_avatars_are_preloaded() {
+        let old = vec![String::from("a"), String::from("b")];
+        let new = vec![
+            String::from("b"),
+            String::from("c"),
+            String::from("c"),
+            String::from("d"),
+        ];
+        assert_eq!(added_avatar_urls(&old, &new), ["c", "d"]);
+        assert!(added_avatar_urls(&new, &old).contains(&String::from("a")));
+    }
+}
@@ -0,0 +1,62 @@
+use serde::Serialize;
+
+// What the diagnostics panel shows, and copies as JSON for bug reports.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostics {
+    pub connection: String,
+    pub reconnect_attempts: u32,
+    pub queued_messages: usize,
+    pub message_count: usize,
+    pub last_error: Option<String>,
+    pub socket_url: String,
+}
+
+impl Diagnostics {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    // Label and value pairs, in display order.
+    pub fn rows(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Connection", self.connection.clone()),
+            ("Reconnect attempts", self.reconnect_attempts.to_string()),
+            ("Queued messages", self.queued_messages.to_string()),
+            ("Messages loaded", self.message_count.to_string()),
+            ("Last error", self.last_error.clone().unwrap_or_else(|| String::from("none"))),
+            ("Socket URL", self.socket_url.clone()),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Diagnostics {
+        Diagnostics {
+            connection: String::from("Connected"),
+            reconnect_attempts: 2,
+            queued_messages: 1,
+            message_count: 40,
+            last_error: None,
+            socket_url: String::from("ws://127.0.0.1:8080"),
+        }
+    }
+
+    #[test]
+    fn rows_fill_in_a_missing_error() {
+        let rows = sample().rows();
+        assert_eq!(rows[0], ("Connection", String::from("Connected")));
+        assert!(rows.contains(&("Last error", String::from("none"))));
+    }
+
+    #[test]
+    fn json_uses_camel_case_keys() {
+        let json: serde_json::Value = serde_json::from_str(&sample().to_json()).unwrap();
+        assert_eq!(json["reconnectAttempts"], 2);
+        assert_eq!(json["socketUrl"], "ws://127.0.0.1:8080");
+        assert!(json["lastError"].is_null());
+    }
+}
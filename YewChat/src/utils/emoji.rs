@@ -0,0 +1,276 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+pub struct Emoji {
+    pub glyph: &'static str,
+    pub shortcode: &'static str,
+}
+
+// Everything offered in the picker, along with the `:shortcode:` that
+// expands to it while typing.
+pub const EMOJIS: &[Emoji] = &[
+    Emoji { glyph: "😀", shortcode: "grinning" },
+    Emoji { glyph: "😄", shortcode: "smile" },
+    Emoji { glyph: "😂", shortcode: "joy" },
+    Emoji { glyph: "🤣", shortcode: "rofl" },
+    Emoji { glyph: "😊", shortcode: "blush" },
+    Emoji { glyph: "😉", shortcode: "wink" },
+    Emoji { glyph: "😍", shortcode: "heart_eyes" },
+    Emoji { glyph: "🥰", shortcode: "smiling_face_with_hearts" },
+    Emoji { glyph: "😎", shortcode: "sunglasses" },
+    Emoji { glyph: "🤔", shortcode: "thinking" },
+    Emoji { glyph: "😢", shortcode: "cry" },
+    Emoji { glyph: "🥳", shortcode: "partying_face" },
+    Emoji { glyph: "👍", shortcode: "thumbsup" },
+    Emoji { glyph: "👏", shortcode: "clap" },
+    Emoji { glyph: "🙏", shortcode: "pray" },
+    Emoji { glyph: "❤️", shortcode: "heart" },
+    Emoji { glyph: "🎉", shortcode: "tada" },
+    Emoji { glyph: "🔥", shortcode: "fire" },
+    Emoji { glyph: "🚀", shortcode: "rocket" },
+    Emoji { glyph: "✅", shortcode: "white_check_mark" },
+];
+
+// How many emoji the picker's "Recent" row keeps.
+pub const RECENT_EMOJI_LIMIT: usize = 8;
+
+// Moves `emoji` to the front of `recent`, dropping any earlier use of it and
+// anything past `cap`.
+pub fn push_recent(recent: &mut Vec<String>, emoji: &str, cap: usize) {
+    recent.retain(|e| e != emoji);
+    recent.insert(0, emoji.to_string());
+    recent.truncate(cap);
+}
+
+// The emoji `supported` says this platform can draw, in order. If that's
+// none of them the check is more likely wrong than the platform, so the whole
+// list is offered instead.
+pub fn supported_emoji(emoji: &'static [Emoji], supported: impl Fn(&str) -> bool) -> Vec<&'static Emoji> {
+    let filtered: Vec<&'static Emoji> = emoji.iter().filter(|e| supported(e.glyph)).collect();
+    if filtered.is_empty() {
+        emoji.iter().collect()
+    } else {
+        filtered
+    }
+}
+
+// Emoji in the list above that take a Fitzpatrick skin-tone modifier.
+const TONABLE: &[&str] = &["👍", "👏", "🙏"];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum SkinTone {
+    #[default]
+    Default,
+    Light,
+    MediumLight,
+    Medium,
+    MediumDark,
+    Dark,
+}
+
+impl SkinTone {
+    pub fn cycled(self) -> Self {
+        match self {
+            SkinTone::Default => SkinTone::Light,
+            SkinTone::Light => SkinTone::MediumLight,
+            SkinTone::MediumLight => SkinTone::Medium,
+            SkinTone::Medium => SkinTone::MediumDark,
+            SkinTone::MediumDark => SkinTone::Dark,
+            SkinTone::Dark => SkinTone::Default,
+        }
+    }
+
+    fn modifier(self) -> Option<char> {
+        match self {
+            SkinTone::Default => None,
+            SkinTone::Light => Some('\u{1F3FB}'),
+            SkinTone::MediumLight => Some('\u{1F3FC}'),
+            SkinTone::Medium => Some('\u{1F3FD}'),
+            SkinTone::MediumDark => Some('\u{1F3FE}'),
+            SkinTone::Dark => Some('\u{1F3FF}'),
+        }
+    }
+}
+
+// `glyph` with `tone` applied if it supports skin tones; anything else is
+// returned unchanged.
+pub fn apply_skin_tone(glyph: &str, tone: SkinTone) -> String {
+    match tone.modifier() {
+        Some(modifier) if TONABLE.contains(&glyph) => format!("{}{}", glyph, modifier),
+        _ => glyph.to_string(),
+    }
+}
+
+pub fn lookup(shortcode: &str) -> Option<&'static str> {
+    EMOJIS
+        .iter()
+        .find(|e| e.shortcode == shortcode)
+        .map(|e| e.glyph)
+}
+
+// Replaces every completed `:name:` token that names a known emoji, in the
+// given skin tone where it has one. Unknown codes and stray colons are left
+// exactly as typed.
+pub fn expand_shortcodes(text: &str, tone: SkinTone) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find(':') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find(':').and_then(|end| lookup(&after[..end]).map(|g| (end, g))) {
+            Some((end, glyph)) => {
+                out.push_str(&apply_skin_tone(glyph, tone));
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push(':');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+// Server-defined emoji: shortcode (without colons) to image URL.
+pub type CustomEmoji = Rc<HashMap<String, String>>;
+
+#[derive(Debug, PartialEq)]
+pub enum EmojiPart<'a> {
+    Text(&'a str),
+    Image { code: &'a str, url: &'a str },
+}
+
+// Splits out `:code:` tokens registered as custom emoji. Unknown codes stay
+// in the surrounding text.
+pub fn split_custom_emoji<'a>(text: &'a str, registry: &'a HashMap<String, String>) -> Vec<EmojiPart<'a>> {
+    let mut parts = Vec::new();
+    let mut text_start = 0;
+    let mut search = 0;
+
+    while let Some(open) = text[search..].find(':').map(|i| search + i) {
+        let after = open + 1;
+        let close = match text[after..].find(':') {
+            Some(i) => after + i,
+            None => break,
+        };
+        match registry.get_key_value(&text[after..close]) {
+            Some((code, url)) => {
+                if text_start < open {
+                    parts.push(EmojiPart::Text(&text[text_start..open]));
+                }
+                parts.push(EmojiPart::Image { code, url });
+                text_start = close + 1;
+                search = close + 1;
+            }
+            // The closing colon may open the next code, as in "a:b:smile:".
+            None => search = close,
+        }
+    }
+    if text_start < text.len() {
+        parts.push(EmojiPart::Text(&text[text_start..]));
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_a_single_shortcode() {
+        assert_eq!(expand_shortcodes("nice :fire:", SkinTone::Default), "nice 🔥");
+    }
+
+    #[test]
+    fn expands_several_shortcodes() {
+        assert_eq!(expand_shortcodes(":tada::rocket: go :smile:", SkinTone::Default), "🎉🚀 go 😄");
+    }
+
+    #[test]
+    fn leaves_unknown_codes_and_stray_colons_alone() {
+        assert_eq!(expand_shortcodes(":nope: at 9:41", SkinTone::Default), ":nope: at 9:41");
+        assert_eq!(expand_shortcodes("a:b:fire:", SkinTone::Default), "a:b🔥");
+        assert_eq!(expand_shortcodes(":fire", SkinTone::Default), ":fire");
+    }
+
+    #[test]
+    fn shortcodes_take_the_skin_tone() {
+        assert_eq!(expand_shortcodes(":thumbsup:", SkinTone::Dark), "👍\u{1F3FF}");
+        assert_eq!(expand_shortcodes(":fire:", SkinTone::Dark), "🔥");
+    }
+
+    #[test]
+    fn skin_tone_only_applies_to_tonable_emoji() {
+        assert_eq!(apply_skin_tone("👏", SkinTone::Light), "👏\u{1F3FB}");
+        assert_eq!(apply_skin_tone("👏", SkinTone::Default), "👏");
+        assert_eq!(apply_skin_tone("❤️", SkinTone::Medium), "❤️");
+    }
+
+    #[test]
+    fn skin_tones_cycle_back_to_default() {
+        let mut tone = SkinTone::Default;
+        for _ in 0..6 {
+            tone = tone.cycled();
+        }
+        assert_eq!(tone, SkinTone::Default);
+    }
+
+    #[test]
+    fn splits_out_registered_custom_emoji() {
+        let registry: HashMap<String, String> =
+            [("party".to_string(), "https://x.org/party.png".to_string())].into_iter().collect();
+        assert_eq!(
+            split_custom_emoji("let's :party: now", &registry),
+            [
+                EmojiPart::Text("let's "),
+                EmojiPart::Image { code: "party", url: "https://x.org/party.png" },
+                EmojiPart::Text(" now"),
+            ]
+        );
+        assert_eq!(
+            split_custom_emoji("a:b:party:", &registry),
+            [
+                EmojiPart::Text("a:b"),
+                EmojiPart::Image { code: "party", url: "https://x.org/party.png" },
+            ]
+        );
+        assert_eq!(split_custom_emoji(":other:", &registry), [EmojiPart::Text(":other:")]);
+    }
+
+    #[test]
+    fn offers_only_supported_emoji() {
+        let supported = supported_emoji(EMOJIS, |glyph| glyph != "🥰");
+        assert_eq!(supported.len(), EMOJIS.len() - 1);
+        assert!(supported.iter().all(|e| e.glyph != "🥰"));
+    }
+
+    #[test]
+    fn offers_everything_when_nothing_looks_supported() {
+        assert_eq!(supported_emoji(EMOJIS, |_| false).len(), EMOJIS.len());
+    }
+
+    #[test]
+    fn recent_emoji_are_newest_first() {
+        let mut recent = Vec::new();
+        push_recent(&mut recent, "🔥", 3);
+        push_recent(&mut recent, "🎉", 3);
+        assert_eq!(recent, ["🎉", "🔥"]);
+    }
+
+    #[test]
+    fn reusing_an_emoji_moves_it_to_the_front() {
+        let mut recent = vec!["🎉".to_string(), "🔥".to_string(), "👍".to_string()];
+        push_recent(&mut recent, "👍", 3);
+        assert_eq!(recent, ["👍", "🎉", "🔥"]);
+    }
+
+    #[test]
+    fn recent_emoji_are_capped() {
+        let mut recent = vec!["🎉".to_string(), "🔥".to_string()];
+        push_recent(&mut recent, "👍", 2);
+        assert_eq!(recent, ["👍", "🎉"]);
+    }
+}
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+
+// How close together consecutive messages from one sender must be to share
+// a single header.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum GroupWindow {
+    Off,
+    #[default]
+    OneMinute,
+    FiveMinutes,
+}
+
+impl GroupWindow {
+    pub fn cycled(self) -> Self {
+        match self {
+            GroupWindow::Off => GroupWindow::OneMinute,
+            GroupWindow::OneMinute => GroupWindow::FiveMinutes,
+            GroupWindow::FiveMinutes => GroupWindow::Off,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            GroupWindow::Off => "Off",
+            GroupWindow::OneMinute => "1 minute",
+            GroupWindow::FiveMinutes => "5 minutes",
+        }
+    }
+
+    fn millis(self) -> Option<f64> {
+        match self {
+            GroupWindow::Off => None,
+            GroupWindow::OneMinute => Some(60_000.0),
+            GroupWindow::FiveMinutes => Some(300_000.0),
+        }
+    }
+}
+
+// Whether a message can drop its header and sit under the previous one:
+// same sender, sent within `window` of it. Never with grouping off.
+pub fn should_group(previous: (&str, f64), current: (&str, f64), window: GroupWindow) -> bool {
+    let (previous_from, previous_time) = previous;
+    let (from, time) = current;
+    match window.millis() {
+        Some(window) => from == previous_from && (0.0..=window).contains(&(time - previous_time)),
+        None => false,
+    }
+}
+
+// Where a message's time shows besides its header, as (tooltip on the body,
+// label in the hover gutter). Grouped messages have no header, so the time
+// moves to both; a hidden header time is still a tooltip away.
+pub fn grouped_times(
+    grouped: bool,
+    header_time_hidden: bool,
+    label: &str,
+    title: &str,
+) -> (Option<String>, Option<String>) {
+    let body_title = (grouped || header_time_hidden).then(|| title.to_string());
+    let gutter_time = grouped.then(|| label.to_string());
+    (body_title, gutter_time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_the_same_sender_within_the_window() {
+        let window = GroupWindow::OneMinute;
+        assert!(should_group(("alice", 0.0), ("alice", 60_000.0), window));
+        assert!(!should_group(("alice", 0.0), ("alice", 60_001.0), window));
+        assert!(!should_group(("alice", 0.0), ("bob", 1_000.0), window));
+        assert!(should_group(("alice", 0.0), ("alice", 200_000.0), GroupWindow::FiveMinutes));
+    }
+
+    #[test]
+    fn never_groups_with_grouping_off_or_out_of_order() {
+        assert!(!should_group(("alice", 0.0), ("alice", 0.0), GroupWindow::Off));
+        assert!(!should_group(("alice", 5_000.0), ("alice", 1_000.0), GroupWindow::OneMinute));
+    }
+
+    #[test]
+    fn cycling_visits_every_window() {
+        let start = GroupWindow::default();
+        assert_eq!(start, GroupWindow::OneMinute);
+        assert_eq!(start.cycled(), GroupWindow::FiveMinutes);
+        assert_eq!(start.cycled().cycled(), GroupWindow::Off);
+        assert_eq!(start.cycled().cycled().cycled(), start);
+    }
+
+    #[test]
+    fn follow_up_messages_carry_their_time_in_a_tooltip_and_the_gutter() {
+        let (label, title) = ("09:41", "3/5/2024 09:41:07");
+        assert_eq!(grouped_times(false, false, label, title), (None, None));
+        assert_eq!(
+            grouped_times(true, false, label, title),
+            (Some(title.to_string()), Some(label.to_string()))
+        );
+        assert_eq!(grouped_times(false, true, label, title), (Some(title.to_string()), None));
+    }
+}
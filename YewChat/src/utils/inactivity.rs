@@ -0,0 +1,107 @@
+// Whether anyone is still at the keyboard, so a tab left open for hours
+// doesn't hold a connection the whole time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Inactivity {
+    Active,
+    // Asked "Still there?" and waiting for an answer.
+    Prompted,
+    // Nobody answered in time; the connection should be closed.
+    TimedOut,
+}
+
+pub struct InactivityTimer {
+    prompt_after_ms: f64,
+    grace_ms: f64,
+    last_activity: f64,
+    prompted_at: f64,
+    state: Inactivity,
+}
+
+impl InactivityTimer {
+    pub fn new(now: f64, prompt_after_ms: u32, grace_ms: u32) -> Self {
+        Self {
+            prompt_after_ms: prompt_after_ms as f64,
+            grace_ms: grace_ms as f64,
+            last_activity: now,
+            prompted_at: now,
+            state: Inactivity::Active,
+        }
+    }
+
+    pub fn state(&self) -> Inactivity {
+        self.state
+    }
+
+    // Input only counts until the prompt is up. From then on it takes an
+    // answer (`stay`), so a stray mouse move doesn't dismiss it unseen.
+    pub fn on_activity(&mut self, now: f64) {
+        if self.state == Inactivity::Active {
+            self.last_activity = now;
+        }
+    }
+
+    // Moves on to prompting, then to timing out. Returns the new state only
+    // when it changes.
+    pub fn tick(&mut self, now: f64) -> Option<Inactivity> {
+        match self.state {
+            Inactivity::Active if now - self.last_activity >= self.prompt_after_ms => {
+                self.state = Inactivity::Prompted;
+                self.prompted_at = now;
+                Some(Inactivity::Prompted)
+            }
+            Inactivity::Prompted if now - self.prompted_at >= self.grace_ms => {
+                self.state = Inactivity::TimedOut;
+                Some(Inactivity::TimedOut)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn stay(&mut self, now: f64) {
+        self.state = Inactivity::Active;
+        self.last_activity = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prompts_then_times_out() {
+        let mut timer = InactivityTimer::new(0.0, 1_000, 500);
+        assert_eq!(timer.tick(999.0), None);
+        assert_eq!(timer.tick(1_000.0), Some(Inactivity::Prompted));
+        assert_eq!(timer.tick(1_200.0), None);
+        assert_eq!(timer.tick(1_500.0), Some(Inactivity::TimedOut));
+        assert_eq!(timer.state(), Inactivity::TimedOut);
+        assert_eq!(timer.tick(10_000.0), None);
+    }
+
+    #[test]
+    fn activity_pushes_the_prompt_back() {
+        let mut timer = InactivityTimer::new(0.0, 1_000, 500);
+        timer.on_activity(800.0);
+        assert_eq!(timer.tick(1_500.0), None);
+        assert_eq!(timer.tick(1_800.0), Some(Inactivity::Prompted));
+    }
+
+    #[test]
+    fn activity_doesnt_dismiss_the_prompt() {
+        let mut timer = InactivityTimer::new(0.0, 1_000, 500);
+        timer.tick(1_000.0);
+        timer.on_activity(1_100.0);
+        assert_eq!(timer.state(), Inactivity::Prompted);
+        assert_eq!(timer.tick(1_500.0), Some(Inactivity::TimedOut));
+    }
+
+    #[test]
+    fn staying_starts_over() {
+        let mut timer = InactivityTimer::new(0.0, 1_000, 500);
+        timer.tick(1_000.0);
+        timer.stay(1_200.0);
+        assert_eq!(timer.state(), Inactivity::Active);
+        assert_eq!(timer.tick(2_100.0), None);
+        assert_eq!(timer.tick(2_200.0), Some(Inactivity::Prompted));
+    }
+}
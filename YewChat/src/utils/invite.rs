@@ -0,0 +1,55 @@
+use js_sys::{decode_uri_component, encode_uri_component};
+
+// Link that opens the app on `base` (the page origin) with the same server
+// and channel, so whoever follows it lands in the same room after logging in.
+pub fn invite_url(base: &str, ws: Option<&str>, channel: Option<&str>) -> String {
+    let params: Vec<String> = [("ws", ws), ("channel", channel)]
+        .iter()
+        .filter_map(|(name, value)| {
+            value.map(|value| format!("{}={}", name, String::from(encode_uri_component(value))))
+        })
+        .collect();
+
+    let base = base.trim_end_matches('/');
+    if params.is_empty() {
+        format!("{}/", base)
+    } else {
+        format!("{}/?{}", base, params.join("&"))
+    }
+}
+
+const MESSAGE_FRAGMENT: &str = "#msg-";
+
+// Link to one message: the current page (`page`, without any fragment) with
+// a `#msg-<id>` fragment that scrolls to it when opened.
+pub fn message_link(page: &str, message_id: &str) -> String {
+    let page = page.split('#').next().unwrap_or(page);
+    format!("{}{}{}", page, MESSAGE_FRAGMENT, String::from(encode_uri_component(message_id)))
+}
+
+// The message id a location hash such as `#msg-abc` links to.
+pub fn parse_message_fragment(hash: &str) -> Option<String> {
+    let id = hash.strip_prefix(MESSAGE_FRAGMENT)?;
+    let id: String = decode_uri_component(id).ok()?.into();
+    (!id.is_empty()).then_some(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Parameters go through `encodeURIComponent`, which needs a browser, so
+    // only the bare link is covered here.
+    #[test]
+    fn bare_invite_link_is_the_origin() {
+        assert_eq!(invite_url("https://chat.example", None, None), "https://chat.example/");
+        assert_eq!(invite_url("https://chat.example/", None, None), "https://chat.example/");
+    }
+
+    #[test]
+    fn other_fragments_arent_message_links() {
+        assert_eq!(parse_message_fragment(""), None);
+        assert_eq!(parse_message_fragment("#top"), None);
+        assert_eq!(parse_message_fragment("msg-abc"), None);
+    }
+}
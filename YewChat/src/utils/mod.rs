@@ -0,0 +1,25 @@
+pub mod actions;
+pub mod attachment;
+pub mod avatar;
+pub mod diagnostics;
+pub mod emoji;
+pub mod grouping;
+pub mod inactivity;
+pub mod invite;
+pub mod notify;
+pub mod outbox;
+pub mod pane;
+pub mod pins;
+pub mod poll;
+pub mod presence;
+pub mod reactions;
+pub mod receipts;
+pub mod report;
+pub mod scroll;
+pub mod send_key;
+pub mod style;
+pub mod text;
+pub mod time;
+pub mod toast;
+pub mod typing;
+pub mod unread;
\ No newline at end of file
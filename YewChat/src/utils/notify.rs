@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum NotifyMode {
+    All,
+    #[default]
+    Mentions,
+    Off,
+}
+
+impl NotifyMode {
+    pub fn cycled(self) -> Self {
+        match self {
+            NotifyMode::All => NotifyMode::Mentions,
+            NotifyMode::Mentions => NotifyMode::Off,
+            NotifyMode::Off => NotifyMode::All,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            NotifyMode::All => "All messages",
+            NotifyMode::Mentions => "Mentions only",
+            NotifyMode::Off => "None",
+        }
+    }
+}
+
+// What plays when a message notifies. The sounds ship in `static/sounds`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum NotificationSound {
+    #[default]
+    None,
+    Chime,
+    Pop,
+    Ding,
+}
+
+impl NotificationSound {
+    pub fn cycled(self) -> Self {
+        match self {
+            NotificationSound::None => NotificationSound::Chime,
+            NotificationSound::Chime => NotificationSound::Pop,
+            NotificationSound::Pop => NotificationSound::Ding,
+            NotificationSound::Ding => NotificationSound::None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            NotificationSound::None => "None",
+            NotificationSound::Chime => "Chime",
+            NotificationSound::Pop => "Pop",
+            NotificationSound::Ding => "Ding",
+        }
+    }
+
+    // Where the sound is served from, relative to the page.
+    pub fn asset_url(self) -> Option<&'static str> {
+        match self {
+            NotificationSound::None => None,
+            NotificationSound::Chime => Some("sounds/chime.wav"),
+            NotificationSound::Pop => Some("sounds/pop.wav"),
+            NotificationSound::Ding => Some("sounds/ding.wav"),
+        }
+    }
+}
+
+// Whether `text` contains `@username` as a whole word, ignoring case.
+pub fn mentions(text: &str, username: &str) -> bool {
+    if username.is_empty() {
+        return false;
+    }
+    let haystack = text.to_lowercase();
+    let needle = format!("@{}", username.to_lowercase());
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+
+    haystack.match_indices(&needle).any(|(start, _)| {
+        let before = haystack[..start].chars().next_back();
+        let after = haystack[start + needle.len()..].chars().next();
+        !before.is_some_and(is_word) && !after.is_some_and(is_word)
+    })
+}
+
+pub fn should_notify(mode: NotifyMode, text: &str, username: &str, is_own: bool) -> bool {
+    if is_own {
+        return false;
+    }
+    match mode {
+        NotifyMode::All => true,
+        NotifyMode::Mentions => mentions(text, username),
+        NotifyMode::Off => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mentions_match_whole_names_ignoring_case() {
+        assert!(mentions("hey @Alice, lunch?", "alice"));
+        assert!(mentions("@alice", "alice"));
+        assert!(!mentions("hey @alicia", "alice"));
+        assert!(!mentions("mail bob@alice.com", "alice"));
+        assert!(!mentions("hey alice", "alice"));
+        assert!(!mentions("hey @", ""));
+    }
+
+    #[test]
+    fn notify_mode_decides_what_notifies() {
+        assert!(should_notify(NotifyMode::All, "hi", "alice", false));
+        assert!(!should_notify(NotifyMode::Mentions, "hi", "alice", false));
+        assert!(should_notify(NotifyMode::Mentions, "hi @alice", "alice", false));
+        assert!(!should_notify(NotifyMode::Off, "hi @alice", "alice", false));
+        assert!(!should_notify(NotifyMode::All, "hi @alice", "alice", true));
+    }
+
+    #[test]
+    fn notify_mode_cycles_through_all_three() {
+        let start = NotifyMode::default();
+        assert_eq!(start, NotifyMode::Mentions);
+        assert_eq!(start.cycled().cycled().cycled(), start);
+    }
+
+    #[test]
+    fn every_sound_but_none_has_an_asset() {
+        let mut sound = NotificationSound::default();
+        assert_eq!(sound.asset_url(), None);
+        for _ in 0..3 {
+            sound = sound.cycled();
+            assert!(sound.asset_url().unwrap().starts_with("sounds/"));
+        }
+        assert_eq!(sound.cycled(), NotificationSound::None);
+    }
+}
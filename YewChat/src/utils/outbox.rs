@@ -0,0 +1,84 @@
+// Messages written while the connection was down, waiting to be sent once
+// it's back. Kept in the order they were written and unique by id.
+#[derive(Debug)]
+pub struct Outbox<T> {
+    queued: Vec<(String, T)>,
+}
+
+impl<T> Default for Outbox<T> {
+    fn default() -> Self {
+        Self { queued: vec![] }
+    }
+}
+
+impl<T> Outbox<T> {
+    // Returns false if `id` is already queued.
+    pub fn push(&mut self, id: String, item: T) -> bool {
+        if self.queued.iter().any(|(queued, _)| *queued == id) {
+            return false;
+        }
+        self.queued.push((id, item));
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.queued.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queued.is_empty()
+    }
+
+    // Everything queued, oldest first, leaving the outbox empty.
+    pub fn drain(&mut self) -> Vec<(String, T)> {
+        std::mem::take(&mut self.queued)
+    }
+
+    // Hands everything queued to `send`, oldest first, and returns the ids it
+    // took. Whatever it refuses stays queued, still in order, for the next
+    // flush.
+    pub fn flush(&mut self, mut send: impl FnMut(&str, &T) -> bool) -> Vec<String> {
+        let mut sent = vec![];
+        for (id, item) in self.drain() {
+            if send(&id, &item) {
+                sent.push(id);
+            } else {
+                self.queued.push((id, item));
+            }
+        }
+        sent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drains_in_order_without_duplicates() {
+        let mut outbox = Outbox::default();
+        assert!(outbox.push(String::from("a"), 1));
+        assert!(outbox.push(String::from("b"), 2));
+        assert!(!outbox.push(String::from("a"), 3));
+        assert_eq!(outbox.len(), 2);
+        assert_eq!(outbox.drain(), [(String::from("a"), 1), (String::from("b"), 2)]);
+        assert!(outbox.is_empty());
+    }
+
+    #[test]
+    fn refused_items_stay_queued_in_order() {
+        let mut outbox = Outbox::default();
+        outbox.push(String::from("a"), 1);
+        outbox.push(String::from("b"), 2);
+        outbox.push(String::from("c"), 3);
+
+        let mut offered = vec![];
+        let sent = outbox.flush(|id, item| {
+            offered.push(id.to_string());
+            *item != 2
+        });
+        assert_eq!(offered, ["a", "b", "c"]);
+        assert_eq!(sent, ["a", "c"]);
+        assert_eq!(outbox.drain(), [(String::from("b"), 2)]);
+    }
+}
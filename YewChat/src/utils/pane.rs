@@ -0,0 +1,29 @@
+// What a list pane should show. Panes stay blank while loading so the empty
+// state doesn't flash up before the first data has had a chance to arrive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PaneState {
+    Loading,
+    Empty,
+    Populated,
+}
+
+pub fn pane_state(loaded: bool, len: usize) -> PaneState {
+    match (loaded, len) {
+        (_, n) if n > 0 => PaneState::Populated,
+        (false, _) => PaneState::Loading,
+        (true, _) => PaneState::Empty,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stays_blank_until_loaded() {
+        assert_eq!(pane_state(false, 0), PaneState::Loading);
+        assert_eq!(pane_state(true, 0), PaneState::Empty);
+        assert_eq!(pane_state(false, 3), PaneState::Populated);
+        assert_eq!(pane_state(true, 3), PaneState::Populated);
+    }
+}
@@ -0,0 +1,52 @@
+// Pins or unpins `message_id`, keeping pins in the order they were made.
+// Returns whether the list changed.
+pub fn set_pinned(pinned_ids: &mut Vec<String>, message_id: &str, pinned: bool) -> bool {
+    let index = pinned_ids.iter().position(|id| id == message_id);
+    match (index, pinned) {
+        (None, true) => {
+            pinned_ids.push(message_id.to_string());
+            true
+        }
+        (Some(i), false) => {
+            pinned_ids.remove(i);
+            true
+        }
+        _ => false,
+    }
+}
+
+// Looks the pinned ids up with `find`, newest pin first. Pins whose message
+// isn't loaded are skipped.
+pub fn resolve_pins<'a, T, F>(pinned_ids: &'a [String], find: F) -> Vec<&'a T>
+where
+    T: 'a,
+    F: Fn(&str) -> Option<&'a T>,
+{
+    pinned_ids.iter().rev().filter_map(|id| find(id)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pinning_twice_or_unpinning_nothing_changes_nothing() {
+        let mut pins = Vec::new();
+        assert!(set_pinned(&mut pins, "a", true));
+        assert!(!set_pinned(&mut pins, "a", true));
+        assert!(set_pinned(&mut pins, "b", true));
+        assert!(!set_pinned(&mut pins, "c", false));
+        assert!(set_pinned(&mut pins, "a", false));
+        assert_eq!(pins, ["b"]);
+    }
+
+    #[test]
+    fn resolves_newest_first_skipping_unloaded() {
+        let loaded = [("a", 1), ("c", 3)];
+        let pins = vec![String::from("a"), String::from("b"), String::from("c")];
+        let resolved = resolve_pins(&pins, |id| {
+            loaded.iter().find(|(loaded_id, _)| *loaded_id == id).map(|(_, n)| n)
+        });
+        assert_eq!(resolved, [&3, &1]);
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Poll {
+    pub question: String,
+    pub options: Vec<String>,
+}
+
+// Parses `/poll "Question" "Option A" "Option B" ...`. Needs a question and
+// at least two options; anything else isn't a poll.
+pub fn parse_poll_command(text: &str) -> Option<Poll> {
+    let rest = text.trim().strip_prefix("/poll")?;
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let mut parts = Vec::new();
+    let mut rest = rest.trim_start();
+    while !rest.is_empty() {
+        let quoted = rest.strip_prefix('"')?;
+        let end = quoted.find('"')?;
+        let part = quoted[..end].trim();
+        if part.is_empty() {
+            return None;
+        }
+        parts.push(part.to_string());
+        rest = quoted[end + 1..].trim_start();
+    }
+
+    if parts.len() < 3 {
+        return None;
+    }
+    let question = parts.remove(0);
+    Some(Poll {
+        question,
+        options: parts,
+    })
+}
+
+// Each user's current vote on one poll. Voting again moves the vote.
+#[derive(Debug, Default)]
+pub struct PollTally {
+    votes: HashMap<String, usize>,
+}
+
+impl PollTally {
+    // Returns whether the tally changed.
+    pub fn vote(&mut self, user: &str, option: usize) -> bool {
+        self.votes.insert(user.to_string(), option) != Some(option)
+    }
+
+    pub fn vote_of(&self, user: &str) -> Option<usize> {
+        self.votes.get(user).copied()
+    }
+
+    // Votes per option. Votes for options the poll doesn't have are ignored.
+    pub fn counts(&self, options: usize) -> Vec<usize> {
+        let mut counts = vec![0; options];
+        for option in self.votes.values() {
+            if let Some(count) = counts.get_mut(*option) {
+                *count += 1;
+            }
+        }
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_question_and_options() {
+        let poll = parse_poll_command(r#"/poll "Lunch?" "Pizza" "Sushi""#).unwrap();
+        assert_eq!(poll.question, "Lunch?");
+        assert_eq!(poll.options, ["Pizza", "Sushi"]);
+    }
+
+    #[test]
+    fn trims_whitespace_inside_and_between_parts() {
+        let poll = parse_poll_command(r#"  /poll   " Lunch? "  "Pizza"   " Sushi " "#).unwrap();
+        assert_eq!(poll.question, "Lunch?");
+        assert_eq!(poll.options, ["Pizza", "Sushi"]);
+    }
+
+    #[test]
+    fn rejects_anything_that_isnt_a_full_poll() {
+        assert_eq!(parse_poll_command(r#"/poll "Lunch?" "Pizza""#), None);
+        assert_eq!(parse_poll_command(r#"/poll "Lunch?" "Pizza" """#), None);
+        assert_eq!(parse_poll_command(r#"/poll "Lunch?" "Pizza" "Sushi"#), None);
+        assert_eq!(parse_poll_command(r#"/poll Lunch? Pizza Sushi"#), None);
+        assert_eq!(parse_poll_command(r#"/polls "Lunch?" "Pizza" "Sushi""#), None);
+        assert_eq!(parse_poll_command("hello"), None);
+    }
+
+    #[test]
+    fn counts_one_vote_per_user() {
+        let mut tally = PollTally::default();
+        assert!(tally.vote("alice", 0));
+        assert!(tally.vote("bob", 1));
+        assert!(tally.vote("carol", 1));
+        assert_eq!(tally.counts(3), [1, 2, 0]);
+    }
+
+    #[test]
+    fn voting_again_moves_the_vote() {
+        let mut tally = PollTally::default();
+        tally.vote("alice", 0);
+        assert!(!tally.vote("alice", 0));
+        assert!(tally.vote("alice", 1));
+        assert_eq!(tally.vote_of("alice"), Some(1));
+        assert_eq!(tally.vote_of("bob"), None);
+        assert_eq!(tally.counts(2), [0, 1]);
+    }
+
+    #[test]
+    fn ignores_votes_for_missing_options() {
+        let mut tally = PollTally::default();
+        tally.vote("alice", 5);
+        assert_eq!(tally.counts(2), [0, 0]);
+    }
+}
@@ -0,0 +1,173 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PresenceStatus {
+    Online,
+    Away,
+}
+
+impl PresenceStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            PresenceStatus::Online => "Online",
+            PresenceStatus::Away => "Away",
+        }
+    }
+}
+
+// How the sidebar orders people. Both sort by name; online-first also puts
+// everyone who is away below those who aren't.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum UserSort {
+    #[default]
+    OnlineFirst,
+    Alphabetical,
+}
+
+impl UserSort {
+    pub fn toggled(self) -> Self {
+        match self {
+            UserSort::OnlineFirst => UserSort::Alphabetical,
+            UserSort::Alphabetical => UserSort::OnlineFirst,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            UserSort::OnlineFirst => "Online first",
+            UserSort::Alphabetical => "A–Z",
+        }
+    }
+}
+
+// The sidebar's order, by name ignoring case. Online-first also moves anyone
+// away below everyone online. Ids break ties so equal names keep their place.
+// `id_and_name` reads both off a user.
+pub fn sort_users<T: Clone>(
+    users: &[T],
+    mode: UserSort,
+    id_and_name: impl Fn(&T) -> (&str, &str),
+    status: impl Fn(&str) -> PresenceStatus,
+) -> Vec<T> {
+    let mut sorted = users.to_vec();
+    sorted.sort_by_cached_key(|u| {
+        let (id, name) = id_and_name(u);
+        let away = mode == UserSort::OnlineFirst && status(id) != PresenceStatus::Online;
+        (away, name.to_lowercase(), id.to_string())
+    });
+    sorted
+}
+
+// Sidebar headline: "5 online", or "5 / 20 online" when the server
+// advertises how many the room holds.
+pub fn occupancy_label(online: usize, capacity: Option<u32>) -> String {
+    match capacity {
+        Some(capacity) => format!("{} / {} online", online, capacity),
+        None => format!("{} online", online),
+    }
+}
+
+// Tracks the local user's activity. Both methods return the new status only
+// when it actually changes, i.e. when a presence frame should go out.
+pub struct IdleTracker {
+    idle_after_ms: f64,
+    last_activity: f64,
+    status: PresenceStatus,
+}
+
+impl IdleTracker {
+    pub fn new(now: f64, idle_after_ms: u32) -> Self {
+        Self {
+            idle_after_ms: idle_after_ms as f64,
+            last_activity: now,
+            status: PresenceStatus::Online,
+        }
+    }
+
+    pub fn status(&self) -> PresenceStatus {
+        self.status
+    }
+
+    pub fn on_activity(&mut self, now: f64) -> Option<PresenceStatus> {
+        self.last_activity = now;
+        self.transition(PresenceStatus::Online)
+    }
+
+    pub fn tick(&mut self, now: f64) -> Option<PresenceStatus> {
+        if now - self.last_activity >= self.idle_after_ms {
+            self.transition(PresenceStatus::Away)
+        } else {
+            None
+        }
+    }
+
+    fn transition(&mut self, status: PresenceStatus) -> Option<PresenceStatus> {
+        if self.status == status {
+            return None;
+        }
+        self.status = status;
+        Some(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn goes_away_once_then_back_on_activity() {
+        let mut tracker = IdleTracker::new(0.0, 1_000);
+        assert_eq!(tracker.tick(999.0), None);
+        assert_eq!(tracker.tick(1_000.0), Some(PresenceStatus::Away));
+        assert_eq!(tracker.tick(2_000.0), None);
+        assert_eq!(tracker.on_activity(2_100.0), Some(PresenceStatus::Online));
+        assert_eq!(tracker.on_activity(2_200.0), None);
+        assert_eq!(tracker.status(), PresenceStatus::Online);
+    }
+
+    #[test]
+    fn presence_is_sent_in_lowercase() {
+        assert_eq!(serde_json::to_string(&PresenceStatus::Away).unwrap(), "\"away\"");
+    }
+
+    #[test]
+    fn occupancy_shows_capacity_when_known() {
+        assert_eq!(occupancy_label(5, None), "5 online");
+        assert_eq!(occupancy_label(5, Some(20)), "5 / 20 online");
+    }
+
+    #[test]
+    fn sort_defaults_to_online_first_and_toggles() {
+        assert_eq!(UserSort::default(), UserSort::OnlineFirst);
+        assert_eq!(UserSort::OnlineFirst.toggled(), UserSort::Alphabetical);
+        assert_eq!(UserSort::Alphabetical.toggled(), UserSort::OnlineFirst);
+    }
+
+    fn sorted(users: &[(&'static str, &'static str)], mode: UserSort) -> Vec<&'static str> {
+        let away = ["bob"];
+        sort_users(users, mode, |(id, name)| (*id, *name), |id| {
+            if away.contains(&id) {
+                PresenceStatus::Away
+            } else {
+                PresenceStatus::Online
+            }
+        })
+        .iter()
+        .map(|(id, _)| *id)
+        .collect()
+    }
+
+    #[test]
+    fn online_first_puts_away_users_last() {
+        let users = [("bob", "Bob"), ("carol", "carol"), ("alice", "Alice")];
+        assert_eq!(sorted(&users, UserSort::OnlineFirst), ["alice", "carol", "bob"]);
+        assert_eq!(sorted(&users, UserSort::Alphabetical), ["alice", "bob", "carol"]);
+    }
+
+    #[test]
+    fn equal_names_are_ordered_by_id() {
+        let users = [("sam-2", "Sam"), ("sam-1", "sam"), ("amy", "Amy")];
+        assert_eq!(sorted(&users, UserSort::OnlineFirst), ["amy", "sam-1", "sam-2"]);
+    }
+}
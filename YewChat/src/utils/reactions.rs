@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+// Emojis offered in the quick-reaction bar shown when hovering a message.
+pub const QUICK_REACTIONS: [&str; 5] = ["👍", "❤️", "😂", "🎉", "😮"];
+
+// Moves the highlighted quick reaction by `delta`, wrapping at both ends.
+pub fn quick_react_step(current: usize, delta: isize, len: usize) -> usize {
+    if len == 0 {
+        return 0;
+    }
+    (current as isize + delta).rem_euclid(len as isize) as usize
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reaction {
+    pub emoji: String,
+    // Who reacted, in the order they did.
+    pub users: Vec<String>,
+}
+
+// Reactions per message id. Emojis keep the order they were first used in.
+#[derive(Debug, Default)]
+pub struct Reactions {
+    by_message: HashMap<String, Vec<Reaction>>,
+}
+
+impl Reactions {
+    // Adds or removes `user`'s `emoji` reaction on a message. Returns whether
+    // anything changed.
+    pub fn apply(&mut self, message_id: &str, emoji: &str, user: &str, added: bool) -> bool {
+        let reactions = self.by_message.entry(message_id.to_string()).or_default();
+        let index = reactions.iter().position(|r| r.emoji == emoji);
+
+        let changed = match (index, added) {
+            (Some(i), true) => {
+                let users = &mut reactions[i].users;
+                if users.iter().any(|u| u == user) {
+                    false
+                } else {
+                    users.push(user.to_string());
+                    true
+                }
+            }
+            (None, true) => {
+                reactions.push(Reaction {
+                    emoji: emoji.to_string(),
+                    users: vec![user.to_string()],
+                });
+                true
+            }
+            (Some(i), false) => {
+                let users = &mut reactions[i].users;
+                let before = users.len();
+                users.retain(|u| u != user);
+                let removed = users.len() != before;
+                if users.is_empty() {
+                    reactions.remove(i);
+                }
+                removed
+            }
+            (None, false) => false,
+        };
+
+        if reactions.is_empty() {
+            self.by_message.remove(message_id);
+        }
+        changed
+    }
+
+    pub fn has_reacted(&self, message_id: &str, emoji: &str, user: &str) -> bool {
+        self.for_message(message_id)
+            .iter()
+            .any(|r| r.emoji == emoji && r.users.iter().any(|u| u == user))
+    }
+
+    pub fn for_message(&self, message_id: &str) -> &[Reaction] {
+        self.by_message
+            .get(message_id)
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    pub fn clear(&mut self) {
+        self.by_message.clear();
+    }
+}
+
+// "alice, bob and 3 more" style list of who used a reaction, naming at most
+// `cap` users.
+pub fn reactors_summary(users: &[String], cap: usize) -> String {
+    let shown = users.len().min(cap);
+    let names = users[..shown].join(", ");
+    match users.len() - shown {
+        0 => names,
+        extra if shown == 0 => format!("{} people", extra),
+        extra => format!("{} and {} more", names, extra),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn summary_names_everyone_up_to_the_cap() {
+        assert_eq!(reactors_summary(&names(&["alice"]), 3), "alice");
+        assert_eq!(reactors_summary(&names(&["alice", "bob", "carol"]), 3), "alice, bob, carol");
+    }
+
+    #[test]
+    fn summary_counts_the_rest_beyond_the_cap() {
+        let users = names(&["alice", "bob", "carol", "dave", "erin"]);
+        assert_eq!(reactors_summary(&users, 2), "alice, bob and 3 more");
+        assert_eq!(reactors_summary(&users, 0), "5 people");
+    }
+
+    #[test]
+    fn reacting_twice_with_the_same_emoji_counts_once() {
+        let mut reactions = Reactions::default();
+        assert!(reactions.apply("m1", "👍", "alice", true));
+        assert!(!reactions.apply("m1", "👍", "alice", true));
+        assert!(reactions.apply("m1", "👍", "bob", true));
+        assert_eq!(reactions.for_message("m1")[0].users, ["alice", "bob"]);
+        assert!(reactions.has_reacted("m1", "👍", "bob"));
+    }
+
+    #[test]
+    fn removing_the_last_reactor_drops_the_reaction() {
+        let mut reactions = Reactions::default();
+        reactions.apply("m1", "🎉", "alice", true);
+        assert!(!reactions.apply("m1", "🎉", "bob", false));
+        assert!(reactions.apply("m1", "🎉", "alice", false));
+        assert!(reactions.for_message("m1").is_empty());
+    }
+
+    #[test]
+    fn quick_react_wraps_at_both_ends() {
+        assert_eq!(quick_react_step(4, 1, 5), 0);
+        assert_eq!(quick_react_step(0, -1, 5), 4);
+        assert_eq!(quick_react_step(2, 1, 0), 0);
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+// The furthest message each user has read, by message id.
+#[derive(Debug, Default)]
+pub struct ReadReceipts {
+    latest: HashMap<String, String>,
+}
+
+impl ReadReceipts {
+    // Records that `reader` has seen `message_id`. Receipts only move forward:
+    // `position` gives a message's place in the conversation, and a receipt
+    // for an earlier message than the one already recorded is ignored.
+    // Returns whether anything changed.
+    pub fn record<P>(&mut self, reader: &str, message_id: &str, position: P) -> bool
+    where
+        P: Fn(&str) -> Option<usize>,
+    {
+        if let Some(current) = self.latest.get(reader) {
+            if current == message_id {
+                return false;
+            }
+            if let (Some(current), Some(new)) = (position(current), position(message_id)) {
+                if new < current {
+                    return false;
+                }
+            }
+        }
+        self.latest.insert(reader.to_string(), message_id.to_string());
+        true
+    }
+
+    // Whether `reader` has read `message_id` or anything after it.
+    pub fn has_read<P>(&self, reader: &str, message_id: &str, position: P) -> bool
+    where
+        P: Fn(&str) -> Option<usize>,
+    {
+        match self.latest.get(reader) {
+            Some(latest) if latest == message_id => true,
+            Some(latest) => match (position(latest), position(message_id)) {
+                (Some(latest), Some(target)) => latest >= target,
+                _ => false,
+            },
+            None => false,
+        }
+    }
+
+    // Users whose latest read message is `message_id`, sorted by name.
+    pub fn readers_of(&self, message_id: &str) -> Vec<&str> {
+        let mut readers: Vec<&str> = self
+            .latest
+            .iter()
+            .filter(|(_, id)| id.as_str() == message_id)
+            .map(|(reader, _)| reader.as_str())
+            .collect();
+        readers.sort_unstable();
+        readers
+    }
+
+    pub fn clear(&mut self) {
+        self.latest.clear();
+    }
+}
+
+// Status line for the latest own message: "Seen by everyone" once all
+// `online` other users have read it, "Seen by N" before that, and nothing
+// while nobody has.
+pub fn seen_summary(readers: usize, online: usize) -> Option<String> {
+    match readers {
+        0 => None,
+        n if n >= online => Some("Seen by everyone".to_string()),
+        n => Some(format!("Seen by {}", n)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(id: &str) -> Option<usize> {
+        ["m1", "m2", "m3"].iter().position(|m| *m == id)
+    }
+
+    #[test]
+    fn receipts_only_move_forward() {
+        let mut receipts = ReadReceipts::default();
+        assert!(receipts.record("bob", "m2", position));
+        assert!(!receipts.record("bob", "m2", position));
+        assert!(!receipts.record("bob", "m1", position));
+        assert!(receipts.record("bob", "m3", position));
+        assert_eq!(receipts.readers_of("m3"), ["bob"]);
+        assert!(receipts.readers_of("m2").is_empty());
+    }
+
+    #[test]
+    fn readers_are_sorted_by_name() {
+        let mut receipts = ReadReceipts::default();
+        receipts.record("carol", "m1", position);
+        receipts.record("alice", "m1", position);
+        assert_eq!(receipts.readers_of("m1"), ["alice", "carol"]);
+        receipts.clear();
+        assert!(receipts.readers_of("m1").is_empty());
+    }
+
+    #[test]
+    fn reading_later_messages_counts_as_reading_earlier_ones() {
+        let mut receipts = ReadReceipts::default();
+        receipts.record("bob", "m3", position);
+        assert!(receipts.has_read("bob", "m1", position));
+        assert!(receipts.has_read("bob", "m3", position));
+        assert!(!receipts.has_read("carol", "m1", position));
+        receipts.record("carol", "m1", position);
+        assert!(!receipts.has_read("carol", "m2", position));
+    }
+
+    #[test]
+    fn summary_counts_readers_until_everyone_has_seen() {
+        assert_eq!(seen_summary(0, 3), None);
+        assert_eq!(seen_summary(2, 3).as_deref(), Some("Seen by 2"));
+        assert_eq!(seen_summary(3, 3).as_deref(), Some("Seen by everyone"));
+    }
+}
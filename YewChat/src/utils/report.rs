@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+
+// Why a message is being reported, as offered in the report dialog.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportReason {
+    Spam,
+    Abuse,
+    Other,
+}
+
+impl ReportReason {
+    pub const ALL: [ReportReason; 3] = [ReportReason::Spam, ReportReason::Abuse, ReportReason::Other];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ReportReason::Spam => "Spam",
+            ReportReason::Abuse => "Abuse or harassment",
+            ReportReason::Other => "Something else",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reasons_are_sent_in_lowercase() {
+        let sent: Vec<String> = ReportReason::ALL
+            .iter()
+            .map(|reason| serde_json::to_string(reason).unwrap())
+            .collect();
+        assert_eq!(sent, ["\"spam\"", "\"abuse\"", "\"other\""]);
+    }
+}
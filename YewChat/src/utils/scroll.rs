@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use super::unread::ConversationId;
+
+// How close to the end of the list still counts as being at the bottom.
+pub const BOTTOM_THRESHOLD_PX: f64 = 24.0;
+// How close to the top of the list asks the server for older messages.
+pub const HISTORY_THRESHOLD_PX: f64 = 48.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScrollPosition {
+    // Follows new messages as they arrive.
+    Bottom,
+    Offset(f64),
+}
+
+pub fn scroll_position(scroll_top: f64, scroll_height: f64, client_height: f64) -> ScrollPosition {
+    if scroll_height - client_height - scroll_top <= BOTTOM_THRESHOLD_PX {
+        ScrollPosition::Bottom
+    } else {
+        ScrollPosition::Offset(scroll_top)
+    }
+}
+
+// Where to scroll after older messages were added above the ones on screen,
+// so the same message stays under the reader's eye: everything moved down by
+// however much taller the list got.
+pub fn prepended_scroll_top(old_top: f64, old_height: f64, new_height: f64) -> f64 {
+    old_top + (new_height - old_height).max(0.0)
+}
+
+// Where each conversation was scrolled to when it was last left, so coming
+// back doesn't always jump to the newest message.
+#[derive(Debug, Default)]
+pub struct ScrollMemory {
+    saved: HashMap<ConversationId, ScrollPosition>,
+}
+
+impl ScrollMemory {
+    pub fn save(&mut self, conversation: &str, position: ScrollPosition) {
+        self.saved.insert(conversation.to_string(), position);
+    }
+
+    // Conversations that were never opened start at the bottom.
+    pub fn restore(&self, conversation: &str) -> ScrollPosition {
+        self.saved
+            .get(conversation)
+            .copied()
+            .unwrap_or(ScrollPosition::Bottom)
+    }
+}
+
+// Lets an action through at most once per `interval_ms`.
+#[derive(Debug)]
+pub struct Throttle {
+    interval_ms: f64,
+    last: Option<f64>,
+}
+
+impl Throttle {
+    pub fn new(interval_ms: u32) -> Self {
+        Self {
+            interval_ms: interval_ms as f64,
+            last: None,
+        }
+    }
+
+    // Whether the action may run at `now`. Records the run if so.
+    pub fn ready(&mut self, now: f64) -> bool {
+        match self.last {
+            Some(last) if now - last < self.interval_ms => false,
+            _ => {
+                self.last = Some(now);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unopened_conversations_start_at_the_bottom() {
+        let memory = ScrollMemory::default();
+        assert_eq!(memory.restore("general"), ScrollPosition::Bottom);
+    }
+
+    #[test]
+    fn restores_each_conversations_own_position() {
+        let mut memory = ScrollMemory::default();
+        memory.save("general", ScrollPosition::Offset(120.0));
+        memory.save("random", ScrollPosition::Bottom);
+        assert_eq!(memory.restore("general"), ScrollPosition::Offset(120.0));
+        assert_eq!(memory.restore("random"), ScrollPosition::Bottom);
+
+        memory.save("general", ScrollPosition::Offset(40.0));
+        assert_eq!(memory.restore("general"), ScrollPosition::Offset(40.0));
+    }
+
+    #[test]
+    fn near_the_end_counts_as_the_bottom() {
+        assert_eq!(scroll_position(476.0, 1000.0, 500.0), ScrollPosition::Bottom);
+        assert_eq!(scroll_position(400.0, 1000.0, 500.0), ScrollPosition::Offset(400.0));
+    }
+
+    #[test]
+    fn prepending_keeps_the_same_message_in_view() {
+        assert_eq!(prepended_scroll_top(10.0, 1000.0, 1600.0), 610.0);
+        assert_eq!(prepended_scroll_top(10.0, 1000.0, 900.0), 10.0);
+    }
+
+    #[test]
+    fn throttle_lets_one_run_through_per_interval() {
+        let mut throttle = Throttle::new(100);
+        assert!(throttle.ready(0.0));
+        assert!(!throttle.ready(99.0));
+        assert!(throttle.ready(100.0));
+    }
+}
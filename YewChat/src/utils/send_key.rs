@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+
+// Which key combination sends the message. The other inserts a newline.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum SendKey {
+    #[default]
+    Enter,
+    ModEnter,
+}
+
+impl SendKey {
+    pub fn toggled(self) -> Self {
+        match self {
+            SendKey::Enter => SendKey::ModEnter,
+            SendKey::ModEnter => SendKey::Enter,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SendKey::Enter => "Enter",
+            SendKey::ModEnter => "Ctrl+Enter",
+        }
+    }
+}
+
+// Whether a keydown in the composer sends the message. Shift+Enter always
+// inserts a newline; `modifier` is Ctrl, or Cmd on a Mac.
+pub fn is_send(key: &str, shift: bool, modifier: bool, mode: SendKey) -> bool {
+    if key != "Enter" || shift {
+        return false;
+    }
+    match mode {
+        SendKey::Enter => true,
+        SendKey::ModEnter => modifier,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enter_mode_sends_on_plain_enter() {
+        assert!(is_send("Enter", false, false, SendKey::Enter));
+        assert!(is_send("Enter", false, true, SendKey::Enter));
+        assert!(!is_send("Enter", true, false, SendKey::Enter));
+        assert!(!is_send("a", false, false, SendKey::Enter));
+    }
+
+    #[test]
+    fn mod_enter_mode_needs_the_modifier() {
+        assert!(!is_send("Enter", false, false, SendKey::ModEnter));
+        assert!(is_send("Enter", false, true, SendKey::ModEnter));
+        assert!(!is_send("Enter", true, true, SendKey::ModEnter));
+    }
+}
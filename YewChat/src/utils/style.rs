@@ -0,0 +1,246 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Density {
+    #[default]
+    Cozy,
+    Compact,
+}
+
+impl Density {
+    pub fn toggled(self) -> Self {
+        match self {
+            Density::Cozy => Density::Compact,
+            Density::Compact => Density::Cozy,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Density::Cozy => "Cozy",
+            Density::Compact => "Compact",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum AvatarShape {
+    #[default]
+    Circle,
+    Square,
+}
+
+impl AvatarShape {
+    pub fn toggled(self) -> Self {
+        match self {
+            AvatarShape::Circle => AvatarShape::Square,
+            AvatarShape::Square => AvatarShape::Circle,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AvatarShape::Circle => "Circle",
+            AvatarShape::Square => "Rounded square",
+        }
+    }
+}
+
+// The corner rounding every avatar image gets.
+pub fn avatar_class(shape: AvatarShape) -> &'static str {
+    match shape {
+        AvatarShape::Circle => "rounded-full",
+        AvatarShape::Square => "rounded-lg",
+    }
+}
+
+// Viewports narrower than this (Tailwind's `md`) get the sidebar as a drawer
+// over the conversation instead of a column beside it.
+pub const NARROW_VIEWPORT_PX: f64 = 768.0;
+
+pub fn is_narrow(viewport_width: f64) -> bool {
+    viewport_width < NARROW_VIEWPORT_PX
+}
+
+// The users sidebar: a column, a drawer on narrow screens, or nothing when
+// hidden so the conversation gets the full width.
+pub fn sidebar_class(visible: bool, narrow: bool) -> &'static str {
+    match (visible, narrow) {
+        (false, _) => "hidden",
+        (true, false) => "flex-none w-56 h-screen overflow-y-auto bg-gray-100",
+        (true, true) => "fixed inset-y-0 left-0 z-40 w-64 h-screen overflow-y-auto bg-gray-100 shadow-lg",
+    }
+}
+
+// Tailwind classes for the parts of a message that change with density.
+pub struct DensityClasses {
+    pub bubble: &'static str,
+    pub body: &'static str,
+    pub avatar: &'static str,
+}
+
+pub fn density_classes(density: Density) -> DensityClasses {
+    match density {
+        Density::Cozy => DensityClasses {
+            bubble: "m-8",
+            body: "p-3",
+            avatar: "w-8 h-8 m-3",
+        },
+        Density::Compact => DensityClasses {
+            bubble: "mx-8 my-2",
+            body: "px-3 py-1",
+            avatar: "w-6 h-6 m-2",
+        },
+    }
+}
+
+// Layout for a message bubble. The current user's own messages sit on the
+// right with the avatar after the text and the flat corner flipped.
+pub struct BubbleClasses {
+    pub row: &'static str,
+    pub bubble: &'static str,
+}
+
+pub fn bubble_classes(is_own: bool) -> BubbleClasses {
+    if is_own {
+        BubbleClasses {
+            row: "flex justify-end",
+            bubble: "flex-row-reverse bg-blue-100 rounded-tl-lg rounded-tr-lg rounded-bl-lg",
+        }
+    } else {
+        BubbleClasses {
+            row: "flex",
+            bubble: "bg-gray-100 rounded-tl-lg rounded-tr-lg rounded-br-lg",
+        }
+    }
+}
+
+// Height the emoji picker is capped at (Tailwind `max-h-48`); it scrolls
+// internally beyond that.
+pub const PICKER_MAX_HEIGHT: f64 = 192.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Placement {
+    Above,
+    Below,
+}
+
+impl Placement {
+    // Positions the picker relative to the composer row.
+    pub fn picker_class(self) -> &'static str {
+        match self {
+            // Measured from the composer row, which grows with its input.
+            Placement::Above => "bottom-full mb-2",
+            Placement::Below => "top-full mt-2",
+        }
+    }
+}
+
+// Opens a popup above the input when it fits there, otherwise below when
+// it fits there, otherwise on whichever side has more room.
+pub fn picker_placement(
+    viewport_height: f64,
+    input_top: f64,
+    input_bottom: f64,
+    picker_height: f64,
+) -> Placement {
+    let above = input_top;
+    let below = viewport_height - input_bottom;
+    if above >= picker_height {
+        Placement::Above
+    } else if below >= picker_height || below > above {
+        Placement::Below
+    } else {
+        Placement::Above
+    }
+}
+
+// Tallest the message input grows before it scrolls instead.
+pub const COMPOSER_MAX_HEIGHT_PX: f64 = 120.0;
+
+// Height for the message input given the height its content needs, and
+// whether it has to scroll to show the rest.
+pub fn composer_height(content_height: f64, max_height: f64) -> (f64, bool) {
+    if content_height > max_height {
+        (max_height, true)
+    } else {
+        (content_height, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compact_density_tightens_the_spacing() {
+        let cozy = density_classes(Density::Cozy);
+        let compact = density_classes(Density::Compact);
+        assert_eq!(cozy.bubble, "m-8");
+        assert_eq!(compact.bubble, "mx-8 my-2");
+        assert_eq!(compact.body, "px-3 py-1");
+        assert_eq!(compact.avatar, "w-6 h-6 m-2");
+        assert_eq!(Density::default().toggled(), Density::Compact);
+        assert_eq!(Density::Compact.toggled(), Density::Cozy);
+    }
+
+    #[test]
+    fn own_messages_sit_on_the_right() {
+        let own = bubble_classes(true);
+        assert!(own.row.contains("justify-end"));
+        assert!(own.bubble.contains("flex-row-reverse"));
+        assert!(own.bubble.contains("rounded-bl-lg"));
+
+        let other = bubble_classes(false);
+        assert!(!other.row.contains("justify-end"));
+        assert!(!other.bubble.contains("flex-row-reverse"));
+        assert!(other.bubble.contains("rounded-br-lg"));
+    }
+
+    #[test]
+    fn picker_opens_above_when_it_fits() {
+        assert_eq!(picker_placement(800.0, 600.0, 640.0, 192.0), Placement::Above);
+        assert_eq!(picker_placement(800.0, 192.0, 232.0, 192.0), Placement::Above);
+    }
+
+    #[test]
+    fn picker_opens_below_when_only_that_fits() {
+        assert_eq!(picker_placement(800.0, 100.0, 140.0, 192.0), Placement::Below);
+    }
+
+    #[test]
+    fn picker_takes_the_roomier_side_when_neither_fits() {
+        assert_eq!(picker_placement(300.0, 100.0, 140.0, 192.0), Placement::Below);
+        assert_eq!(picker_placement(300.0, 150.0, 190.0, 192.0), Placement::Above);
+    }
+
+    #[test]
+    fn avatar_shape_picks_the_rounding() {
+        assert_eq!(avatar_class(AvatarShape::Circle), "rounded-full");
+        assert_eq!(avatar_class(AvatarShape::Square), "rounded-lg");
+        assert_eq!(AvatarShape::default().toggled(), AvatarShape::Square);
+        assert_eq!(AvatarShape::Square.toggled(), AvatarShape::Circle);
+    }
+
+    #[test]
+    fn focus_mode_hides_the_sidebar() {
+        assert_eq!(sidebar_class(false, false), "hidden");
+        assert_eq!(sidebar_class(false, true), "hidden");
+        assert_ne!(sidebar_class(true, false), "hidden");
+    }
+
+    #[test]
+    fn narrow_viewports_get_a_drawer() {
+        assert!(is_narrow(767.0));
+        assert!(!is_narrow(768.0));
+        assert!(sidebar_class(true, true).contains("fixed"));
+        assert!(!sidebar_class(true, false).contains("fixed"));
+    }
+
+    #[test]
+    fn composer_grows_until_the_cap_then_scrolls() {
+        assert_eq!(composer_height(40.0, COMPOSER_MAX_HEIGHT_PX), (40.0, false));
+        assert_eq!(composer_height(120.0, 120.0), (120.0, false));
+        assert_eq!(composer_height(300.0, 120.0), (120.0, true));
+    }
+}
@@ -0,0 +1,428 @@
+use crate::config::MAX_MESSAGE_LENGTH;
+
+pub fn quote_lines(text: &str) -> String {
+    text.lines()
+        .map(|line| format!("> {}", line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Composer prefill for quoting part of someone's message.
+pub fn format_quote(from: &str, text: &str) -> String {
+    format!("{} wrote:\n{}\n", from, quote_lines(text.trim()))
+}
+
+// Trims the message and collapses any run of blank lines down to one.
+// Returns None when nothing but whitespace was typed.
+pub fn normalize_message(text: &str) -> Option<String> {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+
+    let mut lines = Vec::new();
+    let mut blank_run = 0;
+    for line in trimmed.lines() {
+        if line.trim().is_empty() {
+            blank_run += 1;
+            if blank_run > 1 {
+                continue;
+            }
+            lines.push("");
+        } else {
+            blank_run = 0;
+            lines.push(line);
+        }
+    }
+    Some(lines.join("\n"))
+}
+
+pub fn should_collapse(text: &str, max_lines: usize, max_chars: usize) -> bool {
+    text.lines().count() > max_lines || text.chars().count() > max_chars
+}
+
+// The start of a long message: at most `max_lines` lines and `max_chars`
+// characters, with an ellipsis when anything was cut.
+pub fn collapsed_preview(text: &str, max_lines: usize, max_chars: usize) -> String {
+    let mut preview: String = text
+        .lines()
+        .take(max_lines)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .chars()
+        .take(max_chars)
+        .collect();
+    // Line endings are normalized the same way as the preview's, so CRLF text
+    // isn't mistaken for having been cut.
+    let full = text.lines().collect::<Vec<_>>().join("\n");
+    if preview.len() < full.trim_end().len() {
+        preview.truncate(preview.trim_end().len());
+        preview.push('…');
+    }
+    preview
+}
+
+// Cuts `text` to at most `max_chars` characters, ending in an ellipsis when
+// anything was removed. Returns whether it was cut.
+pub fn truncate_chars(text: &mut String, max_chars: usize) -> bool {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => {
+            let end = text[..end].char_indices().last().map_or(0, |(i, _)| i);
+            text.truncate(end);
+            text.push('…');
+            true
+        }
+        None => false,
+    }
+}
+
+// A safe link whose path ends in a common image extension, so it can be
+// shown inline. Query strings and fragments are ignored.
+pub fn is_image_url(text: &str) -> bool {
+    let path = text.split(['?', '#']).next().unwrap_or_default();
+    let path = path.to_ascii_lowercase();
+    is_safe_url(text)
+        && [".gif", ".png", ".jpg", ".jpeg", ".webp"]
+            .iter()
+            .any(|ext| path.ends_with(ext))
+}
+
+pub fn first_url(text: &str) -> Option<&str> {
+    text.split_whitespace().find(|word| is_safe_url(word))
+}
+
+// Only plain web links may become clickable or be loaded. Anything else,
+// `javascript:` and `data:` included, stays text.
+pub fn is_safe_url(url: &str) -> bool {
+    let scheme = match url.split_once("://") {
+        Some((scheme, rest)) if !rest.is_empty() => scheme,
+        _ => return false,
+    };
+    let scheme = scheme.to_ascii_lowercase();
+    (scheme == "http" || scheme == "https") && !url.chars().any(|c| c.is_control())
+}
+
+// What text may link to: safe web links, plus `mailto:` addresses, which
+// open a mail client but are never loaded.
+pub fn is_linkable_url(url: &str) -> bool {
+    let is_mailto = url.len() > "mailto:".len()
+        && url.is_char_boundary("mailto:".len())
+        && url[.."mailto:".len()].eq_ignore_ascii_case("mailto:")
+        && !url.chars().any(|c| c.is_control());
+    is_mailto || is_safe_url(url)
+}
+
+// Byte ranges of every case-insensitive, non-overlapping match of `query`.
+pub fn match_ranges(text: &str, query: &str) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    if query.is_empty() {
+        return ranges;
+    }
+
+    let mut indices = text.char_indices();
+    while let Some((start, _)) = indices.next() {
+        if let Some(end) = match_at(&text[start..], query) {
+            ranges.push((start, start + end));
+            // Skip past the match so matches don't overlap.
+            while indices.as_str().len() > text.len() - start - end {
+                indices.next();
+            }
+        }
+    }
+    ranges
+}
+
+// Length in bytes of `text`'s prefix matching `query`, ignoring case.
+fn match_at(text: &str, query: &str) -> Option<usize> {
+    let mut text_chars = text.char_indices();
+    for q in query.chars() {
+        let (_, t) = text_chars.next()?;
+        if !t.to_lowercase().eq(q.to_lowercase()) {
+            return None;
+        }
+    }
+    Some(text_chars.next().map(|(i, _)| i).unwrap_or(text.len()))
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SpoilerPart<'a> {
+    Plain(&'a str),
+    Spoiler(&'a str),
+}
+
+// Splits out `||hidden||` spoilers. An opening `||` without a closing one,
+// or an empty pair, is left as plain text.
+pub fn split_spoilers(text: &str) -> Vec<SpoilerPart<'_>> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+
+    while let Some(open) = rest.find("||") {
+        let after = &rest[open + 2..];
+        let close = match after.find("||") {
+            Some(close) if close > 0 => close,
+            Some(_) => {
+                // "||||": nothing to hide, keep it as text.
+                parts.push(SpoilerPart::Plain(&rest[..open + 4]));
+                rest = &rest[open + 4..];
+                continue;
+            }
+            None => break,
+        };
+        if open > 0 {
+            parts.push(SpoilerPart::Plain(&rest[..open]));
+        }
+        parts.push(SpoilerPart::Spoiler(&after[..close]));
+        rest = &after[close + 2..];
+    }
+    if !rest.is_empty() {
+        parts.push(SpoilerPart::Plain(rest));
+    }
+    parts
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Segment<'a> {
+    Text(&'a str),
+    Link(&'a str),
+}
+
+// Splits a message into text and linkable URLs, keeping the whitespace between
+// words. Trailing punctuation is left out of a link so "see https://x.org."
+// doesn't link the full stop.
+pub fn linkify(text: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut text_start = 0;
+    let mut offset = 0;
+
+    for word in text.split_inclusive(char::is_whitespace) {
+        let start = offset;
+        offset += word.len();
+        let link = word
+            .trim_end()
+            .trim_end_matches(['.', ',', ';', ':', '!', '?', ')']);
+        if !is_linkable_url(link) {
+            continue;
+        }
+        if text_start < start {
+            segments.push(Segment::Text(&text[text_start..start]));
+        }
+        segments.push(Segment::Link(link));
+        text_start = start + link.len();
+    }
+    if text_start < text.len() {
+        segments.push(Segment::Text(&text[text_start..]));
+    }
+    segments
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MessageStats {
+    pub chars: usize,
+    pub words: usize,
+    // Negative once the message is over the limit.
+    pub remaining: i64,
+}
+
+pub fn message_stats(text: &str) -> MessageStats {
+    let chars = text.chars().count();
+    MessageStats {
+        chars,
+        words: text.split_whitespace().count(),
+        remaining: MAX_MESSAGE_LENGTH as i64 - chars as i64,
+    }
+}
+
+pub struct CodeBlock {
+    pub language: Option<String>,
+    pub body: String,
+}
+
+// Splits a message made of a single ``` fenced block into its language tag
+// and body. Whitespace inside the body is kept exactly as sent.
+pub fn parse_fenced(text: &str) -> Option<CodeBlock> {
+    let inner = text.strip_prefix("```")?.strip_suffix("```")?;
+    let (first_line, body) = inner.split_once('\n')?;
+    let language = first_line.trim();
+    let body = body.strip_suffix('\n').unwrap_or(body);
+    Some(CodeBlock {
+        language: if language.is_empty() {
+            None
+        } else {
+            Some(language.to_string())
+        },
+        body: body.to_string(),
+    })
+}
+
+// Wraps composer text in a fence for the code composer mode. Unlike
+// `normalize_message` the body is left untouched apart from trailing newlines.
+pub fn wrap_code(body: &str, language: &str) -> Option<String> {
+    if body.trim().is_empty() {
+        return None;
+    }
+    Some(format!(
+        "```{}\n{}\n```",
+        language.trim(),
+        body.trim_end_matches('\n')
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizing_trims_and_collapses_blank_lines() {
+        assert_eq!(normalize_message("  hi  ").as_deref(), Some("hi"));
+        assert_eq!(normalize_message("a\n\n\n\nb").as_deref(), Some("a\n\nb"));
+        assert_eq!(normalize_message("a\n  \n\t\nb").as_deref(), Some("a\n\nb"));
+        assert_eq!(normalize_message(" \n\t "), None);
+    }
+
+    #[test]
+    fn quotes_name_the_author_and_prefix_each_line() {
+        assert_eq!(format_quote("alice", "  one\ntwo \n"), "alice wrote:\n> one\n> two\n");
+        assert_eq!(quote_lines("a\nb"), "> a\n> b");
+    }
+
+    #[test]
+    fn stats_count_chars_words_and_what_is_left() {
+        let stats = message_stats("héllo  wörld");
+        assert_eq!(stats.chars, 12);
+        assert_eq!(stats.words, 2);
+        assert_eq!(stats.remaining, MAX_MESSAGE_LENGTH as i64 - 12);
+
+        let over = "x".repeat(MAX_MESSAGE_LENGTH + 3);
+        assert_eq!(message_stats(&over).remaining, -3);
+    }
+
+    #[test]
+    fn fenced_blocks_split_into_language_and_body() {
+        let block = parse_fenced("```rust\nfn main() {\n    42\n}\n```").unwrap();
+        assert_eq!(block.language.as_deref(), Some("rust"));
+        assert_eq!(block.body, "fn main() {\n    42\n}");
+
+        let block = parse_fenced("```\n  indented\n```").unwrap();
+        assert_eq!(block.language, None);
+        assert_eq!(block.body, "  indented");
+    }
+
+    #[test]
+    fn text_around_a_fence_isnt_a_code_block() {
+        assert!(parse_fenced("see ```\ncode\n```").is_none());
+        assert!(parse_fenced("```code```").is_none());
+        assert!(parse_fenced("```\nunclosed").is_none());
+    }
+
+    #[test]
+    fn splits_out_spoilers() {
+        assert_eq!(
+            split_spoilers("the end: ||he lives|| ok"),
+            [
+                SpoilerPart::Plain("the end: "),
+                SpoilerPart::Spoiler("he lives"),
+                SpoilerPart::Plain(" ok"),
+            ]
+        );
+        assert_eq!(split_spoilers("||a||||b||"), [SpoilerPart::Spoiler("a"), SpoilerPart::Spoiler("b")]);
+    }
+
+    #[test]
+    fn unclosed_and_empty_spoilers_stay_text() {
+        assert_eq!(split_spoilers("a || b"), [SpoilerPart::Plain("a || b")]);
+        assert_eq!(split_spoilers("a |||| b"), [SpoilerPart::Plain("a ||||"), SpoilerPart::Plain(" b")]);
+    }
+
+    #[test]
+    fn matches_ignore_case_and_dont_overlap() {
+        assert_eq!(match_ranges("Hello hello", "HELLO"), [(0, 5), (6, 11)]);
+        assert_eq!(match_ranges("aaaa", "aa"), [(0, 2), (2, 4)]);
+        assert_eq!(match_ranges("abc", ""), []);
+        assert_eq!(match_ranges("abc", "x"), []);
+    }
+
+    #[test]
+    fn match_ranges_are_byte_offsets() {
+        let text = "Grüße, GRÜSSE";
+        let ranges = match_ranges(text, "grü");
+        assert_eq!(ranges, [(0, 4), (9, 13)]);
+        assert_eq!(&text[ranges[1].0..ranges[1].1], "GRÜ");
+    }
+
+    #[test]
+    fn only_web_links_are_safe() {
+        assert!(is_safe_url("http://example.com"));
+        assert!(is_safe_url("HTTPS://example.com/a?b#c"));
+        assert!(!is_safe_url("javascript:alert(1)"));
+        assert!(!is_safe_url("javascript://alert(1)"));
+        assert!(!is_safe_url("data:text/html,hi"));
+        assert!(!is_safe_url("mailto:alice@example.com"));
+        assert!(!is_safe_url("https://"));
+        assert!(!is_safe_url("https://exa\nmple.com"));
+    }
+
+    #[test]
+    fn mailto_is_linkable_but_scripts_are_not() {
+        assert!(is_linkable_url("mailto:alice@example.com"));
+        assert!(is_linkable_url("MAILTO:alice@example.com"));
+        assert!(is_linkable_url("https://example.com"));
+        assert!(!is_linkable_url("mailto:"));
+        assert!(!is_linkable_url("javascript:alert(1)"));
+    }
+
+    #[test]
+    fn javascript_urls_stay_plain_text() {
+        assert_eq!(
+            linkify("click javascript:alert(1) now"),
+            [Segment::Text("click javascript:alert(1) now")]
+        );
+    }
+
+    #[test]
+    fn links_are_split_out_of_the_text() {
+        assert_eq!(
+            linkify("see http://a.org and https://b.org/x."),
+            [
+                Segment::Text("see "),
+                Segment::Link("http://a.org"),
+                Segment::Text(" and "),
+                Segment::Link("https://b.org/x"),
+                Segment::Text("."),
+            ]
+        );
+        assert_eq!(
+            linkify("mail mailto:bob@example.com, thanks"),
+            [
+                Segment::Text("mail "),
+                Segment::Link("mailto:bob@example.com"),
+                Segment::Text(", thanks"),
+            ]
+        );
+    }
+
+    #[test]
+    fn collapses_on_too_many_lines_or_chars() {
+        assert!(!should_collapse("a\nb", 2, 10));
+        assert!(should_collapse("a\nb\nc", 2, 10));
+        assert!(should_collapse("abcdefghijk", 2, 10));
+    }
+
+    #[test]
+    fn preview_cuts_to_the_limits_with_an_ellipsis() {
+        assert_eq!(collapsed_preview("a\nb\nc", 2, 100), "a\nb…");
+        assert_eq!(collapsed_preview("abcdef", 5, 3), "abc…");
+        assert_eq!(collapsed_preview("one two", 5, 4), "one…");
+    }
+
+    #[test]
+    fn preview_of_text_that_fits_is_unchanged() {
+        assert_eq!(collapsed_preview("a\nb", 2, 100), "a\nb");
+        assert_eq!(collapsed_preview("a\nb\n\n", 2, 100), "a\nb");
+    }
+
+    #[test]
+    fn crlf_text_that_fits_gets_no_ellipsis() {
+        assert_eq!(collapsed_preview("a\r\nb\r\n", 2, 100), "a\nb");
+        assert_eq!(collapsed_preview("a\r\nb\r\nc", 2, 100), "a\nb…");
+    }
+}
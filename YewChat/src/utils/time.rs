@@ -0,0 +1,546 @@
+use js_sys::{Array, Date, Intl, Object, Reflect};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::JsValue;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TimeFormat {
+    Hour12,
+    Hour24,
+}
+
+impl TimeFormat {
+    // Follows whatever clock the browser locale uses by default.
+    #[cfg(target_arch = "wasm32")]
+    pub fn from_locale() -> Self {
+        let options = Object::new();
+        Reflect::set(&options, &"hour".into(), &"numeric".into()).ok();
+        let resolved = Intl::DateTimeFormat::new(&Array::new(), &options).resolved_options();
+        match Reflect::get(&resolved, &"hour12".into()).ok().and_then(|v| v.as_bool()) {
+            Some(true) => TimeFormat::Hour12,
+            _ => TimeFormat::Hour24,
+        }
+    }
+
+    // There is no browser locale to ask off the web, e.g. in unit tests.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_locale() -> Self {
+        TimeFormat::Hour24
+    }
+
+    pub fn toggled(self) -> Self {
+        match self {
+            TimeFormat::Hour12 => TimeFormat::Hour24,
+            TimeFormat::Hour24 => TimeFormat::Hour12,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TimeFormat::Hour12 => "12h",
+            TimeFormat::Hour24 => "24h",
+        }
+    }
+}
+
+// Whether message headers show their time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum TimestampMode {
+    #[default]
+    Always,
+    OnHover,
+    Hidden,
+}
+
+impl TimestampMode {
+    pub fn cycled(self) -> Self {
+        match self {
+            TimestampMode::Always => TimestampMode::OnHover,
+            TimestampMode::OnHover => TimestampMode::Hidden,
+            TimestampMode::Hidden => TimestampMode::Always,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TimestampMode::Always => "Always",
+            TimestampMode::OnHover => "On hover",
+            TimestampMode::Hidden => "Hidden",
+        }
+    }
+}
+
+// Classes for the time in a message header. Hovering relies on the message
+// bubble being a Tailwind `group`.
+pub fn timestamp_class(mode: TimestampMode) -> &'static str {
+    match mode {
+        TimestampMode::Always => "",
+        TimestampMode::OnHover => "opacity-0 group-hover:opacity-100 transition-opacity",
+        TimestampMode::Hidden => "hidden",
+    }
+}
+
+pub fn format_clock(hours: u32, minutes: u32, format: TimeFormat) -> String {
+    clock(hours, minutes, None, format)
+}
+
+// The tooltip's longer form: `date` as the locale writes it, then the time
+// down to the second, e.g. "3/5/2024 09:41:07" or "3/5/2024 9:41:07 AM".
+pub fn format_full(date: &str, hours: u32, minutes: u32, seconds: u32, format: TimeFormat) -> String {
+    format!("{} {}", date, clock(hours, minutes, Some(seconds), format))
+}
+
+fn clock(hours: u32, minutes: u32, seconds: Option<u32>, format: TimeFormat) -> String {
+    let seconds = seconds.map(|s| format!(":{:02}", s)).unwrap_or_default();
+    match format {
+        TimeFormat::Hour24 => format!("{:02}:{:02}{}", hours, minutes, seconds),
+        TimeFormat::Hour12 => {
+            let suffix = if hours < 12 { "AM" } else { "PM" };
+            let hours = match hours % 12 {
+                0 => 12,
+                h => h,
+            };
+            format!("{}:{:02}{} {}", hours, minutes, seconds, suffix)
+        }
+    }
+}
+
+// Compact label shown inline next to the sender, e.g. "09:41" or "9:41 AM".
+pub fn format_time(epoch_ms: f64, format: TimeFormat) -> String {
+    let date = Date::new(&JsValue::from_f64(epoch_ms));
+    format_clock(date.get_hours(), date.get_minutes(), format)
+}
+
+// Full date and time down to the second, the date in the browser's locale.
+pub fn full_timestamp(epoch_ms: f64, format: TimeFormat) -> String {
+    let date = Date::new(&JsValue::from_f64(epoch_ms));
+    let day: String = date.to_locale_date_string("default", &Object::new()).into();
+    format_full(&day, date.get_hours(), date.get_minutes(), date.get_seconds(), format)
+}
+
+// Local midnight at the start of the day containing `epoch_ms`.
+fn start_of_day(epoch_ms: f64) -> f64 {
+    let date = Date::new(&JsValue::from_f64(epoch_ms));
+    date.set_hours(0);
+    date.set_minutes(0);
+    date.set_seconds(0);
+    date.set_milliseconds(0);
+    date.get_time()
+}
+
+// A span of time as "2d 3h", "1h 5m" or "12m", dropping the smallest unit
+// once days are involved.
+pub fn format_duration(ms: f64) -> String {
+    let minutes = (ms.max(0.0) / 60_000.0).floor() as u64;
+    let (days, hours, minutes) = (minutes / 1440, minutes % 1440 / 60, minutes % 60);
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+// How far the server's clock is ahead of ours, from one round trip. The
+// server read its clock at `server` somewhere between our `sent` and
+// `received`; halfway is the best guess.
+pub fn clock_offset(sent: f64, server: f64, received: f64) -> f64 {
+    server - (sent + received) / 2.0
+}
+
+const DAY_MS: f64 = 86_400_000.0;
+
+// Time left until the next midnight from a time of day, on a day without a
+// DST change. At midnight itself that's the whole day.
+pub fn ms_until_midnight_from(hours: u32, minutes: u32, seconds: u32, millis: u32) -> f64 {
+    let elapsed = ((hours * 60 + minutes) * 60 + seconds) as f64 * 1_000.0 + millis as f64;
+    DAY_MS - elapsed
+}
+
+// Time left until the next local midnight. Days that are longer or shorter
+// because of DST are corrected for by how far the UTC offset moves by then.
+pub fn ms_until_midnight(now: f64) -> f64 {
+    let date = Date::new(&JsValue::from_f64(now));
+    let naive = ms_until_midnight_from(
+        date.get_hours(),
+        date.get_minutes(),
+        date.get_seconds(),
+        date.get_milliseconds(),
+    );
+    let midnight = Date::new(&JsValue::from_f64(now + naive));
+    naive + (midnight.get_timezone_offset() - date.get_timezone_offset()) * 60_000.0
+}
+
+pub fn same_day(a: f64, b: f64) -> bool {
+    start_of_day(a) == start_of_day(b)
+}
+
+// "Today", "Yesterday", or the date in the browser's locale.
+pub fn day_label(epoch_ms: f64, now: f64) -> String {
+    if same_day(epoch_ms, now) {
+        return "Today".to_string();
+    }
+    let yesterday = Date::new(&JsValue::from_f64(start_of_day(now)));
+    yesterday.set_date(yesterday.get_date() - 1);
+    if same_day(epoch_ms, yesterday.get_time()) {
+        return "Yesterday".to_string();
+    }
+    Date::new(&JsValue::from_f64(epoch_ms))
+        .to_locale_date_string("default", &Object::new())
+        .into()
+}
+
+// Reads a server `timestamp` as epoch millis. Accepts a string of epoch
+// millis, "HH:MM" (taken as that time today, relative to `now`), ISO-8601
+// and anything else `Date.parse` understands. `None` if none of those fit.
+pub fn parse_timestamp(raw: &str, now: f64) -> Option<f64> {
+    let raw = raw.trim();
+    if !raw.is_empty() && raw.bytes().all(|b| b.is_ascii_digit()) {
+        return raw.parse().ok();
+    }
+    if let Some((hours, minutes)) = parse_clock(raw) {
+        return Some(clock_today(now, hours, minutes));
+    }
+    if let Some(iso) = parse_iso8601(raw) {
+        return Some(match iso.offset_minutes {
+            Some(offset) => iso.as_utc() - offset as f64 * 60_000.0,
+            None => local_to_epoch(&iso),
+        });
+    }
+    date_parse(raw)
+}
+
+// "HH:MM" on the local day containing `now`.
+#[cfg(target_arch = "wasm32")]
+fn clock_today(now: f64, hours: u32, minutes: u32) -> f64 {
+    let date = Date::new(&JsValue::from_f64(now));
+    date.set_hours(hours);
+    date.set_minutes(minutes);
+    date.set_seconds(0);
+    date.set_milliseconds(0);
+    date.get_time()
+}
+
+// An ISO date-time without an offset, read in the browser's time zone.
+#[cfg(target_arch = "wasm32")]
+fn local_to_epoch(iso: &IsoDateTime) -> f64 {
+    Date::new_with_year_month_day_hr_min_sec_milli(
+        iso.year,
+        iso.month as i32 - 1,
+        iso.day as i32,
+        iso.hours as i32,
+        iso.minutes as i32,
+        iso.seconds as i32,
+        iso.millis as i32,
+    )
+    .get_time()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn date_parse(raw: &str) -> Option<f64> {
+    let parsed = Date::parse(raw);
+    (!parsed.is_nan()).then_some(parsed)
+}
+
+// There is no time zone to ask off the web, e.g. in unit tests, so local
+// time is taken to be UTC and only the formats above are understood.
+#[cfg(not(target_arch = "wasm32"))]
+fn clock_today(now: f64, hours: u32, minutes: u32) -> f64 {
+    (now / DAY_MS).floor() * DAY_MS + (hours * 60 + minutes) as f64 * 60_000.0
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn local_to_epoch(iso: &IsoDateTime) -> f64 {
+    iso.as_utc()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn date_parse(_raw: &str) -> Option<f64> {
+    None
+}
+
+// An ISO-8601 date-time such as "2024-03-05T09:41:07.250+07:00". Seconds
+// and the fraction are optional; `offset_minutes` is east of UTC, and `None`
+// when the string has no offset and so means local time.
+#[derive(Debug, PartialEq)]
+pub struct IsoDateTime {
+    pub year: u32,
+    pub month: u32,
+    pub day: u32,
+    pub hours: u32,
+    pub minutes: u32,
+    pub seconds: u32,
+    pub millis: u32,
+    pub offset_minutes: Option<i32>,
+}
+
+impl IsoDateTime {
+    // Epoch millis if the wall-clock time were in UTC.
+    pub fn as_utc(&self) -> f64 {
+        let days = days_from_civil(self.year, self.month, self.day) as f64;
+        let ms = ((self.hours * 60 + self.minutes) * 60 + self.seconds) as f64 * 1_000.0 + self.millis as f64;
+        days * DAY_MS + ms
+    }
+}
+
+pub fn parse_iso8601(raw: &str) -> Option<IsoDateTime> {
+    let (date, time) = raw.split_once(['T', ' '])?;
+    let mut date = date.splitn(3, '-');
+    let year = fixed_digits(date.next()?, 4)?;
+    let month = fixed_digits(date.next()?, 2)?;
+    let day = fixed_digits(date.next()?, 2)?;
+    if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+
+    let (time, offset_minutes) = if let Some(time) = time.strip_suffix('Z') {
+        (time, Some(0))
+    } else if let Some(sign) = time.rfind(['+', '-']) {
+        (&time[..sign], Some(parse_offset(&time[sign..])?))
+    } else {
+        (time, None)
+    };
+
+    let mut time = time.splitn(3, ':');
+    let hours = fixed_digits(time.next()?, 2)?;
+    let minutes = fixed_digits(time.next()?, 2)?;
+    let (seconds, millis) = match time.next() {
+        Some(seconds) => {
+            let (seconds, fraction) = match seconds.split_once('.') {
+                Some((seconds, fraction)) => (seconds, Some(fraction)),
+                None => (seconds, None),
+            };
+            let millis = match fraction {
+                Some(fraction) => parse_millis(fraction)?,
+                None => 0,
+            };
+            (fixed_digits(seconds, 2)?, millis)
+        }
+        None => (0, 0),
+    };
+    if hours > 23 || minutes > 59 || seconds > 59 {
+        return None;
+    }
+
+    Some(IsoDateTime {
+        year,
+        month,
+        day,
+        hours,
+        minutes,
+        seconds,
+        millis,
+        offset_minutes,
+    })
+}
+
+// Exactly `len` ASCII digits.
+fn fixed_digits(s: &str, len: usize) -> Option<u32> {
+    if s.len() != len || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+// A fraction of a second, to millisecond precision.
+fn parse_millis(fraction: &str) -> Option<u32> {
+    if fraction.is_empty() || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let digits: String = fraction.chars().chain("00".chars()).take(3).collect();
+    digits.parse().ok()
+}
+
+// "+07:00", "-0530" or "+07" as minutes east of UTC.
+fn parse_offset(offset: &str) -> Option<i32> {
+    let (sign, rest) = offset.split_at(1);
+    let sign = if sign == "-" { -1 } else { 1 };
+    let rest = rest.replace(':', "");
+    if !rest.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes) = match rest.len() {
+        2 => (fixed_digits(&rest, 2)?, 0),
+        4 => (fixed_digits(&rest[..2], 2)?, fixed_digits(&rest[2..], 2)?),
+        _ => return None,
+    };
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    Some(sign * (hours * 60 + minutes) as i32)
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+    (days_from_civil(next_year, next_month, 1) - days_from_civil(year, month, 1)) as u32
+}
+
+// Days from 1970-01-01 to a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: u32, month: u32, day: u32) -> i64 {
+    let year = year as i64 - if month <= 2 { 1 } else { 0 };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+// "9:41" or "09:41" as (hours, minutes).
+pub fn parse_clock(raw: &str) -> Option<(u32, u32)> {
+    let (hours, minutes) = raw.split_once(':')?;
+    if hours.is_empty() || hours.len() > 2 || minutes.len() != 2 {
+        return None;
+    }
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+    (hours < 24 && minutes < 60).then_some((hours, minutes))
+}
+
+// Anything that goes through `Date` or `Intl` (`format_time`,
+// `full_timestamp`, `ms_until_midnight`, `day_label`) needs a browser, so
+// only the arithmetic around it is tested here.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_in_24h() {
+        assert_eq!(format_clock(0, 5, TimeFormat::Hour24), "00:05");
+        assert_eq!(format_clock(9, 41, TimeFormat::Hour24), "09:41");
+        assert_eq!(format_clock(23, 59, TimeFormat::Hour24), "23:59");
+    }
+
+    #[test]
+    fn clock_in_12h_around_noon_and_midnight() {
+        assert_eq!(format_clock(0, 0, TimeFormat::Hour12), "12:00 AM");
+        assert_eq!(format_clock(0, 30, TimeFormat::Hour12), "12:30 AM");
+        assert_eq!(format_clock(11, 59, TimeFormat::Hour12), "11:59 AM");
+        assert_eq!(format_clock(12, 0, TimeFormat::Hour12), "12:00 PM");
+        assert_eq!(format_clock(13, 5, TimeFormat::Hour12), "1:05 PM");
+        assert_eq!(format_clock(23, 59, TimeFormat::Hour12), "11:59 PM");
+    }
+
+    #[test]
+    fn durations_keep_the_two_largest_units() {
+        assert_eq!(format_duration(0.0), "0m");
+        assert_eq!(format_duration(59_999.0), "0m");
+        assert_eq!(format_duration(12.0 * 60_000.0), "12m");
+        assert_eq!(format_duration(65.0 * 60_000.0), "1h 5m");
+        assert_eq!(format_duration((2.0 * 1440.0 + 3.0 * 60.0 + 7.0) * 60_000.0), "2d 3h");
+        assert_eq!(format_duration(-5_000.0), "0m");
+    }
+
+    #[test]
+    fn parses_clock_times() {
+        assert_eq!(parse_clock("9:41"), Some((9, 41)));
+        assert_eq!(parse_clock("09:41"), Some((9, 41)));
+        assert_eq!(parse_clock("23:59"), Some((23, 59)));
+        assert_eq!(parse_clock("24:00"), None);
+        assert_eq!(parse_clock("12:60"), None);
+        assert_eq!(parse_clock("12:5"), None);
+        assert_eq!(parse_clock("123:45"), None);
+        assert_eq!(parse_clock(":45"), None);
+        assert_eq!(parse_clock("noon"), None);
+    }
+
+    #[test]
+    fn epoch_millis_timestamps_are_taken_as_is() {
+        assert_eq!(parse_timestamp("1700000000000", 0.0), Some(1_700_000_000_000.0));
+        assert_eq!(parse_timestamp(" 42 ", 0.0), Some(42.0));
+    }
+
+    #[test]
+    fn clock_offset_assumes_the_server_answered_halfway() {
+        assert_eq!(clock_offset(1_000.0, 5_100.0, 1_200.0), 4_000.0);
+        assert_eq!(clock_offset(1_000.0, 1_100.0, 1_200.0), 0.0);
+    }
+
+    #[test]
+    fn settings_cycle_through_every_choice() {
+        assert_eq!(TimeFormat::Hour12.toggled(), TimeFormat::Hour24);
+        assert_eq!(TimeFormat::Hour24.toggled(), TimeFormat::Hour12);
+        let mode = TimestampMode::default();
+        assert_eq!(mode.cycled().cycled().cycled(), mode);
+    }
+
+    #[test]
+    fn timestamp_class_for_every_mode() {
+        assert_eq!(TimestampMode::default(), TimestampMode::Always);
+        assert_eq!(timestamp_class(TimestampMode::Always), "");
+        assert_eq!(timestamp_class(TimestampMode::OnHover), "opacity-0 group-hover:opacity-100 transition-opacity");
+        assert_eq!(timestamp_class(TimestampMode::Hidden), "hidden");
+    }
+
+    #[test]
+    fn midnight_is_one_millisecond_after_the_last_one_of_the_day() {
+        assert_eq!(ms_until_midnight_from(23, 59, 59, 999), 1.0);
+    }
+
+    #[test]
+    fn at_midnight_the_next_one_is_a_whole_day_away() {
+        assert_eq!(ms_until_midnight_from(0, 0, 0, 0), DAY_MS);
+    }
+
+    #[test]
+    fn at_midday_midnight_is_half_a_day_away() {
+        assert_eq!(ms_until_midnight_from(12, 0, 0, 0), DAY_MS / 2.0);
+        assert_eq!(ms_until_midnight_from(12, 30, 15, 500), 41_384_500.0);
+    }
+
+    #[test]
+    fn tooltip_adds_the_date_and_seconds_to_the_inline_time() {
+        assert_eq!(format_clock(9, 41, TimeFormat::Hour24), "09:41");
+        assert_eq!(format_full("3/5/2024", 9, 41, 7, TimeFormat::Hour24), "3/5/2024 09:41:07");
+
+        assert_eq!(format_clock(13, 5, TimeFormat::Hour12), "1:05 PM");
+        assert_eq!(format_full("3/5/2024", 13, 5, 9, TimeFormat::Hour12), "3/5/2024 1:05:09 PM");
+        assert_eq!(format_full("3/5/2024", 0, 0, 0, TimeFormat::Hour12), "3/5/2024 12:00:00 AM");
+    }
+
+    #[test]
+    fn iso_timestamps_with_an_offset_are_exact() {
+        assert_eq!(parse_timestamp("2023-11-14T22:13:20Z", 0.0), Some(1_700_000_000_000.0));
+        assert_eq!(parse_timestamp("2023-11-14T22:13:20.5Z", 0.0), Some(1_700_000_000_500.0));
+        assert_eq!(parse_timestamp("2023-11-15T05:13:20+07:00", 0.0), Some(1_700_000_000_000.0));
+        assert_eq!(parse_timestamp("2023-11-14T16:43:20-0530", 0.0), Some(1_700_000_000_000.0));
+        assert_eq!(parse_timestamp("1970-01-01T00:00Z", 0.0), Some(0.0));
+    }
+
+    #[test]
+    fn iso_timestamps_without_an_offset_are_local_time() {
+        let iso = parse_iso8601("2024-02-29 09:41:07.25").unwrap();
+        assert_eq!(iso.offset_minutes, None);
+        assert_eq!((iso.year, iso.month, iso.day), (2024, 2, 29));
+        assert_eq!((iso.hours, iso.minutes, iso.seconds, iso.millis), (9, 41, 7, 250));
+        // Local time is UTC off the web.
+        assert_eq!(parse_timestamp("2023-11-14T22:13:20", 0.0), Some(1_700_000_000_000.0));
+    }
+
+    #[test]
+    fn clock_timestamps_are_today() {
+        let now = 1_700_000_000_000.0; // 22:13:20 UTC
+        let midnight = 1_699_920_000_000.0;
+        assert_eq!(parse_timestamp("09:41", now), Some(midnight + (9.0 * 60.0 + 41.0) * 60_000.0));
+        assert_eq!(parse_timestamp("0:00", now), Some(midnight));
+    }
+
+    #[test]
+    fn malformed_timestamps_arent_parsed() {
+        for raw in [
+            "",
+            "yesterday",
+            "12:345",
+            "2023-13-01T00:00Z",
+            "2023-02-29T00:00Z",
+            "2023-11-14T24:00Z",
+            "2023-11-14T22:13:20.Z",
+            "2023-11-14T22:13:20+7",
+            "2023-11-14T22:13:20+1é1",
+            "2023-11-14",
+            "23-11-14T22:13Z",
+        ] {
+            assert_eq!(parse_timestamp(raw, 0.0), None, "{}", raw);
+        }
+    }
+}
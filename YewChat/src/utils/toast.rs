@@ -0,0 +1,129 @@
+use std::collections::VecDeque;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToastKind {
+    Info,
+    Error,
+}
+
+impl ToastKind {
+    pub fn classes(self) -> &'static str {
+        match self {
+            ToastKind::Info => "bg-gray-800 text-white",
+            ToastKind::Error => "bg-red-600 text-white",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Toast {
+    pub id: u32,
+    pub text: String,
+    pub kind: ToastKind,
+    // Epoch millis after which it's dismissed on its own.
+    pub expires_at: f64,
+}
+
+// Short-lived notices, oldest first. At most `cap` are kept; pushing past
+// that evicts the oldest, and each goes by itself once it expires.
+#[derive(Debug)]
+pub struct ToastQueue {
+    toasts: VecDeque<Toast>,
+    next_id: u32,
+    cap: usize,
+}
+
+impl ToastQueue {
+    pub fn new(cap: usize) -> Self {
+        Self {
+            toasts: VecDeque::new(),
+            next_id: 0,
+            cap,
+        }
+    }
+
+    // Returns the new toast's id and the ids of any toasts evicted for it.
+    pub fn push(&mut self, text: String, kind: ToastKind, expires_at: f64) -> (u32, Vec<u32>) {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.toasts.push_back(Toast {
+            id,
+            text,
+            kind,
+            expires_at,
+        });
+
+        let mut evicted = Vec::new();
+        while self.toasts.len() > self.cap {
+            if let Some(toast) = self.toasts.pop_front() {
+                evicted.push(toast.id);
+            }
+        }
+        (id, evicted)
+    }
+
+    // Returns whether the toast was still showing.
+    pub fn dismiss(&mut self, id: u32) -> bool {
+        let before = self.toasts.len();
+        self.toasts.retain(|t| t.id != id);
+        self.toasts.len() != before
+    }
+
+    // Drops every toast that has expired by `now`. Returns whether any had.
+    pub fn prune(&mut self, now: f64) -> bool {
+        let before = self.toasts.len();
+        self.toasts.retain(|t| t.expires_at > now);
+        self.toasts.len() != before
+    }
+
+    // When the next toast is due to go, if any are showing.
+    pub fn next_expiry(&self) -> Option<f64> {
+        self.toasts.iter().map(|t| t.expires_at).reduce(f64::min)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Toast> {
+        self.toasts.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pushing_past_the_cap_evicts_the_oldest() {
+        let mut queue = ToastQueue::new(2);
+        assert_eq!(queue.push(String::from("a"), ToastKind::Info, 0.0), (0, vec![]));
+        assert_eq!(queue.push(String::from("b"), ToastKind::Info, 0.0), (1, vec![]));
+        assert_eq!(queue.push(String::from("c"), ToastKind::Error, 0.0), (2, vec![0]));
+        let ids: Vec<u32> = queue.iter().map(|t| t.id).collect();
+        assert_eq!(ids, [1, 2]);
+    }
+
+    #[test]
+    fn dismissing_reports_whether_it_was_showing() {
+        let mut queue = ToastQueue::new(3);
+        let (id, _) = queue.push(String::from("a"), ToastKind::Info, 0.0);
+        assert!(queue.dismiss(id));
+        assert!(!queue.dismiss(id));
+        assert_eq!(queue.iter().count(), 0);
+    }
+
+    #[test]
+    fn expired_toasts_are_pruned() {
+        let mut queue = ToastQueue::new(3);
+        queue.push(String::from("a"), ToastKind::Info, 4_000.0);
+        queue.push(String::from("b"), ToastKind::Error, 5_000.0);
+        assert_eq!(queue.next_expiry(), Some(4_000.0));
+
+        assert!(!queue.prune(3_999.0));
+        assert!(queue.prune(4_000.0));
+        let texts: Vec<&str> = queue.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(texts, ["b"]);
+        assert_eq!(queue.next_expiry(), Some(5_000.0));
+
+        assert!(queue.prune(9_000.0));
+        assert_eq!(queue.next_expiry(), None);
+        assert!(!queue.prune(9_000.0));
+    }
+}
@@ -0,0 +1,96 @@
+// What the server was last told about whether we're typing, so only changes
+// go out. A `true` is followed by one `false` when the composer goes quiet,
+// is emptied or is sent. Typing again while that `false` is still pending
+// just cancels it (the caller drops its timer) without a second `true`.
+#[derive(Debug, Default)]
+pub struct TypingStateMachine {
+    sent: bool,
+}
+
+impl TypingStateMachine {
+    // Each of these returns the status to send, if it changed.
+    pub fn on_input(&mut self, has_text: bool) -> Option<bool> {
+        self.set(has_text)
+    }
+
+    pub fn on_idle(&mut self) -> Option<bool> {
+        self.set(false)
+    }
+
+    pub fn on_submit(&mut self) -> Option<bool> {
+        self.set(false)
+    }
+
+    // The status to send again when the server may have missed the last
+    // one, e.g. after the tab was hidden: the composer's, changed or not.
+    pub fn resync(&mut self, has_text: bool) -> bool {
+        self.on_input(has_text).unwrap_or(has_text)
+    }
+
+    pub fn is_typing(&self) -> bool {
+        self.sent
+    }
+
+    fn set(&mut self, typing: bool) -> Option<bool> {
+        if self.sent == typing {
+            return None;
+        }
+        self.sent = typing;
+        Some(typing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_changes_are_sent() {
+        let mut typing = TypingStateMachine::default();
+        assert_eq!(typing.on_input(true), Some(true));
+        assert!(typing.is_typing());
+        assert_eq!(typing.on_input(true), None);
+        assert_eq!(typing.on_idle(), Some(false));
+        assert!(!typing.is_typing());
+        assert_eq!(typing.on_idle(), None);
+    }
+
+    #[test]
+    fn emptying_the_composer_stops_typing() {
+        let mut typing = TypingStateMachine::default();
+        typing.on_input(true);
+        assert_eq!(typing.on_input(false), Some(false));
+        assert_eq!(typing.on_input(false), None);
+    }
+
+    #[test]
+    fn submitting_stops_typing_once() {
+        let mut typing = TypingStateMachine::default();
+        typing.on_input(true);
+        assert_eq!(typing.on_submit(), Some(false));
+        // Nothing is left pending for the idle timer to send.
+        assert_eq!(typing.on_idle(), None);
+        assert_eq!(typing.on_submit(), None);
+    }
+
+    #[test]
+    fn typing_again_after_going_idle_starts_over() {
+        let mut typing = TypingStateMachine::default();
+        typing.on_input(true);
+        typing.on_idle();
+        assert_eq!(typing.on_input(true), Some(true));
+    }
+
+    #[test]
+    fn resync_reports_the_composers_state() {
+        let mut typing = TypingStateMachine::default();
+        typing.on_input(true);
+        // The composer was emptied while the tab was hidden.
+        assert!(!typing.resync(false));
+        assert!(!typing.is_typing());
+        // Nothing changed, but it's sent again all the same.
+        assert!(!typing.resync(false));
+        assert!(typing.resync(true));
+        assert!(typing.resync(true));
+    }
+}
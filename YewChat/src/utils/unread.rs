@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+pub type ConversationId = String;
+
+// The room everyone joins after logging in, and where messages without a
+// channel are filed.
+pub const GENERAL: &str = "general";
+
+#[derive(Debug, Default)]
+pub struct UnreadCounts {
+    active: Option<ConversationId>,
+    counts: HashMap<ConversationId, usize>,
+}
+
+impl UnreadCounts {
+    pub fn new(active: &str) -> Self {
+        Self {
+            active: Some(active.to_string()),
+            counts: HashMap::new(),
+        }
+    }
+
+    // Counts a message for `conversation` unless it is the one being viewed.
+    // Returns whether the badge changed.
+    pub fn record(&mut self, conversation: &str) -> bool {
+        if self.active.as_deref() == Some(conversation) {
+            return false;
+        }
+        *self.counts.entry(conversation.to_string()).or_insert(0) += 1;
+        true
+    }
+
+    // Switches the active conversation and clears its badge.
+    pub fn activate(&mut self, conversation: &str) {
+        self.active = Some(conversation.to_string());
+        self.counts.remove(conversation);
+    }
+
+    pub fn active(&self) -> Option<&str> {
+        self.active.as_deref()
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.clear();
+    }
+
+    pub fn count(&self, conversation: &str) -> usize {
+        self.counts.get(conversation).copied().unwrap_or(0)
+    }
+}
+
+// Where the "New messages" divider goes: before the first message after
+// `last_read`. `None` when `last_read` isn't in the list or nothing follows it.
+pub fn divider_index(message_ids: &[Option<&str>], last_read: &str) -> Option<usize> {
+    let read = message_ids.iter().position(|id| *id == Some(last_read))?;
+    (read + 1 < message_ids.len()).then(|| read + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_messages_for_inactive_conversations() {
+        let mut unread = UnreadCounts::new(GENERAL);
+        assert!(unread.record("random"));
+        assert!(unread.record("random"));
+        assert_eq!(unread.count("random"), 2);
+    }
+
+    #[test]
+    fn ignores_messages_for_the_active_conversation() {
+        let mut unread = UnreadCounts::new(GENERAL);
+        assert!(!unread.record(GENERAL));
+        assert_eq!(unread.count(GENERAL), 0);
+    }
+
+    #[test]
+    fn activating_resets_the_count() {
+        let mut unread = UnreadCounts::new(GENERAL);
+        unread.record("random");
+        unread.activate("random");
+        assert_eq!(unread.active(), Some("random"));
+        assert_eq!(unread.count("random"), 0);
+        assert!(!unread.record("random"));
+        assert!(unread.record(GENERAL));
+        assert_eq!(unread.count(GENERAL), 1);
+    }
+
+    #[test]
+    fn divider_goes_after_the_last_read_message() {
+        let ids = [Some("a"), None, Some("b"), Some("c")];
+        assert_eq!(divider_index(&ids, "b"), Some(3));
+        assert_eq!(divider_index(&ids, "c"), None);
+        assert_eq!(divider_index(&ids, "missing"), None);
+    }
+}